@@ -0,0 +1,5 @@
+-- ReplayGain values, read from tags or measured by the loudness analysis
+ALTER TABLE tracks ADD COLUMN replay_gain_db REAL;
+ALTER TABLE tracks ADD COLUMN replay_peak REAL;
+ALTER TABLE albums ADD COLUMN replay_gain_db REAL;
+ALTER TABLE albums ADD COLUMN replay_peak REAL;
@@ -0,0 +1,9 @@
+-- Named equalizer presets, bands stored as JSON
+CREATE TABLE IF NOT EXISTS eq_presets (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL UNIQUE,
+    preamp_db REAL NOT NULL DEFAULT 0,
+    bands TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,4 @@
+-- Audible region of each track, measured by the silence analysis
+ALTER TABLE tracks ADD COLUMN silence_start_ms INTEGER;
+ALTER TABLE tracks ADD COLUMN silence_end_ms INTEGER;
+ALTER TABLE tracks ADD COLUMN silence_threshold_db REAL;
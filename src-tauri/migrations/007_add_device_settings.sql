@@ -0,0 +1,8 @@
+-- Channel balance, mono and swap per output device
+CREATE TABLE IF NOT EXISTS output_device_settings (
+    device_name TEXT PRIMARY KEY,
+    balance REAL NOT NULL DEFAULT 0,
+    mono BOOLEAN NOT NULL DEFAULT FALSE,
+    swap_channels BOOLEAN NOT NULL DEFAULT FALSE,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
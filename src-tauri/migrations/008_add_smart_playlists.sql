@@ -0,0 +1,2 @@
+-- Rules of smart playlists as JSON; NULL for playlists with manual membership
+ALTER TABLE playlists ADD COLUMN smart_rules TEXT;
@@ -0,0 +1,27 @@
+-- Key playlist entries by their own id so one track can appear several times.
+-- SQLite cannot drop a constraint, so the table is rebuilt without
+-- UNIQUE(playlist_id, track_id), closing any gaps in the positions on the way.
+CREATE TABLE IF NOT EXISTS playlist_entries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    playlist_id INTEGER NOT NULL,
+    track_id INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    added_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+    FOREIGN KEY (track_id) REFERENCES tracks(id) ON DELETE CASCADE
+);
+
+INSERT INTO playlist_entries (id, playlist_id, track_id, position, added_at)
+SELECT
+    id,
+    playlist_id,
+    track_id,
+    ROW_NUMBER() OVER (PARTITION BY playlist_id ORDER BY position, id) - 1,
+    added_at
+FROM playlist_tracks;
+
+DROP TABLE playlist_tracks;
+ALTER TABLE playlist_entries RENAME TO playlist_tracks;
+
+CREATE INDEX IF NOT EXISTS idx_playlist_tracks_playlist ON playlist_tracks(playlist_id, position);
+CREATE INDEX IF NOT EXISTS idx_playlist_tracks_track ON playlist_tracks(track_id);
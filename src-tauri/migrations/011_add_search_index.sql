@@ -0,0 +1,74 @@
+-- Full-text search over the library. Accents are folded away so "beyonce"
+-- finds "Beyoncé", and 2 and 3 character prefixes are indexed for
+-- search-as-you-type.
+CREATE VIRTUAL TABLE IF NOT EXISTS tracks_fts USING fts5(
+    title,
+    artists,
+    album,
+    album_artist,
+    genre,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+-- Kept in sync by DbHelper::upsert_track and DbHelper::delete_tracks
+INSERT INTO tracks_fts (rowid, title, artists, album, album_artist, genre)
+SELECT
+    t.id,
+    t.title,
+    (SELECT group_concat(name, ' ') FROM (
+        SELECT name FROM artists WHERE id = t.artist_id
+        UNION
+        SELECT a.name FROM track_artists ta JOIN artists a ON a.id = ta.artist_id
+        WHERE ta.track_id = t.id
+    )),
+    al.title,
+    t.album_artist,
+    t.genre
+FROM tracks t
+LEFT JOIN albums al ON t.album_id = al.id;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS artists_fts USING fts5(
+    name,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+INSERT INTO artists_fts (rowid, name)
+SELECT id, name FROM artists;
+
+CREATE VIRTUAL TABLE IF NOT EXISTS playlists_fts USING fts5(
+    name,
+    description,
+    tokenize = 'unicode61 remove_diacritics 2',
+    prefix = '2 3'
+);
+
+INSERT INTO playlists_fts (rowid, name, description)
+SELECT id, name, description FROM playlists;
+
+-- Artists and playlists live in a single table each, so triggers keep their
+-- indexes current
+CREATE TRIGGER IF NOT EXISTS artists_fts_insert AFTER INSERT ON artists BEGIN
+    INSERT INTO artists_fts (rowid, name) VALUES (new.id, new.name);
+END;
+
+CREATE TRIGGER IF NOT EXISTS artists_fts_update AFTER UPDATE OF name ON artists BEGIN
+    UPDATE artists_fts SET name = new.name WHERE rowid = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS artists_fts_delete AFTER DELETE ON artists BEGIN
+    DELETE FROM artists_fts WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_fts_insert AFTER INSERT ON playlists BEGIN
+    INSERT INTO playlists_fts (rowid, name, description) VALUES (new.id, new.name, new.description);
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_fts_update AFTER UPDATE OF name, description ON playlists BEGIN
+    UPDATE playlists_fts SET name = new.name, description = new.description WHERE rowid = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS playlists_fts_delete AFTER DELETE ON playlists BEGIN
+    DELETE FROM playlists_fts WHERE rowid = old.id;
+END;
@@ -1,27 +1,71 @@
 //! Audio Engine using FFmpeg (decoding) + CPAL (output)
 
-use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use cpal::{Stream, StreamConfig};
-use ringbuf::{
-    traits::{Consumer, Observer, Producer, Split},
-    HeapRb,
-};
+use cpal::traits::{DeviceTrait, HostTrait};
+use ringbuf::traits::{Observer, Producer};
 use serde::Serialize;
 use souvlaki::{MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig};
-use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::atomic::Ordering;
 use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
-use tauri::{AppHandle, Emitter, Manager};
-use log::{info, error};
-
-use crate::ffmpeg::{self, FFmpegProcess};
-
-const EVENT_PLAYBACK_STATE: &str = "audio-playback-state";
-const EVENT_PLAYBACK_PROGRESS: &str = "audio-playback-progress";
-const EVENT_PLAYBACK_FINISHED: &str = "audio-playback-finished";
-const EVENT_PLAYBACK_ERROR: &str = "audio-playback-error";
+use tauri::{AppHandle, Manager};
+use log::{info, warn, error};
+
+use crate::loudness::TrackLoudness;
+use crate::silence::TrackSilence;
+use crate::database::DbHelper;
+use crate::ffmpeg::AudioMetadata;
+use crate::profile::get_library_db_path;
+
+pub mod ab_loop;
+pub mod channel_mix;
+pub mod crossfade;
+pub mod decoder;
+pub mod equalizer;
+pub mod events;
+pub mod history;
+mod normalization;
+pub mod queue;
+pub mod sink;
+pub mod sleep_timer;
+mod speed;
+mod symphonia_decoder;
+pub mod visualizer;
+
+use ab_loop::{AbLoop, LoopPoint, SavedLoop};
+use channel_mix::ChannelMix;
+use crossfade::{CrossfadeSettings, FadeCurve};
+use decoder::{Decoder, DecoderBackend};
+use equalizer::{EqPreset, EqSettings, Equalizer};
+use events::EventSink;
+use history::{CompletionThreshold, HistoryWriter, PlayHistorySink, PlayTracker};
+use normalization::{Normalization, NormalizationMode};
+use queue::{PlayQueue, QueueItem, QueueSnapshot, RepeatMode};
+use sink::{CpalSink, OutputControls, OutputSink, Renderer, StreamFormat};
+use sleep_timer::{SleepTimer, SleepTimerMode, SleepTimerStatus};
+use speed::{PlaybackSpeed, SpeedMode};
+use visualizer::{Visualizer, VisualizerSettings};
+
+pub const EVENT_PLAYBACK_STATE: &str = "audio-playback-state";
+pub const EVENT_PLAYBACK_PROGRESS: &str = "audio-playback-progress";
+pub const EVENT_PLAYBACK_FINISHED: &str = "audio-playback-finished";
+pub const EVENT_PLAYBACK_ERROR: &str = "audio-playback-error";
+pub const EVENT_TRACK_CHANGED: &str = "audio-track-changed";
+pub const EVENT_QUEUE_CHANGED: &str = "audio-queue-changed";
+pub const EVENT_OUTPUT_FORMAT: &str = "audio-output-format";
+pub const EVENT_VISUALIZATION: &str = "audio-visualization";
+pub const EVENT_SLEEP_TIMER: &str = "audio-sleep-timer";
+pub const EVENT_LOOP_CHANGED: &str = "audio-loop-changed";
+
+/// Key for per-device settings while the system default device is in use
+const DEFAULT_DEVICE_KEY: &str = "default";
+
+/// Restart the current track instead of going back if we are past this point
+const PREVIOUS_RESTART_THRESHOLD_MS: u64 = 3000;
+
+/// Fade-in applied after a seek so the jump does not click
+const SEEK_FADE_IN_MS: u64 = 10;
 
 /// Playback state shared between threads
 #[derive(Debug, Clone, Serialize)]
@@ -32,6 +76,7 @@ pub struct PlaybackState {
     pub position_ms: u64,
     pub duration_ms: u64,
     pub volume: f32,
+    pub speed: f32,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -39,6 +84,39 @@ pub struct AudioDevice {
     pub name: String,
 }
 
+/// Payload describing the format the output stream was opened with
+#[derive(Debug, Clone, Serialize)]
+pub struct OutputFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+    pub source_sample_rate: u32,
+    pub source_channels: u16,
+    pub matches_source: bool,
+    pub fallback_reason: Option<String>, // Why the source format could not be used
+}
+
+/// Payload emitted when the worker switches to a pre-buffered track
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackChange {
+    pub path: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub duration_ms: u64,
+}
+
+impl TrackChange {
+    fn new(item: &QueueItem, duration_ms: u64) -> Self {
+        Self {
+            path: item.file_path.clone(),
+            title: item.title.clone(),
+            artist: item.artist.clone().unwrap_or("Unknown".into()),
+            album: item.album.clone().unwrap_or("Unknown".into()),
+            duration_ms,
+        }
+    }
+}
+
 impl Default for PlaybackState {
     fn default() -> Self {
         Self {
@@ -48,31 +126,68 @@ impl Default for PlaybackState {
             position_ms: 0,
             duration_ms: 0,
             volume: 1.0,
+            speed: 1.0,
         }
     }
 }
 
 enum AudioCommand {
-    Play {
-        path: String,
-        title: String,
-        artist: String,
-        album: String,
-        _cover: Option<String>,
+    Play(QueueItem),
+    EnqueueNext(QueueItem),
+    ClearNext,
+    SetQueue {
+        items: Vec<QueueItem>,
+        start_index: usize,
+    },
+    InsertIntoQueue {
+        items: Vec<QueueItem>,
+        index: Option<usize>,
+    },
+    MoveInQueue {
+        from: usize,
+        to: usize,
     },
+    RemoveFromQueue(usize),
+    ClearQueue,
+    JumpToQueueIndex(usize),
+    SetShuffle(bool),
+    SetRepeat(RepeatMode),
+    Next,
+    Previous,
     Pause,
     Resume,
+    Toggle,
     Stop,
     Seek(u64),
     SetVolume(f32),
     SetDevice(String),
-    SetCrossfade(u64), // Duration in milliseconds
+    SetCrossfade(CrossfadeSettings),
+    SetNormalization(Normalization),
+    SetEqualizer(EqSettings),
+    SetSpeed(PlaybackSpeed),
+    SetMatchSourceRate(bool),
+    SetDecoderBackend(DecoderBackend),
+    SetSkipSilence(bool),
+    SetVisualizer(Option<VisualizerSettings>),
+    StartSleepTimer { mode: SleepTimerMode, fade_ms: u64 },
+    CancelSleepTimer,
+    SetLoopPoint {
+        point: LoopPoint,
+        position_ms: Option<u64>,
+    },
+    SetLoop(AbLoop),
+    SetChannelMix(ChannelMix),
+    SetHistoryThreshold(CompletionThreshold),
 }
 
 pub struct AudioEngine {
     command_tx: Sender<AudioCommand>,
     state: Arc<Mutex<PlaybackState>>,
-    media_controls: Arc<Mutex<MediaControls>>,
+    queue: Arc<Mutex<PlayQueue>>,
+    media_controls: Option<Arc<Mutex<MediaControls>>>,
+    eq_settings: Mutex<EqSettings>,
+    device_name: Mutex<Option<String>>,
+    channel_mix: Mutex<ChannelMix>,
 }
 
 impl AudioEngine {
@@ -105,74 +220,159 @@ impl AudioEngine {
         let mut controls = MediaControls::new(config).expect("Failed to initialize media controls");
         controls.set_playback(MediaPlayback::Stopped).ok();
 
+        let history = Arc::new(HistoryWriter::spawn(handle.clone()));
+        Self::start(
+            || Box::new(CpalSink::new()),
+            Arc::new(handle),
+            Some(history),
+            Some(Arc::new(Mutex::new(controls))),
+        )
+    }
+
+    /// Engine without OS media controls or listening history, rendering to
+    /// whatever sink `make_output` builds on the worker thread
+    pub fn headless<F>(make_output: F, events: Arc<dyn EventSink>) -> Self
+    where
+        F: FnOnce() -> Box<dyn OutputSink> + Send + 'static,
+    {
+        Self::start(make_output, events, None, None)
+    }
+
+    fn start<F>(
+        make_output: F,
+        events: Arc<dyn EventSink>,
+        history: Option<Arc<dyn PlayHistorySink>>,
+        media_controls: Option<Arc<Mutex<MediaControls>>>,
+    ) -> Self
+    where
+        F: FnOnce() -> Box<dyn OutputSink> + Send + 'static,
+    {
         let (tx, rx) = mpsc::channel();
         let state = Arc::new(Mutex::new(PlaybackState::default()));
-        let controls = Arc::new(Mutex::new(controls));
+        let queue = Arc::new(Mutex::new(PlayQueue::new()));
 
         let state_clone = state.clone();
-        let controls_clone = controls.clone();
-        let handle_clone = handle.clone();
+        let queue_clone = queue.clone();
+        let controls_clone = media_controls.clone();
 
+        // Output streams are not Send on every platform, so the sink is
+        // created on the worker thread that owns it
         thread::spawn(move || {
-            let mut worker = AudioWorker::new(rx, state_clone, controls_clone, handle_clone);
+            let mut worker = AudioWorker::new(
+                rx,
+                state_clone,
+                queue_clone,
+                controls_clone,
+                events,
+                history,
+                make_output(),
+            );
             worker.run();
         });
 
         Self {
             command_tx: tx,
             state,
-            media_controls: controls,
+            queue,
+            media_controls,
+            eq_settings: Mutex::new(EqSettings::default()),
+            device_name: Mutex::new(None),
+            channel_mix: Mutex::new(ChannelMix::default()),
         }
     }
 
-    pub fn init_media_events(&self, handle: AppHandle) {
-        let controls = self.media_controls.clone();
+    /// Route OS media keys straight to the worker so they keep working while
+    /// the webview is hidden or suspended
+    pub fn init_media_events(&self, _handle: AppHandle) {
+        let Some(controls) = self.media_controls.clone() else {
+            return;
+        };
         let mut controls_guard = controls.lock().unwrap();
+        let tx = self.command_tx.clone();
 
         controls_guard
-            .attach(move |event| match event {
-                souvlaki::MediaControlEvent::Play => {
-                    handle.emit("media-play", ()).unwrap();
-                }
-                souvlaki::MediaControlEvent::Pause => {
-                    handle.emit("media-pause", ()).unwrap();
-                }
-                souvlaki::MediaControlEvent::Toggle => {
-                    handle.emit("media-toggle", ()).unwrap();
-                }
-                souvlaki::MediaControlEvent::Next => {
-                    handle.emit("media-next", ()).unwrap();
-                }
-                souvlaki::MediaControlEvent::Previous => {
-                    handle.emit("media-prev", ()).unwrap();
-                }
-                souvlaki::MediaControlEvent::Stop => {
-                    handle.emit("media-stop", ()).unwrap();
-                }
-                _ => {}
+            .attach(move |event| {
+                let cmd = match event {
+                    souvlaki::MediaControlEvent::Play => AudioCommand::Resume,
+                    souvlaki::MediaControlEvent::Pause => AudioCommand::Pause,
+                    souvlaki::MediaControlEvent::Toggle => AudioCommand::Toggle,
+                    souvlaki::MediaControlEvent::Next => AudioCommand::Next,
+                    souvlaki::MediaControlEvent::Previous => AudioCommand::Previous,
+                    souvlaki::MediaControlEvent::Stop => AudioCommand::Stop,
+                    _ => return,
+                };
+                tx.send(cmd).ok();
             })
             .ok();
     }
 
-    pub fn play(
-        &self,
-        path: String,
-        title: String,
-        artist: String,
-        album: String,
-        cover: Option<String>,
-    ) {
+    pub fn play(&self, item: QueueItem) {
+        self.command_tx.send(AudioCommand::Play(item)).ok();
+    }
+
+    /// Pre-buffer the track that should follow the current one gaplessly
+    pub fn enqueue_next(&self, item: QueueItem) {
+        self.command_tx.send(AudioCommand::EnqueueNext(item)).ok();
+    }
+
+    pub fn clear_next(&self) {
+        self.command_tx.send(AudioCommand::ClearNext).ok();
+    }
+
+    pub fn set_queue(&self, items: Vec<QueueItem>, start_index: usize) {
         self.command_tx
-            .send(AudioCommand::Play {
-                path,
-                title,
-                artist,
-                album,
-                _cover: cover,
-            })
+            .send(AudioCommand::SetQueue { items, start_index })
+            .ok();
+    }
+
+    pub fn insert_into_queue(&self, items: Vec<QueueItem>, index: Option<usize>) {
+        self.command_tx
+            .send(AudioCommand::InsertIntoQueue { items, index })
+            .ok();
+    }
+
+    pub fn move_in_queue(&self, from: usize, to: usize) {
+        self.command_tx
+            .send(AudioCommand::MoveInQueue { from, to })
+            .ok();
+    }
+
+    pub fn remove_from_queue(&self, index: usize) {
+        self.command_tx
+            .send(AudioCommand::RemoveFromQueue(index))
+            .ok();
+    }
+
+    pub fn clear_queue(&self) {
+        self.command_tx.send(AudioCommand::ClearQueue).ok();
+    }
+
+    pub fn jump_to_queue_index(&self, index: usize) {
+        self.command_tx
+            .send(AudioCommand::JumpToQueueIndex(index))
             .ok();
     }
 
+    pub fn set_shuffle(&self, enabled: bool) {
+        self.command_tx.send(AudioCommand::SetShuffle(enabled)).ok();
+    }
+
+    pub fn set_repeat(&self, mode: RepeatMode) {
+        self.command_tx.send(AudioCommand::SetRepeat(mode)).ok();
+    }
+
+    pub fn next(&self) {
+        self.command_tx.send(AudioCommand::Next).ok();
+    }
+
+    pub fn previous(&self) {
+        self.command_tx.send(AudioCommand::Previous).ok();
+    }
+
+    pub fn get_queue(&self) -> QueueSnapshot {
+        self.queue.lock().unwrap().snapshot()
+    }
+
     pub fn pause(&self) {
         self.command_tx.send(AudioCommand::Pause).ok();
     }
@@ -194,62 +394,209 @@ impl AudioEngine {
     }
 
     pub fn set_device(&self, device_name: String) {
+        *self.device_name.lock().unwrap() = Some(device_name.clone());
         self.command_tx
             .send(AudioCommand::SetDevice(device_name))
             .ok();
     }
 
-    pub fn set_crossfade(&self, duration_ms: u64) {
+    pub fn set_crossfade(&self, settings: CrossfadeSettings) {
+        self.command_tx
+            .send(AudioCommand::SetCrossfade(settings))
+            .ok();
+    }
+
+    pub fn set_normalization(&self, mode: NormalizationMode, preamp_db: f32) {
+        self.command_tx
+            .send(AudioCommand::SetNormalization(Normalization { mode, preamp_db }))
+            .ok();
+    }
+
+    pub fn set_equalizer(&self, settings: EqSettings) {
+        *self.eq_settings.lock().unwrap() = settings.clone();
+        self.command_tx.send(AudioCommand::SetEqualizer(settings)).ok();
+    }
+
+    pub fn set_speed(&self, rate: f32, mode: SpeedMode) {
+        self.command_tx
+            .send(AudioCommand::SetSpeed(PlaybackSpeed::new(rate, mode)))
+            .ok();
+    }
+
+    /// Open the device at each track's own sample rate when it supports it
+    pub fn set_match_source_rate(&self, enabled: bool) {
+        self.command_tx
+            .send(AudioCommand::SetMatchSourceRate(enabled))
+            .ok();
+    }
+
+    pub fn set_decoder_backend(&self, backend: DecoderBackend) {
+        self.command_tx
+            .send(AudioCommand::SetDecoderBackend(backend))
+            .ok();
+    }
+
+    /// Start tracks after their leading silence and end them before the trailing one
+    pub fn set_skip_silence(&self, enabled: bool) {
+        self.command_tx
+            .send(AudioCommand::SetSkipSilence(enabled))
+            .ok();
+    }
+
+    /// Start emitting spectrum and level frames, or stop with `None`
+    pub fn set_visualizer(&self, settings: Option<VisualizerSettings>) {
+        self.command_tx
+            .send(AudioCommand::SetVisualizer(settings))
+            .ok();
+    }
+
+    /// Fade out and stop playback after a time or a number of tracks
+    pub fn start_sleep_timer(&self, mode: SleepTimerMode, fade_ms: u64) {
+        self.command_tx
+            .send(AudioCommand::StartSleepTimer { mode, fade_ms })
+            .ok();
+    }
+
+    pub fn cancel_sleep_timer(&self) {
+        self.command_tx.send(AudioCommand::CancelSleepTimer).ok();
+    }
+
+    /// Set A or B on the current track, at `position_ms` or where playback is now
+    pub fn set_loop_point(&self, point: LoopPoint, position_ms: Option<u64>) {
+        self.command_tx
+            .send(AudioCommand::SetLoopPoint { point, position_ms })
+            .ok();
+    }
+
+    /// Replace both loop points, e.g. with a saved loop; the default clears them
+    pub fn set_loop(&self, ab_loop: AbLoop) {
+        self.command_tx.send(AudioCommand::SetLoop(ab_loop)).ok();
+    }
+
+    pub fn set_channel_mix(&self, mix: ChannelMix) {
+        *self.channel_mix.lock().unwrap() = mix;
+        self.command_tx.send(AudioCommand::SetChannelMix(mix)).ok();
+    }
+
+    pub fn get_channel_mix(&self) -> ChannelMix {
+        *self.channel_mix.lock().unwrap()
+    }
+
+    /// Name per-device settings are stored under
+    pub fn device_key(&self) -> String {
+        self.device_name
+            .lock()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| DEFAULT_DEVICE_KEY.to_string())
+    }
+
+    /// When a play counts as completed in the listening history
+    pub fn set_history_threshold(&self, threshold: CompletionThreshold) {
         self.command_tx
-            .send(AudioCommand::SetCrossfade(duration_ms))
+            .send(AudioCommand::SetHistoryThreshold(threshold))
             .ok();
     }
 
+    pub fn get_equalizer(&self) -> EqSettings {
+        self.eq_settings.lock().unwrap().clone()
+    }
+
     pub fn get_state(&self) -> PlaybackState {
         self.state.lock().unwrap().clone()
     }
 }
 
-/// State of the crossfade
+/// State of the crossfade, counted in output samples
 enum CrossfadeState {
     None,
-    Fading {
-        start_time: Instant,
-        duration: Duration,
-    },
+    Fading { position: usize, length: usize },
+}
+
+/// A track whose decoder was opened ahead of time so its samples can be
+/// spliced into the ring buffer as soon as the current track hits EOF
+struct PreparedTrack {
+    process: Box<dyn Decoder>,
+    queue_index: Option<usize>,
+    item: QueueItem,
+    duration_ms: u64,
+    source: StreamFormat,
+    start_ms: u64, // Where the decoder was opened, past any skipped silence
+}
+
+/// A track already in the ring buffer whose change is announced once the
+/// output plays its first sample
+struct PendingSwitch {
+    track: TrackChange,
+    queue_index: Option<usize>,
+    at_sample: u64, // Position of its first sample in the ring buffer's stream
+}
+
+/// The play of a track that was replaced while some of it was still in the
+/// ring buffer, recorded once the output has played that part
+struct OutgoingPlay {
+    tracker: PlayTracker,
+    until_sample: u64, // Position in the ring buffer's stream where the track ends
 }
 
 struct AudioWorker {
     receiver: Receiver<AudioCommand>,
     state: Arc<Mutex<PlaybackState>>,
-    media_controls: Arc<Mutex<MediaControls>>,
-    app_handle: AppHandle,
+    queue: Arc<Mutex<PlayQueue>>,
+    media_controls: Option<Arc<Mutex<MediaControls>>>,
+    events: Arc<dyn EventSink>,
+    history: Option<Arc<dyn PlayHistorySink>>,
+    play_tracker: PlayTracker,
+    outgoing_play: Option<OutgoingPlay>,
+    completion: CompletionThreshold,
 
     // Playback resources
-    _current_stream: Option<Stream>,
+    output: Box<dyn OutputSink>,
     producer: Option<ringbuf::HeapProd<f32>>,
-    volume: Arc<AtomicU64>,
-    is_playing: Arc<AtomicBool>,
-    device_error: Arc<AtomicBool>,
-
-    // FFmpeg processes
-    primary_process: Option<FFmpegProcess>,
-    secondary_process: Option<FFmpegProcess>, // For the incoming track during crossfade
+    controls: OutputControls,
+    pending_seek: Option<u64>,
+    seek_fade_remaining: usize, // Samples left in the post-seek fade-in
+    draining: bool,             // Decoding finished, waiting for the output to play out
+    samples_pushed: u64,        // Samples pushed into the current ring buffer
+    pending_switch: Option<PendingSwitch>,
+
+    // Decoders (dropping one kills its FFmpeg process, if any)
+    decoder_backend: DecoderBackend,
+    primary_process: Option<Box<dyn Decoder>>,
+    secondary_process: Option<Box<dyn Decoder>>, // For the incoming track during crossfade
+    next_track: Option<PreparedTrack>,        // Pre-buffered for gapless playback
 
     // Crossfade State
-    crossfade_setting: Duration, // User preference
+    crossfade: CrossfadeSettings, // User preference
     crossfade_state: CrossfadeState,
 
+    // Loudness normalization
+    normalization: Normalization,
+    primary_loudness: TrackLoudness,
+    secondary_loudness: TrackLoudness,
+
+    equalizer: Equalizer,
+    speed: PlaybackSpeed,
+
     // Device config
     device_sample_rate: u32,
     device_channels: u16,
     selected_device_name: Option<String>,
+    match_source_rate: bool,
+    visualizer: Option<Visualizer>, // Only while someone subscribes
+    sleep_timer: Option<SleepTimer>,
+    ab_loop: AbLoop,
 
     // Track info
     current_file_path: Option<String>,
+    current_album: Option<String>, // For the same-album crossfade rule
+    current_silence: TrackSilence,
+    current_source: StreamFormat, // Probed format, asked for when reopening the output
+    skip_silence: bool,
     duration_ms: u64,
     current_position_ms: u64,
-    samples_played: u64,
+    position_base_ms: u64, // Media time where the current decoder started
+    samples_played: u64,   // Output samples pushed since then
 
     // Buffers
     primary_buffer: Vec<f32>,
@@ -260,16 +607,16 @@ impl AudioWorker {
     fn new(
         receiver: Receiver<AudioCommand>,
         state: Arc<Mutex<PlaybackState>>,
-        media_controls: Arc<Mutex<MediaControls>>,
-        app_handle: AppHandle,
+        queue: Arc<Mutex<PlayQueue>>,
+        media_controls: Option<Arc<Mutex<MediaControls>>>,
+        events: Arc<dyn EventSink>,
+        history: Option<Arc<dyn PlayHistorySink>>,
+        output: Box<dyn OutputSink>,
     ) -> Self {
-        let host = cpal::default_host();
-        
         // Gracefully handle missing audio devices
-        let (sample_rate, channels) = host
-            .default_output_device()
-            .and_then(|device| device.default_output_config().ok())
-            .map(|config| (config.sample_rate().0, config.channels()))
+        let (sample_rate, channels) = output
+            .default_format()
+            .map(|format| (format.sample_rate, format.channels))
             .unwrap_or_else(|| {
                 error!("No audio output device found, using default config (44100Hz, stereo)");
                 (44100, 2) // Default fallback
@@ -278,23 +625,50 @@ impl AudioWorker {
         Self {
             receiver,
             state,
+            queue,
             media_controls,
-            app_handle,
-            _current_stream: None,
+            events,
+            history,
+            play_tracker: PlayTracker::default(),
+            outgoing_play: None,
+            completion: CompletionThreshold::default(),
+            output,
             producer: None,
-            volume: Arc::new(AtomicU64::new(f32::to_bits(1.0) as u64)),
-            is_playing: Arc::new(AtomicBool::new(false)),
-            device_error: Arc::new(AtomicBool::new(false)),
+            controls: OutputControls::default(),
+            pending_seek: None,
+            seek_fade_remaining: 0,
+            draining: false,
+            samples_pushed: 0,
+            pending_switch: None,
+            decoder_backend: DecoderBackend::default(),
             primary_process: None,
             secondary_process: None,
-            crossfade_setting: Duration::from_secs(0),
+            next_track: None,
+            crossfade: CrossfadeSettings::default(),
             crossfade_state: CrossfadeState::None,
+            normalization: Normalization::default(),
+            primary_loudness: TrackLoudness::default(),
+            secondary_loudness: TrackLoudness::default(),
+            equalizer: Equalizer::new(sample_rate, channels),
+            speed: PlaybackSpeed::default(),
             device_sample_rate: sample_rate,
             device_channels: channels,
             selected_device_name: None,
+            match_source_rate: false,
+            visualizer: None,
+            sleep_timer: None,
+            ab_loop: AbLoop::default(),
             current_file_path: None,
+            current_album: None,
+            current_silence: TrackSilence::default(),
+            current_source: StreamFormat {
+                sample_rate,
+                channels,
+            },
+            skip_silence: false,
             duration_ms: 0,
             current_position_ms: 0,
+            position_base_ms: 0,
             samples_played: 0,
             primary_buffer: vec![0.0f32; 8192],
             secondary_buffer: vec![0.0f32; 8192],
@@ -304,15 +678,26 @@ impl AudioWorker {
     fn run(&mut self) {
         loop {
             match self.receiver.recv_timeout(Duration::from_millis(5)) {
-                Ok(cmd) => self.handle_command(cmd),
+                Ok(cmd) => {
+                    self.handle_command(cmd);
+                    // Drain what queued up meanwhile so rapid scrubbing only decodes the latest target
+                    while let Ok(cmd) = self.receiver.try_recv() {
+                        self.handle_command(cmd);
+                    }
+                    self.apply_pending_seek();
+                }
                 Err(mpsc::RecvTimeoutError::Timeout) => {
-                    if self.device_error.load(Ordering::Relaxed) {
+                    if self.controls.device_error.load(Ordering::Relaxed) {
                         self.handle_device_change();
                     }
-                    if self.is_playing.load(Ordering::Relaxed) {
+                    if self.controls.is_playing.load(Ordering::Relaxed) {
                         self.decode_and_push();
                     }
+                    self.poll_pending_switch();
+                    self.poll_outgoing_play();
                     self.emit_progress();
+                    self.emit_visualization();
+                    self.tick_sleep_timer();
                 }
                 Err(mpsc::RecvTimeoutError::Disconnected) => break,
             }
@@ -320,22 +705,69 @@ impl AudioWorker {
     }
 
     fn handle_command(&mut self, cmd: AudioCommand) {
+        // Commands act on the track the worker already moved to
+        self.flush_pending_switch();
+        // Anything other than another seek must see the seek already applied
+        if !matches!(cmd, AudioCommand::Seek(_)) {
+            self.apply_pending_seek();
+        }
+
         match cmd {
-            AudioCommand::Play {
-                path,
-                title,
-                artist,
-                album,
-                _cover,
-            } => {
-                self.handle_play_request(&path, &title, &artist, &album);
+            AudioCommand::Play(item) => self.handle_play_request(&item),
+            AudioCommand::EnqueueNext(item) => self.prepare_next_track(item, None),
+            AudioCommand::ClearNext => {
+                self.next_track = None;
+            }
+            AudioCommand::SetQueue { items, start_index } => {
+                self.queue.lock().unwrap().set(items, start_index);
+                self.play_current_queue_item();
+            }
+            AudioCommand::InsertIntoQueue { items, index } => {
+                self.queue.lock().unwrap().insert(items, index);
+                self.on_queue_changed();
+            }
+            AudioCommand::MoveInQueue { from, to } => {
+                if self.queue.lock().unwrap().move_item(from, to) {
+                    self.on_queue_changed();
+                }
+            }
+            AudioCommand::RemoveFromQueue(index) => {
+                if self.queue.lock().unwrap().remove(index) {
+                    self.on_queue_changed();
+                }
+            }
+            AudioCommand::ClearQueue => {
+                self.queue.lock().unwrap().clear_upcoming();
+                self.on_queue_changed();
+            }
+            AudioCommand::JumpToQueueIndex(index) => {
+                if self.queue.lock().unwrap().jump_to(index) {
+                    self.play_current_queue_item();
+                }
+            }
+            AudioCommand::SetShuffle(enabled) => {
+                self.queue.lock().unwrap().set_shuffle(enabled);
+                self.on_queue_changed();
+            }
+            AudioCommand::SetRepeat(mode) => {
+                self.queue.lock().unwrap().set_repeat(mode);
+                self.on_queue_changed();
             }
+            AudioCommand::Next => self.skip_next(),
+            AudioCommand::Previous => self.skip_previous(),
             AudioCommand::Pause => self.pause(),
             AudioCommand::Resume => self.resume(),
+            AudioCommand::Toggle => {
+                if self.controls.is_playing.load(Ordering::Relaxed) {
+                    self.pause();
+                } else if self.current_file_path.is_some() {
+                    self.resume();
+                }
+            }
             AudioCommand::Stop => self.stop(),
-            AudioCommand::Seek(pos) => self.seek(pos),
+            AudioCommand::Seek(pos) => self.pending_seek = Some(pos),
             AudioCommand::SetVolume(vol) => {
-                self.volume
+                self.controls.volume
                     .store(f32::to_bits(vol) as u64, Ordering::Relaxed);
                 self.state.lock().unwrap().volume = vol;
             }
@@ -343,20 +775,93 @@ impl AudioWorker {
                 self.selected_device_name = Some(name);
                 self.handle_device_change();
             }
-            AudioCommand::SetCrossfade(ms) => {
-                self.crossfade_setting = Duration::from_millis(ms);
+            AudioCommand::SetCrossfade(settings) => {
+                self.crossfade = settings;
+            }
+            AudioCommand::SetNormalization(normalization) => {
+                self.normalization = normalization;
+            }
+            AudioCommand::SetEqualizer(settings) => {
+                self.equalizer.set_settings(settings);
+            }
+            AudioCommand::SetSpeed(speed) => self.set_speed(speed),
+            AudioCommand::SetDecoderBackend(backend) => {
+                // Takes effect from the next track or seek
+                self.decoder_backend = backend;
+            }
+            AudioCommand::SetSkipSilence(enabled) => {
+                // Trimmed ends apply right away, trimmed starts from the next track
+                self.skip_silence = enabled;
+                if let Some(next) = self.next_track.take() {
+                    self.prepare_next_track(next.item, next.queue_index);
+                }
+            }
+            AudioCommand::SetMatchSourceRate(enabled) => {
+                // Takes effect from the next track that opens the stream
+                self.match_source_rate = enabled;
+            }
+            AudioCommand::SetVisualizer(settings) => {
+                self.visualizer = match (settings, self.visualizer.take()) {
+                    (Some(settings), Some(mut visualizer)) => {
+                        visualizer.set_settings(settings);
+                        Some(visualizer)
+                    }
+                    (Some(settings), None) => Some(Visualizer::new(
+                        settings,
+                        StreamFormat {
+                            sample_rate: self.device_sample_rate,
+                            channels: self.device_channels,
+                        },
+                    )),
+                    (None, _) => None,
+                };
+            }
+            AudioCommand::StartSleepTimer { mode, fade_ms } => {
+                info!("Sleep timer set: {:?}", mode);
+                self.sleep_timer = Some(SleepTimer::new(mode, fade_ms, Instant::now()));
+                if self.sleep_ends_with_track() {
+                    self.next_track = None;
+                }
+                self.tick_sleep_timer();
+            }
+            AudioCommand::SetLoopPoint { point, position_ms } => {
+                if self.current_file_path.is_some() {
+                    let position_ms = position_ms.unwrap_or_else(|| self.played_position_ms());
+                    self.ab_loop.set(point, position_ms);
+                    self.emit(EVENT_LOOP_CHANGED, self.ab_loop);
+                }
+            }
+            AudioCommand::SetChannelMix(mix) => {
+                self.controls
+                    .channel_mix
+                    .store(mix.to_bits(), Ordering::Relaxed);
+            }
+            AudioCommand::SetHistoryThreshold(threshold) => {
+                self.completion = threshold;
+            }
+            AudioCommand::SetLoop(ab_loop) => {
+                self.ab_loop = ab_loop;
+                self.emit(EVENT_LOOP_CHANGED, self.ab_loop);
+            }
+            AudioCommand::CancelSleepTimer => {
+                if self.sleep_timer.take().is_some() {
+                    info!("Sleep timer cancelled");
+                    self.restore_volume();
+                    self.emit(EVENT_SLEEP_TIMER, SleepTimerStatus::inactive());
+                }
             }
         }
     }
 
-    fn handle_play_request(&mut self, path: &str, title: &str, artist: &str, album: &str) {
+    fn handle_play_request(&mut self, item: &QueueItem) {
+        let path = item.file_path.as_str();
         // Check if we are playing the same file
         let is_same_track = self.current_file_path.as_deref() == Some(path);
 
         // Decide if we should crossfade or hard cut
-        // Crossfade if: we are currently playing, crossfade_setting > 0, we have a primary process, AND it's a different track
-        let should_crossfade = self.is_playing.load(Ordering::Relaxed)
-            && self.crossfade_setting.as_millis() > 0
+        // Crossfade if: we are currently playing, crossfade is enabled, we have a primary process, AND it's a different track
+        let should_crossfade = self.controls.is_playing.load(Ordering::Relaxed)
+            && self.crossfade.is_enabled()
             && self.primary_process.is_some()
             && !is_same_track;
 
@@ -367,84 +872,105 @@ impl AudioWorker {
             // 3. Set CrossfadeState to Fading
 
             // Probe new file
-            let metadata = match ffmpeg::probe_file(path) {
+            let metadata = match decoder::probe(self.decoder_backend, path) {
                 Ok(m) => m,
                 Err(e) => {
                     let msg = format!("Failed to probe file (crossfade): {}", e);
-                    self.app_handle.emit(EVENT_PLAYBACK_ERROR, msg).ok();
+                    self.emit(EVENT_PLAYBACK_ERROR, msg);
                     return;
                 }
             };
 
-            match FFmpegProcess::spawn(path, self.device_sample_rate, self.device_channels) {
+            // Fading it in would resample it to the current rate; reopen the
+            // stream at its own rate instead
+            if self.match_source_rate
+                && (metadata.sample_rate, metadata.channels)
+                    != (self.device_sample_rate, self.device_channels)
+            {
+                info!("New track needs a different output format, skipping crossfade");
+                self.play_file_hard_cut(item);
+                return;
+            }
+
+            let start_ms = self.trim_start_ms(item);
+            match self.spawn_decoder(path, Some(start_ms).filter(|ms| *ms > 0)) {
                 Ok(process) => {
                     info!("Crossfading to new track: {}", path);
                     self.secondary_process = Some(process);
+                    self.secondary_loudness = item.loudness;
+                    self.next_track = None;
                     self.crossfade_state = CrossfadeState::Fading {
-                        start_time: Instant::now(),
-                        duration: self.crossfade_setting,
+                        position: 0,
+                        length: self.samples_for_ms(self.crossfade.duration_ms),
                     };
 
                     // Note: We don't update current_file_path metadata yet to keeping the UI showing the old song fading out
                     // But typically UI wants to show the new song immediately.
                     // Let's swap metadata immediately for UI responsiveness, even though audio is mixing.
-                    self.current_file_path = Some(path.to_string());
-                    self.duration_ms = metadata.duration_ms;
-                    self.current_position_ms = 0;
-                    self.samples_played = 0;
+                    self.set_current_track(
+                        item,
+                        metadata.duration_ms,
+                        source_format(&metadata),
+                        start_ms,
+                    );
 
                     {
                         let mut s = self.state.lock().unwrap();
                         s.current_file = Some(path.to_string());
                         s.duration_ms = self.duration_ms;
-                        s.position_ms = 0;
+                        s.position_ms = start_ms;
                     }
 
-                    self.update_media_metadata(title, artist, album, self.duration_ms);
+                    self.update_media_metadata(&TrackChange::new(item, self.duration_ms));
                     self.emit_state();
                 }
                 Err(e) => {
                     error!("Failed to spawn secondary FFmpeg: {}", e);
                     // Fallback to hard cut
-                    self.play_file_hard_cut(path, title, artist, album);
+                    self.play_file_hard_cut(item);
                 }
             }
         } else {
             info!("Playing track (hard cut): {}", path);
-            self.play_file_hard_cut(path, title, artist, album);
+            self.play_file_hard_cut(item);
         }
     }
 
-    fn play_file_hard_cut(&mut self, path: &str, title: &str, artist: &str, album: &str) {
+    fn play_file_hard_cut(&mut self, item: &QueueItem) {
         self.stop(); // Clears everything
+        let path = item.file_path.as_str();
 
-        let metadata = match ffmpeg::probe_file(path) {
+        let metadata = match decoder::probe(self.decoder_backend, path) {
             Ok(m) => m,
             Err(e) => {
                 let msg = format!("Failed to probe file: {}", e);
-                self.app_handle.emit(EVENT_PLAYBACK_ERROR, msg).ok();
+                self.emit(EVENT_PLAYBACK_ERROR, msg);
                 return;
             }
         };
 
-        self.duration_ms = metadata.duration_ms;
-        self.recreate_cpal_stream(metadata.sample_rate, metadata.channels);
+        self.open_output(metadata.sample_rate, metadata.channels);
 
-        match FFmpegProcess::spawn(path, self.device_sample_rate, self.device_channels) {
+        let start_ms = self.trim_start_ms(item);
+        match self.spawn_decoder(path, Some(start_ms).filter(|ms| *ms > 0)) {
             Ok(process) => {
                 info!("Spawned FFmpeg process for: {}", path);
                 self.primary_process = Some(process);
+                self.primary_loudness = item.loudness;
             }
             Err(e) => {
                 let msg = format!("Failed to spawn FFmpeg: {}", e);
-                self.app_handle.emit(EVENT_PLAYBACK_ERROR, msg).ok();
+                self.emit(EVENT_PLAYBACK_ERROR, msg);
                 return;
             }
         }
 
-        self.current_file_path = Some(path.to_string());
-        self.current_position_ms = 0;
-        self.samples_played = 0;
+        self.set_current_track(
+            item,
+            metadata.duration_ms,
+            source_format(&metadata),
+            start_ms,
+        );
 
         {
             let mut s = self.state.lock().unwrap();
@@ -452,271 +978,655 @@ impl AudioWorker {
             s.is_paused = false;
             s.current_file = Some(path.to_string());
             s.duration_ms = self.duration_ms;
-            s.position_ms = 0;
+            s.position_ms = start_ms;
         }
 
-        self.update_media_metadata(title, artist, album, self.duration_ms);
-        self.is_playing.store(true, Ordering::Relaxed);
+        self.update_media_metadata(&TrackChange::new(item, self.duration_ms));
+        self.controls.is_playing.store(true, Ordering::Relaxed);
         self.emit_state();
     }
 
-    fn update_media_metadata(&self, title: &str, artist: &str, album: &str, duration_ms: u64) {
-        if let Ok(mut c) = self.media_controls.lock() {
-            c.set_metadata(MediaMetadata {
-                title: Some(title),
-                artist: Some(artist),
-                album: Some(album),
-                duration: Some(Duration::from_millis(duration_ms)),
-                cover_url: None,
-            })
-            .ok();
-            c.set_playback(MediaPlayback::Playing {
-                progress: Some(MediaPosition(Duration::ZERO)),
-            })
-            .ok();
+    /// Start the queue's current entry, crossfading if the user enabled it
+    fn play_current_queue_item(&mut self) {
+        let current = self.queue.lock().unwrap().current().map(|(_, item)| item.clone());
+        if let Some(item) = current {
+            self.handle_play_request(&item);
         }
+        self.on_queue_changed();
     }
 
-    fn recreate_cpal_stream(&mut self, _sample_rate: u32, _channels: u16) {
-        let host = cpal::default_host();
-
-        let device = if let Some(ref name) = self.selected_device_name {
-            host.output_devices()
-                .ok()
-                .and_then(|mut devices| {
-                    devices.find(|d| d.name().map(|n| n == *name).unwrap_or(false))
-                })
-                .or_else(|| host.default_output_device())
+    fn skip_next(&mut self) {
+        let next = self.queue.lock().unwrap().advance(false);
+        if next.is_some() {
+            self.play_current_queue_item();
         } else {
-            host.default_output_device()
-        };
+            self.stop();
+            self.on_queue_changed();
+        }
+    }
+
+    fn skip_previous(&mut self) {
+        if self.current_file_path.is_some()
+            && self.played_position_ms() > PREVIOUS_RESTART_THRESHOLD_MS
+        {
+            self.seek(0);
+            return;
+        }
+
+        let previous = self.queue.lock().unwrap().go_previous();
+        if previous.is_some() {
+            self.play_current_queue_item();
+        }
+    }
+
+    /// Keep the pre-buffered track in line with the queue and notify the UI
+    fn on_queue_changed(&mut self) {
+        self.sync_next_track();
+        let snapshot = self.queue.lock().unwrap().snapshot();
+        self.emit(EVENT_QUEUE_CHANGED, &snapshot);
+    }
 
-        let Some(device) = device else {
-            error!("No audio output device available");
-            self.app_handle.emit(EVENT_PLAYBACK_ERROR, "No audio output device available").ok();
+    fn sync_next_track(&mut self) {
+        if self.current_file_path.is_none() {
             return;
+        }
+
+        let upcoming = {
+            let queue = self.queue.lock().unwrap();
+            queue
+                .peek_next(true)
+                .and_then(|index| queue.get(index).map(|item| (index, item.clone())))
         };
 
-        let config: StreamConfig = match device.default_output_config() {
-            Ok(c) => c.into(),
-            Err(e) => {
-                error!("Failed to get audio config: {}", e);
-                self.app_handle.emit(EVENT_PLAYBACK_ERROR, format!("Audio device error: {}", e)).ok();
-                return;
+        let Some((index, item)) = upcoming else {
+            // Only drop tracks we prepared from the queue, not manual enqueues
+            if self.next_track.as_ref().is_some_and(|n| n.queue_index.is_some()) {
+                self.next_track = None;
             }
+            return;
         };
 
-        self.device_sample_rate = config.sample_rate.0;
-        self.device_channels = config.channels;
+        if let Some(next) = self.next_track.as_mut() {
+            if next.item.file_path == item.file_path {
+                next.queue_index = Some(index);
+                return;
+            }
+        }
 
-        let buffer_size = self.device_sample_rate as usize * self.device_channels as usize; // 1 sec
-        let rb = HeapRb::<f32>::new(buffer_size);
-        let (producer, consumer) = rb.split();
-        self.producer = Some(producer);
+        self.prepare_next_track(item, Some(index));
+    }
 
-        let volume = self.volume.clone();
-        let is_playing = self.is_playing.clone();
-        let device_error = self.device_error.clone();
-        let mut consumer = consumer;
-        let channels = self.device_channels as usize;
-
-        let stream = match device.build_output_stream(
-            &config,
-            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                if !is_playing.load(Ordering::Relaxed) {
-                    data.fill(0.0);
-                    return;
-                }
+    fn prepare_next_track(&mut self, item: QueueItem, queue_index: Option<usize>) {
+        // Replacing the pending track drops (and kills) its process
+        self.next_track = None;
+        if self.sleep_ends_with_track() {
+            return;
+        }
+        let path = item.file_path.as_str();
 
-                let vol = f32::from_bits(volume.load(Ordering::Relaxed) as u32);
-                for frame in data.chunks_mut(channels) {
-                    for sample in frame.iter_mut() {
-                        if let Some(s) = consumer.try_pop() {
-                            *sample = s * vol;
-                        } else {
-                            *sample = 0.0;
-                        }
-                    }
-                }
-            },
-            move |err| {
-                error!("CPAL Error: {}", err);
-                device_error.store(true, Ordering::Relaxed);
-            },
-            None,
-        ) {
-            Ok(s) => s,
+        let metadata = match decoder::probe(self.decoder_backend, path) {
+            Ok(m) => m,
             Err(e) => {
-                error!("Failed to build audio stream: {}", e);
-                self.app_handle.emit(EVENT_PLAYBACK_ERROR, format!("Failed to initialize audio: {}", e)).ok();
+                error!("Failed to probe next track {}: {}", path, e);
                 return;
             }
         };
 
-        if let Err(e) = stream.play() {
-            error!("Failed to play audio stream: {}", e);
-            self.app_handle.emit(EVENT_PLAYBACK_ERROR, format!("Failed to start playback: {}", e)).ok();
+        // Splicing would resample it to the current rate; let the track end
+        // and reopen the stream at its own rate instead
+        if self.match_source_rate
+            && (metadata.sample_rate, metadata.channels)
+                != (self.device_sample_rate, self.device_channels)
+        {
+            info!("Next track needs a different output format, skipping pre-buffer");
             return;
         }
-        
-        self._current_stream = Some(stream);
+
+        let start_ms = self.trim_start_ms(&item);
+        match self.spawn_decoder(path, Some(start_ms).filter(|ms| *ms > 0)) {
+            Ok(process) => {
+                info!("Pre-buffering next track: {}", path);
+                self.next_track = Some(PreparedTrack {
+                    process,
+                    queue_index,
+                    item,
+                    duration_ms: metadata.duration_ms,
+                    source: source_format(&metadata),
+                    start_ms,
+                });
+            }
+            Err(e) => error!("Failed to spawn FFmpeg for next track: {}", e),
+        }
     }
 
-    fn decode_and_push(&mut self) {
-        let mut track_finished = false;
+    /// Announce a spliced or faded-in track once the output reaches it
+    fn poll_pending_switch(&mut self) {
+        let reached = self.pending_switch.as_ref().is_some_and(|pending| {
+            self.controls.samples_rendered.load(Ordering::Acquire) >= pending.at_sample
+        });
+        if reached {
+            self.flush_pending_switch();
+        }
+    }
 
-        {
-            let Some(ref mut producer) = self.producer else {
-                return;
-            };
+    /// Announce a pending track change right away, before its ring buffer
+    /// goes away or the worker acts on the new track
+    fn flush_pending_switch(&mut self) {
+        if let Some(pending) = self.pending_switch.take() {
+            self.finish_track_switch(pending.track, pending.queue_index);
+        }
+    }
 
-            // Ensure we have at least a primary process
-            if self.primary_process.is_none() {
-                return;
-            }
+    /// Update bookkeeping after the pre-buffered track has been spliced or faded in
+    fn finish_track_switch(&mut self, track: TrackChange, queue_index: Option<usize>) {
+        info!("Switched to next track: {}", track.path);
+        if let Some(timer) = self.sleep_timer.as_mut() {
+            timer.track_finished();
+        }
+        {
+            let mut s = self.state.lock().unwrap();
+            s.current_file = Some(track.path.clone());
+            s.duration_ms = track.duration_ms;
+            s.position_ms = self.current_position_ms;
+        }
 
-            let capacity = producer.capacity().get();
-            let target_fill = capacity / 2;
+        self.update_media_metadata(&track);
+        self.emit(EVENT_TRACK_CHANGED, &track);
+        self.emit_state();
 
-            // We will process chunk by chunk
-            loop {
-                let occupied = capacity - producer.vacant_len();
-                if occupied >= target_fill {
-                    break;
-                }
-                if producer.vacant_len() < self.primary_buffer.len() {
-                    break;
-                }
+        if let Some(index) = queue_index {
+            self.queue.lock().unwrap().jump_to(index);
+            self.on_queue_changed();
+        }
+    }
 
-                // Check crossfade status
-                let mut crossfade_progress = 0.0;
-                let mut is_fading = false;
+    fn update_media_metadata(&self, track: &TrackChange) {
+        self.with_media_controls(|c| {
+            c.set_metadata(MediaMetadata {
+                title: Some(&track.title),
+                artist: Some(&track.artist),
+                album: Some(&track.album),
+                duration: Some(Duration::from_millis(track.duration_ms)),
+                cover_url: None,
+            })
+            .ok();
+            c.set_playback(MediaPlayback::Playing {
+                progress: Some(MediaPosition(Duration::ZERO)),
+            })
+            .ok();
+        });
+    }
 
-                if let CrossfadeState::Fading {
-                    start_time,
-                    duration,
-                } = self.crossfade_state
-                {
-                    let elapsed = start_time.elapsed();
-                    if elapsed < duration {
-                        crossfade_progress = elapsed.as_secs_f32() / duration.as_secs_f32();
-                        is_fading = true;
-                    } else {
-                        // Fade complete! Inline finish_crossfade logic
-                        if let Some(mut old_p) = self.primary_process.take() {
-                            old_p.kill();
-                        }
-                        self.primary_process = self.secondary_process.take();
-                        self.crossfade_state = CrossfadeState::None;
-                        continue;
-                    }
-                }
+    /// Open the output for a track in the given source format
+    fn open_output(&mut self, sample_rate: u32, channels: u16) {
+        let source = StreamFormat {
+            sample_rate,
+            channels,
+        };
+        self.flush_pending_switch();
+        self.close_outgoing_play();
+        let controls = self.controls.clone();
+        let mut producer = None;
+
+        let opened = self.output.open(
+            source,
+            self.match_source_rate,
+            self.selected_device_name.as_deref(),
+            &mut |format| {
+                let (prod, renderer) = Renderer::new(format, controls.clone());
+                producer = Some(prod);
+                renderer
+            },
+        );
 
-                // Temporarily borrow buffers separately
-                let primary_buffer = &mut self.primary_buffer;
+        let opened = match opened {
+            Ok(opened) => opened,
+            Err(e) => {
+                error!("Failed to open audio output: {}", e);
+                self.emit(EVENT_PLAYBACK_ERROR, e);
+                return;
+            }
+        };
 
-                // Read Primary
-                let primary_read = if let Some(proc) = &mut self.primary_process {
-                    match proc.read_samples(primary_buffer) {
-                        Ok(n) => n,
-                        Err(_) => 0,
-                    }
+        self.producer = producer;
+        self.samples_pushed = 0;
+        self.device_sample_rate = opened.format.sample_rate;
+        self.device_channels = opened.format.channels;
+        self.equalizer
+            .set_format(self.device_sample_rate, self.device_channels);
+        if let Some(visualizer) = self.visualizer.as_mut() {
+            visualizer.set_format(opened.format);
+        }
+
+        if let Some(reason) = &opened.fallback_reason {
+            warn!("Not matching source rate: {}", reason);
+        }
+        let format = OutputFormat {
+            sample_rate: self.device_sample_rate,
+            channels: self.device_channels,
+            source_sample_rate: sample_rate,
+            source_channels: channels,
+            matches_source: opened.format == source,
+            fallback_reason: opened.fallback_reason,
+        };
+        self.emit(EVENT_OUTPUT_FORMAT, &format);
+    }
+
+    fn decode_and_push(&mut self) {
+        if self.draining {
+            if self.producer.as_ref().is_some_and(|p| p.occupied_len() > 0) {
+                return;
+            }
+            self.draining = false;
+            self.flush_pending_switch();
+            self.handle_end_of_track();
+            return;
+        }
+
+        // Ensure we have at least a primary process, and wait for the callback
+        // to drop pre-seek audio before pushing new samples
+        if self.primary_process.is_none() || self.controls.flush_pending.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(mut producer) = self.producer.take() else {
+            return;
+        };
+
+        let seek_fade_len = self.seek_fade_len();
+        let channels = self.device_channels.max(1) as usize;
+        let mut track_finished = false;
+        let mut switched_to: Option<PendingSwitch> = None;
+
+        let capacity = producer.capacity().get();
+        let target_fill = capacity / 2;
+
+        // We will process chunk by chunk
+        loop {
+            let occupied = capacity - producer.vacant_len();
+            if occupied >= target_fill {
+                break;
+            }
+            if producer.vacant_len() < self.primary_buffer.len() {
+                break;
+            }
+
+            // Fade into the pre-buffered track ahead of the current one's end
+            if matches!(self.crossfade_state, CrossfadeState::None) {
+                if let Some((track, queue_index)) = self.start_auto_crossfade() {
+                    switched_to = Some(PendingSwitch {
+                        track,
+                        queue_index,
+                        at_sample: self.samples_pushed,
+                    });
+                }
+            }
+
+            // Check crossfade status
+            let mut fade: Option<(usize, usize)> = None;
+
+            if let CrossfadeState::Fading { position, length } = self.crossfade_state {
+                if position < length {
+                    fade = Some((position, length));
                 } else {
+                    // Fade complete! Inline finish_crossfade logic
+                    self.primary_process = self.secondary_process.take();
+                    self.primary_loudness = self.secondary_loudness;
+                    self.crossfade_state = CrossfadeState::None;
+                    continue;
+                }
+            }
+
+            // Jump back to A once playback reaches B
+            if fade.is_none() {
+                if let Some((start_ms, end_ms)) = self.ab_loop.range() {
+                    if self.current_position_ms >= end_ms && self.loop_back(start_ms) {
+                        continue;
+                    }
+                }
+            }
+
+            // Read Primary
+            let mut primary_read = match self.primary_process.as_mut().map(|p| p.read_samples(&mut self.primary_buffer)) {
+                Some(Ok(n)) => n,
+                Some(Err(e)) => {
+                    error!("Decoder error: {}", e);
+                    self.emit(EVENT_PLAYBACK_ERROR, format!("Decoder error: {}", e));
                     0
-                };
+                }
+                None => 0,
+            };
 
-                if primary_read == 0 && !is_fading {
-                    track_finished = true;
-                    break;
+            // Past the trimmed end counts as EOF, and nothing past B is played
+            if fade.is_none() {
+                if let Some(left) = self.samples_until_trim_end() {
+                    primary_read = primary_read.min(left);
+                }
+                if let Some(left) = self.samples_until_loop_end() {
+                    primary_read = primary_read.min(left);
                 }
+            }
 
-                // If fading, read secondary
-                if is_fading && self.secondary_process.is_some() {
-                    let secondary_buffer = &mut self.secondary_buffer;
-                    let secondary_read = if let Some(proc) = &mut self.secondary_process {
-                        match proc.read_samples(secondary_buffer) {
-                            Ok(n) => n,
-                            Err(_) => 0,
-                        }
-                    } else {
-                        0
-                    };
+            if primary_read == 0 && fade.is_none() {
+                // Splice the pre-buffered track into the same ring buffer
+                if let Some(next) = self.next_track.take() {
+                    self.primary_process = Some(next.process);
+                    self.primary_loudness = next.item.loudness;
+                    self.set_current_track(
+                        &next.item,
+                        next.duration_ms,
+                        next.source,
+                        next.start_ms,
+                    );
+                    switched_to = Some(PendingSwitch {
+                        track: TrackChange::new(&next.item, next.duration_ms),
+                        queue_index: next.queue_index,
+                        at_sample: self.samples_pushed,
+                    });
+                    continue;
+                }
+
+                track_finished = true;
+                break;
+            }
+
+            let primary_buffer = &mut self.primary_buffer;
 
-                    // Mixing logic
-                    // We drive the output by the Secondary (incoming) track since it's the future.
-                    let mix_count = secondary_read;
+            normalization::apply_gain(
+                &mut primary_buffer[..primary_read],
+                self.normalization.gain_for(&self.primary_loudness),
+            );
 
-                    if mix_count == 0 {
-                        // Secondary finished or failed?
-                        // If secondary is empty, we probably shouldn't be fading or track ended.
-                        // Treat as track finished for safety to avoid infinite loop.
-                        track_finished = true;
+            if self.seek_fade_remaining > 0 {
+                for sample in primary_buffer[..primary_read].iter_mut() {
+                    if self.seek_fade_remaining == 0 {
                         break;
                     }
+                    *sample *= 1.0 - self.seek_fade_remaining as f32 / seek_fade_len as f32;
+                    self.seek_fade_remaining -= 1;
+                }
+            }
 
-                    // Zero-pad primary if it ended early
-                    if primary_read < mix_count {
-                        for i in primary_read..mix_count {
-                            primary_buffer[i] = 0.0;
-                        }
+            // If fading, read secondary
+            let pushed = if let (Some((position, length)), Some(proc)) =
+                (fade, self.secondary_process.as_mut())
+            {
+                let secondary_buffer = &mut self.secondary_buffer;
+                let secondary_read = match proc.read_samples(secondary_buffer) {
+                    Ok(n) => n,
+                    Err(e) => {
+                        error!("Decoder error: {}", e);
+                        0
                     }
+                };
 
-                    for i in 0..mix_count {
-                        let p = primary_buffer[i];
-                        let s = secondary_buffer[i];
-                        primary_buffer[i] =
-                            (p * (1.0 - crossfade_progress)) + (s * crossfade_progress);
-                    }
+                // Mixing logic
+                // We drive the output by the Secondary (incoming) track since it's the future.
+                let mix_count = secondary_read;
+                normalization::apply_gain(
+                    &mut secondary_buffer[..mix_count],
+                    self.normalization.gain_for(&self.secondary_loudness),
+                );
+
+                if mix_count == 0 {
+                    // Secondary finished or failed?
+                    // If secondary is empty, we probably shouldn't be fading or track ended.
+                    // Treat as track finished for safety to avoid infinite loop.
+                    track_finished = true;
+                    break;
+                }
+
+                // Zero-pad primary if it ended early
+                if primary_read < mix_count {
+                    primary_buffer[primary_read..mix_count].fill(0.0);
+                }
 
-                    producer.push_slice(&primary_buffer[..mix_count]);
+                // Gains move once per frame so the channels stay in step
+                let curve = self.crossfade.curve;
+                for (i, (p, s)) in primary_buffer[..mix_count]
+                    .iter_mut()
+                    .zip(&secondary_buffer[..mix_count])
+                    .enumerate()
+                {
+                    let frame_start = position + i - i % channels;
+                    let (out_gain, in_gain) = curve.gains(frame_start as f32 / length as f32);
+                    *p = *p * out_gain + *s * in_gain;
+                }
 
-                    // Inline update_stats
-                    self.samples_played += mix_count as u64;
-                    let samples_per_ms =
-                        (self.device_sample_rate as u64 * self.device_channels as u64) / 1000;
-                    if samples_per_ms > 0 {
-                        self.current_position_ms = self.samples_played / samples_per_ms;
-                    }
-                } else {
-                    // Just Primary
-                    if primary_read > 0 {
-                        producer.push_slice(&primary_buffer[..primary_read]);
-
-                        // Inline update_stats
-                        self.samples_played += primary_read as u64;
-                        let samples_per_ms =
-                            (self.device_sample_rate as u64 * self.device_channels as u64) / 1000;
-                        if samples_per_ms > 0 {
-                            self.current_position_ms = self.samples_played / samples_per_ms;
-                        }
-                    }
+                self.crossfade_state = CrossfadeState::Fading {
+                    position: position + mix_count,
+                    length,
+                };
+                mix_count
+            } else {
+                // Just Primary
+                primary_read
+            };
+
+            if pushed > 0 {
+                self.equalizer.process(&mut primary_buffer[..pushed]);
+                producer.push_slice(&primary_buffer[..pushed]);
+                self.samples_pushed += pushed as u64;
+                self.play_tracker.add(
+                    pushed,
+                    self.device_sample_rate as u64 * self.device_channels as u64,
+                    self.speed.rate,
+                );
+                if let Some(visualizer) = self.visualizer.as_mut() {
+                    visualizer.push(&primary_buffer[..pushed]);
                 }
+
+                // Inline update_stats
+                self.samples_played += pushed as u64;
+                self.current_position_ms = media_position_ms(
+                    self.position_base_ms,
+                    self.samples_played,
+                    self.device_sample_rate,
+                    self.device_channels,
+                    self.speed.rate,
+                );
             }
-        } // End of producer borrow scope
+        }
+
+        self.producer = Some(producer);
+
+        if let Some(pending) = switched_to {
+            // A switch still waiting for the output is announced first
+            self.flush_pending_switch();
+            self.pending_switch = Some(pending);
+        }
 
         if track_finished {
-            self.handle_end_of_track();
+            // Let the output play what is still buffered before stopping it
+            self.primary_process = None;
+            self.secondary_process = None;
+            self.crossfade_state = CrossfadeState::None;
+            self.draining = true;
+        }
+    }
+
+    /// Start fading into the pre-buffered track once the current one is
+    /// within the crossfade length of its end
+    fn start_auto_crossfade(&mut self) -> Option<(TrackChange, Option<usize>)> {
+        let end_ms = self.end_ms();
+        if !self.crossfade.is_enabled() || end_ms == 0 || self.ab_loop.range().is_some() {
+            return None;
+        }
+        let next = self.next_track.as_ref()?;
+
+        // Output time left, which differs from media time at other speeds
+        let remaining_ms = (end_ms.saturating_sub(self.current_position_ms) as f64
+            / self.speed.rate as f64) as u64;
+        if remaining_ms > self.crossfade.duration_ms {
+            return None;
+        }
+
+        // Leave album transitions to the gapless splice
+        if self.crossfade.skip_same_album
+            && crossfade::same_album(self.current_album.as_deref(), next.item.album.as_deref())
+        {
+            return None;
+        }
+
+        let length = self.samples_for_ms(remaining_ms);
+        if length == 0 {
+            return None;
+        }
+
+        let next = self.next_track.take()?;
+        info!("Crossfading into next track: {}", next.item.file_path);
+        self.secondary_process = Some(next.process);
+        self.secondary_loudness = next.item.loudness;
+        self.crossfade_state = CrossfadeState::Fading {
+            position: 0,
+            length,
+        };
+        self.set_current_track(&next.item, next.duration_ms, next.source, next.start_ms);
+
+        let change = TrackChange::new(&next.item, next.duration_ms);
+        Some((change, next.queue_index))
+    }
+
+    /// Point the position bookkeeping at a track that starts playing now,
+    /// `start_ms` into the file
+    fn set_current_track(
+        &mut self,
+        item: &QueueItem,
+        duration_ms: u64,
+        source: StreamFormat,
+        start_ms: u64,
+    ) {
+        let outgoing = std::mem::take(&mut self.play_tracker);
+        self.hand_over_play(outgoing, self.samples_pushed);
+        self.play_tracker.start(item.id, duration_ms);
+        self.current_file_path = Some(item.file_path.clone());
+        self.current_album = item.album.clone();
+        self.current_silence = item.silence;
+        self.current_source = source;
+        if self.ab_loop != AbLoop::default() {
+            // Loop points belong to the track they were set on
+            self.ab_loop = AbLoop::default();
+            self.emit(EVENT_LOOP_CHANGED, self.ab_loop);
+        }
+        self.duration_ms = duration_ms;
+        self.current_position_ms = start_ms;
+        self.samples_played = 0;
+        self.position_base_ms = start_ms;
+    }
+
+    /// Output samples left before B, if a loop is set
+    fn samples_until_loop_end(&self) -> Option<usize> {
+        let (_, end_ms) = self.ab_loop.range()?;
+        let remaining_ms =
+            end_ms.saturating_sub(self.current_position_ms) as f64 / self.speed.rate as f64;
+        Some(self.samples_for_ms(remaining_ms.ceil() as u64))
+    }
+
+    /// Reopen the current track at A without flushing what is buffered, so
+    /// the loop continues seamlessly
+    fn loop_back(&mut self, start_ms: u64) -> bool {
+        let Some(path) = self.current_file_path.clone() else {
+            return false;
+        };
+        match self.spawn_decoder(&path, Some(start_ms)) {
+            Ok(process) => {
+                self.primary_process = Some(process);
+                self.current_position_ms = start_ms;
+                self.position_base_ms = start_ms;
+                self.samples_played = 0;
+                true
+            }
+            Err(e) => {
+                error!("Failed to loop back to {}ms: {}", start_ms, e);
+                self.ab_loop = AbLoop::default();
+                self.emit(EVENT_LOOP_CHANGED, self.ab_loop);
+                false
+            }
+        }
+    }
+
+    /// Media position the output is playing now, behind the decoder by what is buffered
+    fn played_position_ms(&self) -> u64 {
+        let queued = self.producer.as_ref().map_or(0, |p| p.occupied_len()) as u64;
+        let samples_per_sec = self.device_sample_rate as u64 * self.device_channels as u64;
+        let queued_ms = (queued * 1000).checked_div(samples_per_sec).unwrap_or(0);
+        self.current_position_ms
+            .saturating_sub((queued_ms as f64 * self.speed.rate as f64) as u64)
+    }
+
+    /// Where playback of `item` should begin
+    fn trim_start_ms(&self, item: &QueueItem) -> u64 {
+        match item.silence.silence_start_ms {
+            Some(start_ms) if self.skip_silence => start_ms,
+            _ => 0,
         }
     }
 
+    /// Where the current track should be treated as finished
+    fn end_ms(&self) -> u64 {
+        match self.current_silence.silence_end_ms {
+            Some(end_ms) if self.skip_silence => end_ms,
+            _ => self.duration_ms,
+        }
+    }
+
+    /// Output samples left before the trimmed end of the current track
+    fn samples_until_trim_end(&self) -> Option<usize> {
+        if !self.skip_silence {
+            return None;
+        }
+        let end_ms = self.current_silence.silence_end_ms?;
+        let remaining_ms =
+            end_ms.saturating_sub(self.current_position_ms) as f64 / self.speed.rate as f64;
+        Some(self.samples_for_ms(remaining_ms.ceil() as u64))
+    }
+
     fn handle_device_change(&mut self) {
-        self.device_error.store(false, Ordering::Relaxed);
+        self.controls.device_error.store(false, Ordering::Relaxed);
         if self.current_file_path.is_some() {
-            self._current_stream = None;
+            let previous_format = (self.device_sample_rate, self.device_channels);
+            let position_ms = self.played_position_ms();
+            self.flush_pending_switch();
+            self.output.close();
             self.producer = None;
-            self.recreate_cpal_stream(self.device_sample_rate, self.device_channels);
+            self.open_output(self.current_source.sample_rate, self.current_source.channels);
+
+            // The decoders were opened for the old device format
+            if previous_format != (self.device_sample_rate, self.device_channels) {
+                self.seek(position_ms);
+                if let Some(next) = self.next_track.take() {
+                    self.prepare_next_track(next.item, next.queue_index);
+                }
+            }
         }
     }
 
     fn handle_end_of_track(&mut self) {
         info!("Track finished naturally");
+
+        if self.sleep_ends_with_track() {
+            self.finish_sleep_timer();
+            return;
+        }
+        if let Some(timer) = self.sleep_timer.as_mut() {
+            timer.track_finished();
+        }
+
+        // Nothing was pre-buffered (e.g. the spawn failed), so fall back to a hard cut
+        let next = self.queue.lock().unwrap().advance(true);
+        if next.is_some() {
+            self.stop();
+            self.play_current_queue_item();
+            return;
+        }
+
         self.stop();
-        self.app_handle.emit(EVENT_PLAYBACK_FINISHED, ()).ok();
+        self.on_queue_changed();
+        self.emit(EVENT_PLAYBACK_FINISHED, ());
     }
 
     fn pause(&mut self) {
         info!("Playback paused");
-        self.is_playing.store(false, Ordering::Relaxed);
+        self.controls.is_playing.store(false, Ordering::Relaxed);
         {
             let mut s = self.state.lock().unwrap();
             s.is_paused = true;
@@ -728,7 +1638,7 @@ impl AudioWorker {
 
     fn resume(&mut self) {
         info!("Playback resumed");
-        self.is_playing.store(true, Ordering::Relaxed);
+        self.controls.is_playing.store(true, Ordering::Relaxed);
         {
             let mut s = self.state.lock().unwrap();
             s.is_paused = false;
@@ -740,22 +1650,30 @@ impl AudioWorker {
 
     fn stop(&mut self) {
         info!("Playback stopped");
-        self.is_playing.store(false, Ordering::Relaxed);
+        self.controls.is_playing.store(false, Ordering::Relaxed);
+        self.discard_queued_play();
+        self.finish_play();
 
-        if let Some(mut p) = self.primary_process.take() {
-            p.kill();
-        }
-        if let Some(mut s) = self.secondary_process.take() {
-            s.kill();
-        }
+        self.primary_process = None;
+        self.secondary_process = None;
+        self.next_track = None;
 
-        self._current_stream = None;
+        self.output.close();
         self.producer = None;
         self.current_file_path = None;
+        self.current_album = None;
+        self.current_silence = TrackSilence::default();
+        self.ab_loop = AbLoop::default();
         self.current_position_ms = 0;
         self.duration_ms = 0;
+        self.position_base_ms = 0;
         self.samples_played = 0;
+        self.seek_fade_remaining = 0;
+        self.draining = false;
         self.crossfade_state = CrossfadeState::None;
+        if let Some(visualizer) = self.visualizer.as_mut() {
+            visualizer.clear();
+        }
 
         {
             let mut s = self.state.lock().unwrap();
@@ -765,9 +1683,9 @@ impl AudioWorker {
             s.current_file = None;
         }
 
-        if let Ok(mut c) = self.media_controls.lock() {
+        self.with_media_controls(|c| {
             c.set_playback(MediaPlayback::Stopped).ok();
-        }
+        });
 
         self.emit_state();
     }
@@ -779,30 +1697,33 @@ impl AudioWorker {
         };
 
         // Stop any fading, just hard seek primary
-        if let Some(mut s) = self.secondary_process.take() {
-            s.kill();
-        }
-        if let Some(mut p) = self.primary_process.take() {
-            p.kill();
+        if self.secondary_process.take().is_some() {
+            // current_file_path already points at the incoming track
+            self.primary_loudness = self.secondary_loudness;
         }
+        self.primary_process = None;
         self.crossfade_state = CrossfadeState::None;
+        self.draining = false;
+
+        // Keep the output stream alive and only drop the buffered audio
+        if self.producer.is_some() {
+            self.discard_queued_play();
+            self.controls.flush_pending.store(true, Ordering::Release);
+            if let Some(visualizer) = self.visualizer.as_mut() {
+                visualizer.clear();
+            }
+        } else {
+            self.open_output(self.current_source.sample_rate, self.current_source.channels);
+        }
 
-        self.producer = None;
-        self._current_stream = None;
-
-        match FFmpegProcess::spawn_at(
-            &path,
-            self.device_sample_rate,
-            self.device_channels,
-            Some(pos_ms),
-        ) {
+        match self.spawn_decoder(&path, Some(pos_ms)) {
             Ok(process) => {
                 self.primary_process = Some(process);
-                self.recreate_cpal_stream(self.device_sample_rate, self.device_channels);
+                self.seek_fade_remaining = self.seek_fade_len();
 
                 self.current_position_ms = pos_ms;
-                self.samples_played =
-                    pos_ms * (self.device_sample_rate as u64 * self.device_channels as u64) / 1000;
+                self.position_base_ms = pos_ms;
+                self.samples_played = 0;
 
                 {
                     let mut s = self.state.lock().unwrap();
@@ -814,21 +1735,208 @@ impl AudioWorker {
         }
     }
 
+    fn seek_fade_len(&self) -> usize {
+        self.samples_for_ms(SEEK_FADE_IN_MS)
+    }
+
+    /// Output samples covering `ms` of audio, in whole frames
+    fn samples_for_ms(&self, ms: u64) -> usize {
+        (self.device_sample_rate as u64 * ms / 1000) as usize * self.device_channels as usize
+    }
+
+    fn apply_pending_seek(&mut self) {
+        if let Some(pos_ms) = self.pending_seek.take() {
+            self.seek(pos_ms);
+        }
+    }
+
+    fn spawn_decoder(&self, path: &str, seek_ms: Option<u64>) -> std::io::Result<Box<dyn Decoder>> {
+        let filter = self.speed.filter(self.device_sample_rate);
+        decoder::open(
+            self.decoder_backend,
+            path,
+            self.device_sample_rate,
+            self.device_channels,
+            seek_ms,
+            filter.as_deref(),
+        )
+    }
+
+    fn set_speed(&mut self, speed: PlaybackSpeed) {
+        if speed == self.speed {
+            return;
+        }
+        info!("Playback speed set to {}x ({:?})", speed.rate, speed.mode);
+        // Measured at the old rate, which the buffered audio was rendered at
+        let position_ms = self.played_position_ms();
+        self.speed = speed;
+        self.state.lock().unwrap().speed = speed.rate;
+
+        // Restart the decoders so the new filter graph takes effect
+        if self.current_file_path.is_some() {
+            self.seek(position_ms);
+            if let Some(next) = self.next_track.take() {
+                self.prepare_next_track(next.item, next.queue_index);
+            }
+        }
+        self.emit_state();
+    }
+
     fn emit_progress(&self) {
         let mut s = self.state.lock().unwrap();
         if s.is_playing && !s.is_paused {
             s.position_ms = self.current_position_ms;
-            self.app_handle.emit(EVENT_PLAYBACK_PROGRESS, &*s).ok();
+            self.emit(EVENT_PLAYBACK_PROGRESS, &*s);
+        }
+    }
+
+    /// Analyze what the output is playing for subscribed visualizers
+    fn emit_visualization(&mut self) {
+        let Some(visualizer) = self.visualizer.as_mut() else {
+            return;
+        };
+        if !self.controls.is_playing.load(Ordering::Relaxed) {
+            return;
+        }
+        let queued = self.producer.as_ref().map_or(0, |p| p.occupied_len());
+        if let Some(frame) = visualizer.frame(queued) {
+            events::emit(self.events.as_ref(), EVENT_VISUALIZATION, &frame);
         }
     }
 
+    /// Record the current play in the listening history
+    fn finish_play(&mut self) {
+        let current = std::mem::take(&mut self.play_tracker);
+        self.record_play(current);
+    }
+
+    fn record_play(&self, mut tracker: PlayTracker) {
+        if let (Some(play), Some(history)) =
+            (tracker.finish(&self.completion), self.history.as_ref())
+        {
+            history.record(&play);
+        }
+    }
+
+    /// Keep the play of a replaced track open until the output reaches
+    /// `until_sample`, where the track's last buffered sample ends
+    fn hand_over_play(&mut self, tracker: PlayTracker, until_sample: u64) {
+        // A track shorter than the buffer plays out before the next one ends
+        if let Some(previous) = self.outgoing_play.take() {
+            self.record_play(previous.tracker);
+        }
+        self.outgoing_play = Some(OutgoingPlay {
+            tracker,
+            until_sample,
+        });
+    }
+
+    /// Record the replaced track's play once the output has played it out
+    fn poll_outgoing_play(&mut self) {
+        let reached = self.outgoing_play.as_ref().is_some_and(|outgoing| {
+            self.controls.samples_rendered.load(Ordering::Acquire) >= outgoing.until_sample
+        });
+        if reached {
+            self.close_outgoing_play();
+        }
+    }
+
+    /// Record the replaced track's play without the part the output has not
+    /// reached, which is about to be dropped
+    fn close_outgoing_play(&mut self) {
+        if let Some(mut outgoing) = self.outgoing_play.take() {
+            let rendered = self.controls.samples_rendered.load(Ordering::Acquire);
+            outgoing.tracker.discard(
+                outgoing.until_sample.saturating_sub(rendered) as usize,
+                self.device_sample_rate as u64 * self.device_channels as u64,
+                self.speed.rate,
+            );
+            self.record_play(outgoing.tracker);
+        }
+    }
+
+    /// Uncount audio that is about to be dropped from the ring buffer unheard
+    fn discard_queued_play(&mut self) {
+        // The replaced track's unplayed part comes off its own play
+        self.close_outgoing_play();
+        let queued = self.producer.as_ref().map_or(0, |p| p.occupied_len());
+        self.play_tracker.discard(
+            queued,
+            self.device_sample_rate as u64 * self.device_channels as u64,
+            self.speed.rate,
+        );
+    }
+
+    fn sleep_ends_with_track(&self) -> bool {
+        self.sleep_timer
+            .as_ref()
+            .is_some_and(|timer| timer.ends_with_current_track())
+    }
+
+    /// Output time until the current track ends, including what is still buffered
+    fn track_remaining_ms(&self) -> u64 {
+        let queued = self.producer.as_ref().map_or(0, |p| p.occupied_len()) as u64;
+        let samples_per_sec = self.device_sample_rate as u64 * self.device_channels as u64;
+        let queued_ms = (queued * 1000).checked_div(samples_per_sec).unwrap_or(0);
+        if self.draining {
+            return queued_ms;
+        }
+        let decode_ms = self.end_ms().saturating_sub(self.current_position_ms) as f64
+            / self.speed.rate as f64;
+        decode_ms as u64 + queued_ms
+    }
+
+    /// Fade the output toward the sleep timer's end and report the countdown
+    fn tick_sleep_timer(&mut self) {
+        let Some(timer) = self.sleep_timer.as_ref() else {
+            return;
+        };
+        let now = Instant::now();
+        if timer.is_expired(now) {
+            self.finish_sleep_timer();
+            return;
+        }
+
+        let track_remaining = (self.current_file_path.is_some() && timer.ends_with_current_track())
+            .then(|| self.track_remaining_ms());
+        let remaining = timer.remaining_ms(now, track_remaining);
+        let volume = self.state.lock().unwrap().volume * timer.gain(remaining);
+        self.controls
+            .volume
+            .store(f32::to_bits(volume) as u64, Ordering::Relaxed);
+
+        let Some(timer) = self.sleep_timer.as_mut() else {
+            return;
+        };
+        if timer.report_due(now) {
+            let status = timer.status(remaining);
+            self.emit(EVENT_SLEEP_TIMER, status);
+        }
+    }
+
+    fn finish_sleep_timer(&mut self) {
+        info!("Sleep timer finished");
+        self.sleep_timer = None;
+        self.stop();
+        self.restore_volume();
+        self.emit(EVENT_SLEEP_TIMER, SleepTimerStatus::inactive());
+    }
+
+    /// Undo any sleep fade so the next session starts at the user's volume
+    fn restore_volume(&self) {
+        let volume = self.state.lock().unwrap().volume;
+        self.controls
+            .volume
+            .store(f32::to_bits(volume) as u64, Ordering::Relaxed);
+    }
+
     fn emit_state(&self) {
         let s = self.state.lock().unwrap();
-        self.app_handle.emit(EVENT_PLAYBACK_STATE, &*s).ok();
+        self.emit(EVENT_PLAYBACK_STATE, &*s);
     }
 
     fn update_media_controls(&self) {
-        if let Ok(mut c) = self.media_controls.lock() {
+        self.with_media_controls(|c| {
             let s = self.state.lock().unwrap();
             let pos = MediaPosition(Duration::from_millis(s.position_ms));
             if s.is_paused {
@@ -842,15 +1950,64 @@ impl AudioWorker {
                 })
                 .ok();
             }
+        });
+    }
+
+    /// Run `f` on the OS media controls, if this engine has any
+    fn with_media_controls(&self, f: impl FnOnce(&mut MediaControls)) {
+        if let Some(Ok(mut c)) = self.media_controls.as_ref().map(|c| c.lock()) {
+            f(&mut c);
         }
     }
+
+    fn emit<S: Serialize>(&self, event: &str, payload: S) {
+        events::emit(self.events.as_ref(), event, payload);
+    }
+}
+
+/// Convert output samples pushed since `base_ms` into media time
+fn media_position_ms(base_ms: u64, samples: u64, sample_rate: u32, channels: u16, rate: f32) -> u64 {
+    let samples_per_ms = (sample_rate as u64 * channels as u64) as f64 / 1000.0;
+    if samples_per_ms == 0.0 {
+        return base_ms;
+    }
+    base_ms + (samples as f64 / samples_per_ms * rate as f64) as u64
+}
+
+/// Format a track was encoded in, which match-source mode opens the output at
+fn source_format(metadata: &AudioMetadata) -> StreamFormat {
+    StreamFormat {
+        sample_rate: metadata.sample_rate,
+        channels: metadata.channels,
+    }
 }
 
 pub struct AudioState(pub Arc<AudioEngine>);
-pub fn start_progress_tracking(_app: AppHandle, _engine: Arc<AudioEngine>) {}
 
 use crate::error::AppError;
 
+/// Build a queue item for the single-track commands
+fn single_track(
+    path: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    cover: Option<String>,
+    loudness: Option<TrackLoudness>,
+) -> QueueItem {
+    QueueItem {
+        id: None,
+        title: title.unwrap_or("Unknown".into()),
+        artist,
+        album,
+        duration_ms: 0,
+        file_path: path,
+        artwork_path: cover,
+        loudness: loudness.unwrap_or_default(),
+        silence: TrackSilence::default(),
+    }
+}
+
 #[tauri::command]
 pub fn audio_play(
     state: tauri::State<AudioState>,
@@ -859,14 +2016,110 @@ pub fn audio_play(
     artist: Option<String>,
     album: Option<String>,
     cover: Option<String>,
+    loudness: Option<TrackLoudness>,
+) -> Result<(), AppError> {
+    state
+        .0
+        .play(single_track(path, title, artist, album, cover, loudness));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_enqueue_next(
+    state: tauri::State<AudioState>,
+    path: String,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    cover: Option<String>,
+    loudness: Option<TrackLoudness>,
+) -> Result<(), AppError> {
+    state
+        .0
+        .enqueue_next(single_track(path, title, artist, album, cover, loudness));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_clear_next(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.clear_next();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_set(
+    state: tauri::State<AudioState>,
+    items: Vec<QueueItem>,
+    start_index: usize,
+) -> Result<(), AppError> {
+    state.0.set_queue(items, start_index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_insert(
+    state: tauri::State<AudioState>,
+    items: Vec<QueueItem>,
+    index: Option<usize>,
 ) -> Result<(), AppError> {
-    state.0.play(
-        path,
-        title.unwrap_or("Unknown".into()),
-        artist.unwrap_or("Unknown".into()),
-        album.unwrap_or("Unknown".into()),
-        cover,
-    );
+    state.0.insert_into_queue(items, index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_move(
+    state: tauri::State<AudioState>,
+    from: usize,
+    to: usize,
+) -> Result<(), AppError> {
+    state.0.move_in_queue(from, to);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_remove(state: tauri::State<AudioState>, index: usize) -> Result<(), AppError> {
+    state.0.remove_from_queue(index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_clear(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.clear_queue();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_jump(state: tauri::State<AudioState>, index: usize) -> Result<(), AppError> {
+    state.0.jump_to_queue_index(index);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_queue_get(state: tauri::State<AudioState>) -> QueueSnapshot {
+    state.0.get_queue()
+}
+
+#[tauri::command]
+pub fn audio_set_shuffle(state: tauri::State<AudioState>, enabled: bool) -> Result<(), AppError> {
+    state.0.set_shuffle(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_repeat(state: tauri::State<AudioState>, mode: RepeatMode) -> Result<(), AppError> {
+    state.0.set_repeat(mode);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_next(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.next();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_previous(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.previous();
     Ok(())
 }
 
@@ -914,22 +2167,265 @@ pub fn audio_get_devices() -> Result<Vec<AudioDevice>, AppError> {
 
 #[tauri::command]
 pub fn audio_set_device(
+    app: AppHandle,
     state: tauri::State<AudioState>,
     device_name: String,
 ) -> Result<(), AppError> {
     state.0.set_device(device_name);
+    restore_channel_mix(&app, &state.0)
+}
+
+/// Apply the channel mix saved for the current device, or the neutral mix
+fn restore_channel_mix(app: &AppHandle, engine: &AudioEngine) -> Result<(), AppError> {
+    let db_path = get_library_db_path(app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    let mix = db
+        .get_channel_mix(&engine.device_key())
+        .map_err(|e| AppError::Database(e.to_string()))?;
+    engine.set_channel_mix(mix.unwrap_or_default());
     Ok(())
 }
 
+#[tauri::command]
+pub fn audio_restore_channel_mix(
+    app: AppHandle,
+    state: tauri::State<AudioState>,
+) -> Result<(), AppError> {
+    restore_channel_mix(&app, &state.0)
+}
+
+/// Set balance, mono and swap, and remember them for the current device
+#[tauri::command]
+pub fn audio_set_channel_mix(
+    app: AppHandle,
+    state: tauri::State<AudioState>,
+    balance: f32,
+    mono: bool,
+    swap: bool,
+) -> Result<(), AppError> {
+    let mix = ChannelMix::new(balance, mono, swap);
+    state.0.set_channel_mix(mix);
+
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.save_channel_mix(&state.0.device_key(), &mix)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_get_channel_mix(state: tauri::State<AudioState>) -> ChannelMix {
+    state.0.get_channel_mix()
+}
+
 #[tauri::command]
 pub fn audio_set_crossfade(
     state: tauri::State<AudioState>,
     duration_ms: u64,
+    curve: Option<FadeCurve>,
+    skip_same_album: Option<bool>,
+) -> Result<(), AppError> {
+    state.0.set_crossfade(CrossfadeSettings {
+        duration_ms,
+        curve: curve.unwrap_or_default(),
+        skip_same_album: skip_same_album.unwrap_or(false),
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_normalization(
+    state: tauri::State<AudioState>,
+    mode: NormalizationMode,
+    preamp_db: Option<f32>,
+) -> Result<(), AppError> {
+    state.0.set_normalization(mode, preamp_db.unwrap_or(0.0));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_equalizer(
+    state: tauri::State<AudioState>,
+    settings: EqSettings,
 ) -> Result<(), AppError> {
-    state.0.set_crossfade(duration_ms);
+    state.0.set_equalizer(settings);
     Ok(())
 }
 
+#[tauri::command]
+pub fn audio_set_speed(
+    state: tauri::State<AudioState>,
+    rate: f32,
+    mode: Option<SpeedMode>,
+) -> Result<(), AppError> {
+    state.0.set_speed(rate, mode.unwrap_or_default());
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_match_source_rate(
+    state: tauri::State<AudioState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    state.0.set_match_source_rate(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_subscribe_visualization(
+    state: tauri::State<AudioState>,
+    rate_hz: Option<u32>,
+    bands: Option<usize>,
+) -> Result<(), AppError> {
+    let defaults = VisualizerSettings::default();
+    state.0.set_visualizer(Some(VisualizerSettings {
+        rate_hz: rate_hz.unwrap_or(defaults.rate_hz),
+        bands: bands.unwrap_or(defaults.bands),
+    }));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_unsubscribe_visualization(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.set_visualizer(None);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_start_sleep_timer(
+    state: tauri::State<AudioState>,
+    mode: SleepTimerMode,
+    fade_ms: Option<u64>,
+) -> Result<(), AppError> {
+    state
+        .0
+        .start_sleep_timer(mode, fade_ms.unwrap_or(sleep_timer::DEFAULT_FADE_MS));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_cancel_sleep_timer(state: tauri::State<AudioState>) -> Result<(), AppError> {
+    state.0.cancel_sleep_timer();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_decoder_backend(
+    state: tauri::State<AudioState>,
+    backend: DecoderBackend,
+) -> Result<(), AppError> {
+    state.0.set_decoder_backend(backend);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_skip_silence(
+    state: tauri::State<AudioState>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    state.0.set_skip_silence(enabled);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_set_history_threshold(
+    state: tauri::State<AudioState>,
+    percent: Option<f32>,
+    max_ms: Option<u64>,
+) -> Result<(), AppError> {
+    let defaults = CompletionThreshold::default();
+    state.0.set_history_threshold(CompletionThreshold {
+        percent: percent.unwrap_or(defaults.percent),
+        max_ms: max_ms.unwrap_or(defaults.max_ms),
+    });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_get_equalizer(state: tauri::State<AudioState>) -> EqSettings {
+    state.0.get_equalizer()
+}
+
+#[tauri::command]
+pub fn audio_get_eq_presets(app: AppHandle) -> Result<Vec<EqPreset>, AppError> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.get_eq_presets()
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_save_eq_preset(app: AppHandle, preset: EqPreset) -> Result<(), AppError> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.save_eq_preset(&preset)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_delete_eq_preset(app: AppHandle, name: String) -> Result<(), AppError> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.delete_eq_preset(&name)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_set_loop_point(
+    state: tauri::State<AudioState>,
+    point: LoopPoint,
+    position_ms: Option<u64>,
+) -> Result<(), AppError> {
+    state.0.set_loop_point(point, position_ms);
+    Ok(())
+}
+
+/// Loop between two points, or stop looping when either is missing
+#[tauri::command]
+pub fn audio_set_loop(
+    state: tauri::State<AudioState>,
+    start_ms: Option<u64>,
+    end_ms: Option<u64>,
+) -> Result<(), AppError> {
+    state.0.set_loop(AbLoop { start_ms, end_ms });
+    Ok(())
+}
+
+#[tauri::command]
+pub fn audio_get_track_loops(app: AppHandle, track_id: i64) -> Result<Vec<SavedLoop>, AppError> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.get_track_loops(track_id)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_save_track_loop(
+    app: AppHandle,
+    track_id: i64,
+    name: Option<String>,
+    start_ms: u64,
+    end_ms: u64,
+) -> Result<SavedLoop, AppError> {
+    if AbLoop::new(start_ms, end_ms).range().is_none() {
+        return Err(AppError::Audio(format!(
+            "Invalid loop: {}ms to {}ms",
+            start_ms, end_ms
+        )));
+    }
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.save_track_loop(track_id, name.as_deref(), start_ms, end_ms)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
+#[tauri::command]
+pub fn audio_delete_track_loop(app: AppHandle, id: i64) -> Result<(), AppError> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| AppError::Database(e.to_string()))?;
+    db.delete_track_loop(id)
+        .map_err(|e| AppError::Database(e.to_string()))
+}
+
 #[tauri::command]
 pub fn audio_get_state(state: tauri::State<AudioState>) -> PlaybackState {
     state.0.get_state()
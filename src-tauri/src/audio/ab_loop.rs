@@ -0,0 +1,73 @@
+//! A–B loop points for repeating a passage of the current track
+
+use serde::{Deserialize, Serialize};
+
+/// Shortest passage that can be looped, so a loop cannot spin on itself
+pub const MIN_LOOP_MS: u64 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LoopPoint {
+    A,
+    B,
+}
+
+/// Loop points on the current track; the loop runs once both are set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AbLoop {
+    pub start_ms: Option<u64>,
+    pub end_ms: Option<u64>,
+}
+
+impl AbLoop {
+    pub fn new(start_ms: u64, end_ms: u64) -> Self {
+        Self {
+            start_ms: Some(start_ms),
+            end_ms: Some(end_ms),
+        }
+    }
+
+    pub fn set(&mut self, point: LoopPoint, position_ms: u64) {
+        match point {
+            LoopPoint::A => self.start_ms = Some(position_ms),
+            LoopPoint::B => self.end_ms = Some(position_ms),
+        }
+    }
+
+    /// Start and end of the loop, if it is complete and long enough to play
+    pub fn range(&self) -> Option<(u64, u64)> {
+        let (start, end) = (self.start_ms?, self.end_ms?);
+        (end >= start + MIN_LOOP_MS).then_some((start, end))
+    }
+}
+
+/// A loop saved for a track in the library
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedLoop {
+    pub id: i64,
+    pub track_id: i64,
+    pub name: Option<String>,
+    pub start_ms: u64,
+    pub end_ms: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_loop_runs_once_both_points_are_set() {
+        let mut ab = AbLoop::default();
+        ab.set(LoopPoint::A, 1000);
+        assert_eq!(ab.range(), None);
+
+        ab.set(LoopPoint::B, 1020);
+        assert_eq!(ab.range(), None); // Too short
+
+        ab.set(LoopPoint::B, 4000);
+        assert_eq!(ab.range(), Some((1000, 4000)));
+
+        ab.set(LoopPoint::A, 5000);
+        assert_eq!(ab.range(), None); // B before A
+    }
+}
@@ -0,0 +1,123 @@
+//! Stereo balance, mono downmix and channel swap
+//!
+//! Applied by the renderer just before the volume, as a 2x2 matrix over the
+//! first two channels. Settings travel to the output thread packed into a
+//! single atomic so a change can never be seen half applied.
+
+use serde::{Deserialize, Serialize};
+
+const MONO_BIT: u64 = 1 << 32;
+const SWAP_BIT: u64 = 1 << 33;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChannelMix {
+    /// -1.0 is fully left, 1.0 fully right
+    pub balance: f32,
+    /// Sum both channels and play the result on each
+    pub mono: bool,
+    /// Play the left channel on the right and vice versa
+    pub swap: bool,
+}
+
+impl ChannelMix {
+    pub fn new(balance: f32, mono: bool, swap: bool) -> Self {
+        Self {
+            balance: if balance.is_finite() {
+                balance.clamp(-1.0, 1.0)
+            } else {
+                0.0
+            },
+            mono,
+            swap,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn to_bits(self) -> u64 {
+        let mut bits = self.balance.to_bits() as u64;
+        if self.mono {
+            bits |= MONO_BIT;
+        }
+        if self.swap {
+            bits |= SWAP_BIT;
+        }
+        bits
+    }
+
+    pub fn from_bits(bits: u64) -> Self {
+        Self {
+            balance: f32::from_bits(bits as u32),
+            mono: bits & MONO_BIT != 0,
+            swap: bits & SWAP_BIT != 0,
+        }
+    }
+
+    /// Rows are output channels, columns the input channels feeding them
+    pub fn matrix(&self) -> [[f32; 2]; 2] {
+        let mut matrix = if self.mono {
+            [[0.5, 0.5], [0.5, 0.5]]
+        } else if self.swap {
+            [[0.0, 1.0], [1.0, 0.0]]
+        } else {
+            [[1.0, 0.0], [0.0, 1.0]]
+        };
+
+        // Balance only ever attenuates the side it moves away from
+        let left = (1.0 - self.balance).min(1.0);
+        let right = (1.0 + self.balance).min(1.0);
+        for gain in matrix[0].iter_mut() {
+            *gain *= left;
+        }
+        for gain in matrix[1].iter_mut() {
+            *gain *= right;
+        }
+        matrix
+    }
+}
+
+/// Mix the first two channels of each interleaved frame through `matrix`
+pub fn apply(matrix: &[[f32; 2]; 2], data: &mut [f32], channels: usize) {
+    if channels < 2 {
+        return;
+    }
+    for frame in data.chunks_exact_mut(channels) {
+        let (l, r) = (frame[0], frame[1]);
+        frame[0] = matrix[0][0] * l + matrix[0][1] * r;
+        frame[1] = matrix[1][0] * l + matrix[1][1] * r;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mixed(mix: ChannelMix) -> Vec<f32> {
+        let mut data = vec![0.8, 0.2, 0.8, 0.2];
+        apply(&mix.matrix(), &mut data, 2);
+        data
+    }
+
+    #[test]
+    fn test_matrix_stages() {
+        assert_eq!(mixed(ChannelMix::default()), vec![0.8, 0.2, 0.8, 0.2]);
+        assert_eq!(
+            mixed(ChannelMix::new(0.0, false, true)),
+            vec![0.2, 0.8, 0.2, 0.8]
+        );
+        assert_eq!(
+            mixed(ChannelMix::new(0.0, true, false)),
+            vec![0.5, 0.5, 0.5, 0.5]
+        );
+
+        // Halfway right halves the left channel and leaves the right alone
+        let right = mixed(ChannelMix::new(0.5, false, false));
+        assert_eq!(right[..2], [0.4, 0.2]);
+
+        let mix = ChannelMix::new(-0.25, true, true);
+        assert_eq!(ChannelMix::from_bits(mix.to_bits()), mix);
+        assert_eq!(ChannelMix::new(f32::NAN, false, false).balance, 0.0);
+    }
+}
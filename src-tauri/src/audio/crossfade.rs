@@ -0,0 +1,97 @@
+//! Crossfade settings and fade curve shapes
+
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+
+/// Range covered by the logarithmic curve, from silence to full level
+const LOG_RANGE_DB: f32 = 60.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FadeCurve {
+    #[default]
+    Linear,
+    /// Keeps the summed power constant, so uncorrelated tracks do not dip
+    EqualPower,
+    /// Linear in decibels, which sounds even to the ear
+    Logarithmic,
+    /// Slow start and end with a quicker middle
+    SCurve,
+}
+
+impl FadeCurve {
+    /// Gains for the outgoing and incoming track at `progress` (0..=1)
+    pub fn gains(&self, progress: f32) -> (f32, f32) {
+        let x = progress.clamp(0.0, 1.0);
+        match self {
+            FadeCurve::Linear => (1.0 - x, x),
+            FadeCurve::EqualPower => ((x * FRAC_PI_2).cos(), (x * FRAC_PI_2).sin()),
+            FadeCurve::Logarithmic => (log_fade_in(1.0 - x), log_fade_in(x)),
+            FadeCurve::SCurve => {
+                let fade_in = x * x * (3.0 - 2.0 * x);
+                (1.0 - fade_in, fade_in)
+            }
+        }
+    }
+}
+
+fn log_fade_in(x: f32) -> f32 {
+    if x <= 0.0 {
+        0.0
+    } else {
+        10f32.powf(-LOG_RANGE_DB * (1.0 - x) / 20.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CrossfadeSettings {
+    pub duration_ms: u64,
+    pub curve: FadeCurve,
+    /// Play consecutive tracks of one album back to back instead of fading
+    pub skip_same_album: bool,
+}
+
+impl CrossfadeSettings {
+    pub fn is_enabled(&self) -> bool {
+        self.duration_ms > 0
+    }
+}
+
+/// Whether two tracks are neighbours on the same album
+pub fn same_album(current: Option<&str>, next: Option<&str>) -> bool {
+    match (current, next) {
+        (Some(a), Some(b)) => !a.trim().is_empty() && a.trim().eq_ignore_ascii_case(b.trim()),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curves_start_and_end_at_full_level() {
+        for curve in [
+            FadeCurve::Linear,
+            FadeCurve::EqualPower,
+            FadeCurve::Logarithmic,
+            FadeCurve::SCurve,
+        ] {
+            let (out_start, in_start) = curve.gains(0.0);
+            let (out_end, in_end) = curve.gains(1.0);
+            assert!((out_start - 1.0).abs() < 1e-6 && in_start.abs() < 1e-6, "{:?}", curve);
+            assert!(out_end.abs() < 1e-6 && (in_end - 1.0).abs() < 1e-6, "{:?}", curve);
+        }
+
+        let (out_mid, in_mid) = FadeCurve::EqualPower.gains(0.5);
+        assert!((out_mid * out_mid + in_mid * in_mid - 1.0).abs() < 1e-6);
+
+        // -30 dB halfway through a 60 dB range
+        let (_, log_mid) = FadeCurve::Logarithmic.gains(0.5);
+        assert!((log_mid - 0.0316).abs() < 0.001);
+
+        assert!(same_album(Some("Abbey Road"), Some("abbey road ")));
+        assert!(!same_album(Some(""), Some("")));
+        assert!(!same_album(None, Some("Abbey Road")));
+    }
+}
@@ -0,0 +1,129 @@
+//! Decoder abstraction over the in-process Symphonia backend and FFmpeg
+//!
+//! Symphonia handles the common formats without spawning anything. FFmpeg
+//! stays the fallback for everything it cannot open (e.g. Opus, which
+//! Symphonia 0.5 has no decoder for), for surround sources that have to be
+//! downmixed and for filter graphs such as the speed filters, which only
+//! FFmpeg can apply.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+
+use super::symphonia_decoder::SymphoniaDecoder;
+use crate::ffmpeg::{self, AudioMetadata, FFmpegProcess};
+
+/// Extensions picked for Symphonia in `Auto` mode
+const SYMPHONIA_EXTENSIONS: &[&str] = &["flac", "mp3", "ogg", "wav", "m4a", "aac"];
+
+/// Samples read per call by `decode_file`
+const DECODE_CHUNK: usize = 16384;
+
+/// Source of decoded PCM: interleaved f32 at the output rate and channel count
+pub trait Decoder: Send {
+    /// Fill `buffer` and return the number of samples written, 0 at EOF
+    fn read_samples(&mut self, buffer: &mut [f32]) -> io::Result<usize>;
+}
+
+impl Decoder for FFmpegProcess {
+    fn read_samples(&mut self, buffer: &mut [f32]) -> io::Result<usize> {
+        FFmpegProcess::read_samples(self, buffer)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DecoderBackend {
+    /// Symphonia for the formats it handles well, FFmpeg for the rest
+    #[default]
+    Auto,
+    /// Try Symphonia for every file, falling back to FFmpeg when it fails
+    Symphonia,
+    /// Always use FFmpeg
+    FFmpeg,
+}
+
+impl DecoderBackend {
+    fn prefers_symphonia(&self, path: &str) -> bool {
+        match self {
+            DecoderBackend::Auto => Path::new(path)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| SYMPHONIA_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or(false),
+            DecoderBackend::Symphonia => true,
+            DecoderBackend::FFmpeg => false,
+        }
+    }
+}
+
+/// Open a decoder for `path`, starting at `seek_ms`
+///
+/// A filter graph forces FFmpeg since Symphonia cannot apply it.
+pub fn open(
+    backend: DecoderBackend,
+    path: &str,
+    sample_rate: u32,
+    channels: u16,
+    seek_ms: Option<u64>,
+    filter: Option<&str>,
+) -> io::Result<Box<dyn Decoder>> {
+    if filter.is_none() && backend.prefers_symphonia(path) {
+        match SymphoniaDecoder::open(path, sample_rate, channels, seek_ms) {
+            Ok(decoder) => return Ok(Box::new(decoder)),
+            Err(e) => warn!("Symphonia could not open {}, using FFmpeg: {}", path, e),
+        }
+    }
+
+    let process = FFmpegProcess::spawn_at(path, sample_rate, channels, seek_ms, filter)?;
+    Ok(Box::new(process))
+}
+
+/// Decode all of `path` at the given rate and layout, handing each chunk of
+/// interleaved samples to `process`
+pub fn decode_file(
+    path: &str,
+    sample_rate: u32,
+    channels: u16,
+    mut process: impl FnMut(&[f32]),
+) -> io::Result<()> {
+    let mut decoder = open(
+        DecoderBackend::Auto,
+        path,
+        sample_rate,
+        channels,
+        None,
+        None,
+    )?;
+    let mut buffer = vec![0.0f32; DECODE_CHUNK];
+
+    loop {
+        let read = decoder.read_samples(&mut buffer)?;
+        if read == 0 {
+            return Ok(());
+        }
+        process(&buffer[..read]);
+    }
+}
+
+/// Read duration and source format, preferring Symphonia over `ffprobe`
+pub fn probe(backend: DecoderBackend, path: &str) -> io::Result<AudioMetadata> {
+    if backend.prefers_symphonia(path) {
+        match super::symphonia_decoder::probe(path) {
+            Ok(mut metadata) => {
+                // Streams without a frame count (e.g. MP3 without a Xing header)
+                // only get a duration from ffprobe, which scans the file
+                if metadata.duration_ms == 0 {
+                    match ffmpeg::probe_file(path) {
+                        Ok(probed) => metadata.duration_ms = probed.duration_ms,
+                        Err(e) => warn!("ffprobe could not read the duration of {}: {}", path, e),
+                    }
+                }
+                return Ok(metadata);
+            }
+            Err(e) => warn!("Symphonia could not probe {}, using ffprobe: {}", path, e),
+        }
+    }
+    ffmpeg::probe_file(path)
+}
@@ -0,0 +1,246 @@
+//! Parametric equalizer applied to decoded samples before they reach the ring buffer
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+/// Centre frequencies of the default 10-band layout
+const DEFAULT_FREQUENCIES: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+const DEFAULT_Q: f32 = 1.41;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EqBand {
+    pub frequency: f32,
+    pub gain_db: f32,
+    pub q: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EqSettings {
+    pub enabled: bool,
+    pub preamp_db: f32,
+    pub bands: Vec<EqBand>,
+}
+
+impl Default for EqSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            preamp_db: 0.0,
+            bands: DEFAULT_FREQUENCIES
+                .iter()
+                .map(|&frequency| EqBand {
+                    frequency,
+                    gain_db: 0.0,
+                    q: DEFAULT_Q,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A named set of EQ settings stored in the profile database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EqPreset {
+    pub name: String,
+    pub preamp_db: f32,
+    pub bands: Vec<EqBand>,
+}
+
+/// RBJ peaking filter with transposed direct form II state per channel
+struct PeakingFilter {
+    b0: f64,
+    b1: f64,
+    b2: f64,
+    a1: f64,
+    a2: f64,
+    flat: bool,
+    state: Vec<[f64; 2]>,
+}
+
+impl PeakingFilter {
+    fn new(channels: usize) -> Self {
+        Self {
+            b0: 1.0,
+            b1: 0.0,
+            b2: 0.0,
+            a1: 0.0,
+            a2: 0.0,
+            flat: true,
+            state: vec![[0.0; 2]; channels],
+        }
+    }
+
+    /// Recompute coefficients in place, keeping the delay line so live
+    /// changes don't click
+    fn set_band(&mut self, band: &EqBand, sample_rate: u32) {
+        // Keep the centre frequency below Nyquist so the filter stays stable
+        let nyquist = sample_rate as f64 / 2.0;
+        let frequency = (band.frequency as f64).clamp(10.0, nyquist * 0.95);
+        let q = (band.q as f64).max(0.05);
+
+        let a = 10f64.powf(band.gain_db as f64 / 40.0);
+        let w0 = 2.0 * PI * frequency / sample_rate as f64;
+        let alpha = w0.sin() / (2.0 * q);
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha / a;
+
+        self.b0 = (1.0 + alpha * a) / a0;
+        self.b1 = (-2.0 * cos_w0) / a0;
+        self.b2 = (1.0 - alpha * a) / a0;
+        self.a1 = (-2.0 * cos_w0) / a0;
+        self.a2 = (1.0 - alpha / a) / a0;
+        self.flat = band.gain_db == 0.0;
+    }
+
+    /// A flat band with a settled delay line passes samples through unchanged
+    fn is_identity(&self) -> bool {
+        self.flat
+            && self
+                .state
+                .iter()
+                .all(|s| s[0].abs() < 1e-12 && s[1].abs() < 1e-12)
+    }
+
+    fn process(&mut self, samples: &mut [f32]) {
+        if self.is_identity() {
+            return;
+        }
+        let channels = self.state.len();
+        for frame in samples.chunks_mut(channels) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let x = *sample as f64;
+                let y = self.b0 * x + state[0];
+                state[0] = self.b1 * x - self.a1 * y + state[1];
+                state[1] = self.b2 * x - self.a2 * y;
+                *sample = y as f32;
+            }
+        }
+    }
+}
+
+pub struct Equalizer {
+    settings: EqSettings,
+    sample_rate: u32,
+    channels: usize,
+    preamp: f32,
+    filters: Vec<PeakingFilter>,
+}
+
+impl Equalizer {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let mut eq = Self {
+            settings: EqSettings::default(),
+            sample_rate,
+            channels: channels.max(1) as usize,
+            preamp: 1.0,
+            filters: Vec::new(),
+        };
+        eq.update_coefficients();
+        eq
+    }
+
+    pub fn set_settings(&mut self, settings: EqSettings) {
+        self.settings = settings;
+        self.update_coefficients();
+    }
+
+    /// Recompute coefficients for a new output format
+    pub fn set_format(&mut self, sample_rate: u32, channels: u16) {
+        let channels = channels.max(1) as usize;
+        if sample_rate != self.sample_rate || channels != self.channels {
+            self.sample_rate = sample_rate;
+            self.channels = channels;
+            // The old delay lines belong to a different stream
+            self.filters.clear();
+            self.update_coefficients();
+        }
+    }
+
+    fn update_coefficients(&mut self) {
+        self.preamp = 10f32.powf(self.settings.preamp_db / 20.0);
+        let channels = self.channels;
+        self.filters
+            .resize_with(self.settings.bands.len(), || PeakingFilter::new(channels));
+        for (filter, band) in self.filters.iter_mut().zip(&self.settings.bands) {
+            filter.set_band(band, self.sample_rate);
+        }
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        if !self.settings.enabled {
+            return;
+        }
+
+        if self.preamp != 1.0 {
+            for sample in samples.iter_mut() {
+                *sample *= self.preamp;
+            }
+        }
+        for filter in &mut self.filters {
+            filter.process(samples);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peak_after_eq(eq: &mut Equalizer, freq: f64) -> f32 {
+        let samples: Vec<f32> = (0..48000)
+            .flat_map(|n| {
+                let v = (0.25 * (2.0 * PI * freq * n as f64 / 48000.0).sin()) as f32;
+                [v, v]
+            })
+            .collect();
+        let mut buffer = samples;
+        eq.process(&mut buffer);
+        // Skip the filter's settling time
+        buffer[48000..].iter().fold(0.0f32, |m, s| m.max(s.abs()))
+    }
+
+    #[test]
+    fn test_band_boosts_its_frequency_only() {
+        let mut settings = EqSettings {
+            enabled: true,
+            ..EqSettings::default()
+        };
+        settings.bands[5].gain_db = 6.0; // 1 kHz
+
+        let mut eq = Equalizer::new(48000, 2);
+        eq.set_settings(settings);
+
+        let boosted = peak_after_eq(&mut eq, 1000.0);
+        assert!((boosted / 0.25 - 2.0).abs() < 0.05, "gain {}", boosted / 0.25);
+
+        let mut eq_far = Equalizer::new(48000, 2);
+        eq_far.set_settings(eq.settings.clone());
+        let untouched = peak_after_eq(&mut eq_far, 60.0);
+        assert!((untouched / 0.25 - 1.0).abs() < 0.05, "gain {}", untouched / 0.25);
+    }
+
+    #[test]
+    fn test_settings_change_keeps_filter_state() {
+        let mut settings = EqSettings {
+            enabled: true,
+            ..EqSettings::default()
+        };
+        settings.bands[5].gain_db = 6.0;
+
+        let mut eq = Equalizer::new(48000, 2);
+        eq.set_settings(settings.clone());
+        peak_after_eq(&mut eq, 1000.0);
+        let state = eq.filters[5].state.clone();
+        assert!(state.iter().any(|s| s[0] != 0.0));
+
+        // Dragging a slider must not zero the delay line mid-stream
+        settings.bands[5].gain_db = 7.0;
+        eq.set_settings(settings);
+        assert_eq!(eq.filters[5].state, state);
+
+        eq.set_format(44100, 2);
+        assert!(eq.filters[5].state.iter().all(|s| *s == [0.0; 2]));
+    }
+}
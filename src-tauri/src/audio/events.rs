@@ -0,0 +1,24 @@
+//! Where the worker reports state changes to
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter};
+
+pub trait EventSink: Send + Sync {
+    fn emit(&self, event: &str, payload: Value);
+}
+
+/// Forward to the frontend as Tauri events
+impl EventSink for AppHandle {
+    fn emit(&self, event: &str, payload: Value) {
+        Emitter::emit(self, event, payload).ok();
+    }
+}
+
+/// Serialize `payload` and hand it to `sink`
+pub fn emit<S: Serialize>(sink: &dyn EventSink, event: &str, payload: S) {
+    match serde_json::to_value(payload) {
+        Ok(value) => sink.emit(event, value),
+        Err(e) => log::error!("Failed to serialize {} payload: {}", event, e),
+    }
+}
@@ -0,0 +1,176 @@
+//! Listening history: how long each track was actually heard
+//!
+//! The worker counts the samples it plays for the current track and hands a
+//! `PlayRecord` to its `PlayHistorySink` whenever that track ends, is
+//! skipped or is replaced. Listening time is measured in the track's own
+//! time, so a full listen at double speed still counts as the whole track.
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use tauri::AppHandle;
+
+use crate::database::DbHelper;
+use crate::profile::get_library_db_path;
+
+/// Plays shorter than this are not recorded, e.g. while skipping through a queue
+const MIN_RECORDED_MS: u64 = 1000;
+
+/// When a play counts as completed: after `percent` of the track or after
+/// `max_ms`, whichever comes first
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CompletionThreshold {
+    pub percent: f32,
+    pub max_ms: u64,
+}
+
+impl Default for CompletionThreshold {
+    fn default() -> Self {
+        Self {
+            percent: 50.0,
+            max_ms: 4 * 60 * 1000,
+        }
+    }
+}
+
+impl CompletionThreshold {
+    pub fn is_completed(&self, listened_ms: u64, duration_ms: u64) -> bool {
+        let of_track = duration_ms as f64 * self.percent.clamp(0.0, 100.0) as f64 / 100.0;
+        listened_ms >= self.max_ms || (duration_ms > 0 && listened_ms as f64 >= of_track)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayRecord {
+    pub track_id: i64,
+    pub listened_ms: u64,
+    pub completed: bool,
+}
+
+pub trait PlayHistorySink: Send + Sync {
+    fn record(&self, play: &PlayRecord);
+}
+
+/// Writes to the `play_history` table of the active library on a background
+/// thread, so the audio worker never waits for the database
+pub struct HistoryWriter {
+    sender: Sender<PlayRecord>,
+}
+
+impl HistoryWriter {
+    pub fn spawn(handle: AppHandle) -> Self {
+        let (sender, receiver) = mpsc::channel::<PlayRecord>();
+        thread::spawn(move || {
+            // Reopened only when the active profile points at another library
+            let mut connection: Option<(PathBuf, DbHelper)> = None;
+            for play in receiver {
+                let result = get_library_db_path(&handle).and_then(|db_path| {
+                    let db = match connection.take() {
+                        Some((path, db)) if path == db_path => db,
+                        _ => DbHelper::new(&db_path).map_err(|e| e.to_string())?,
+                    };
+                    let inserted = db.insert_play(&play).map_err(|e| e.to_string());
+                    connection = Some((db_path, db));
+                    inserted
+                });
+                if let Err(e) = result {
+                    warn!("Failed to record play of track {}: {}", play.track_id, e);
+                }
+            }
+        });
+        Self { sender }
+    }
+}
+
+impl PlayHistorySink for HistoryWriter {
+    fn record(&self, play: &PlayRecord) {
+        // Only fails once the writer thread is gone, i.e. during shutdown
+        self.sender.send(play.clone()).ok();
+    }
+}
+
+/// Listening time of the track that is playing now
+#[derive(Default)]
+pub struct PlayTracker {
+    track: Option<(i64, u64)>, // Library id and duration
+    listened_ms: f64,
+}
+
+impl PlayTracker {
+    /// Start counting for a new track; tracks outside the library are not counted
+    pub fn start(&mut self, track_id: Option<i64>, duration_ms: u64) {
+        self.track = track_id.map(|id| (id, duration_ms));
+        self.listened_ms = 0.0;
+    }
+
+    /// Count `samples` output samples, played at `speed`, as heard
+    pub fn add(&mut self, samples: usize, samples_per_sec: u64, speed: f32) {
+        self.listened_ms += source_ms(samples, samples_per_sec, speed);
+    }
+
+    /// Take back samples that were counted but dropped before they played
+    pub fn discard(&mut self, samples: usize, samples_per_sec: u64, speed: f32) {
+        self.listened_ms = (self.listened_ms - source_ms(samples, samples_per_sec, speed)).max(0.0);
+    }
+
+    /// End the current play, returning it if it is worth recording
+    pub fn finish(&mut self, threshold: &CompletionThreshold) -> Option<PlayRecord> {
+        let (track_id, duration_ms) = self.track.take()?;
+        let listened_ms = std::mem::take(&mut self.listened_ms).round() as u64;
+        (listened_ms >= MIN_RECORDED_MS).then(|| PlayRecord {
+            track_id,
+            listened_ms,
+            completed: threshold.is_completed(listened_ms, duration_ms),
+        })
+    }
+}
+
+/// Track time covered by `samples` output samples played at `speed`
+fn source_ms(samples: usize, samples_per_sec: u64, speed: f32) -> f64 {
+    if samples_per_sec == 0 {
+        return 0.0;
+    }
+    samples as f64 * 1000.0 / samples_per_sec as f64 * speed as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracker_records_listened_time() {
+        let threshold = CompletionThreshold::default();
+        let mut tracker = PlayTracker::default();
+
+        // 90 of 200 seconds, with one second buffered but dropped
+        tracker.start(Some(7), 200_000);
+        tracker.add(44100 * 2 * 91, 44100 * 2, 1.0);
+        tracker.discard(44100 * 2, 44100 * 2, 1.0);
+        let play = tracker.finish(&threshold).unwrap();
+        assert_eq!(play.track_id, 7);
+        assert_eq!(play.listened_ms, 90_000);
+        assert!(!play.completed);
+        assert!(tracker.finish(&threshold).is_none());
+
+        // Half of a short track, or four minutes of a long one, completes it
+        assert!(threshold.is_completed(100_000, 200_000));
+        assert!(threshold.is_completed(240_000, 3_600_000));
+
+        // Skipped right away, or not a library track
+        tracker.start(Some(7), 200_000);
+        tracker.add(100, 44100 * 2, 1.0);
+        assert!(tracker.finish(&threshold).is_none());
+        tracker.start(None, 200_000);
+        tracker.add(44100 * 2 * 60, 44100 * 2, 1.0);
+        assert!(tracker.finish(&threshold).is_none());
+
+        // Playing the whole track at double speed takes half the time
+        tracker.start(Some(7), 200_000);
+        tracker.add(44100 * 2 * 100, 44100 * 2, 2.0);
+        let play = tracker.finish(&threshold).unwrap();
+        assert_eq!(play.listened_ms, 200_000);
+        assert!(play.completed);
+    }
+}
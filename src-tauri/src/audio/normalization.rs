@@ -0,0 +1,91 @@
+//! Playback gain derived from ReplayGain / R128 loudness values
+
+use serde::{Deserialize, Serialize};
+
+use crate::loudness::TrackLoudness;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NormalizationMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Normalization {
+    pub mode: NormalizationMode,
+    pub preamp_db: f32,
+}
+
+impl Normalization {
+    /// Linear gain to apply to a track. Falls back to the other mode's values
+    /// when the preferred ones are missing, and leaves tracks without any
+    /// loudness information untouched.
+    pub fn gain_for(&self, loudness: &TrackLoudness) -> f32 {
+        let (gain_db, peak) = match self.mode {
+            NormalizationMode::Off => return 1.0,
+            NormalizationMode::Track => (
+                loudness.track_gain_db.or(loudness.album_gain_db),
+                loudness.track_peak.or(loudness.album_peak),
+            ),
+            NormalizationMode::Album => (
+                loudness.album_gain_db.or(loudness.track_gain_db),
+                loudness.album_peak.or(loudness.track_peak),
+            ),
+        };
+
+        let Some(gain_db) = gain_db else {
+            return 1.0;
+        };
+
+        let gain = 10f32.powf((gain_db + self.preamp_db) / 20.0);
+
+        // Clipping prevention: never push the peak above full scale
+        match peak {
+            Some(peak) if peak > 0.0 => gain.min(1.0 / peak),
+            _ => gain,
+        }
+    }
+}
+
+pub fn apply_gain(samples: &mut [f32], gain: f32) {
+    if gain == 1.0 {
+        return;
+    }
+    for sample in samples {
+        *sample *= gain;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gain_fallback_and_clipping() {
+        let loudness = TrackLoudness {
+            track_gain_db: Some(-6.0),
+            track_peak: Some(0.5),
+            album_gain_db: None,
+            album_peak: None,
+        };
+
+        let album = Normalization {
+            mode: NormalizationMode::Album,
+            preamp_db: 0.0,
+        };
+        assert!((album.gain_for(&loudness) - 0.501).abs() < 0.001);
+
+        // +12 dB would push the 0.5 peak well past full scale
+        let boosted = Normalization {
+            mode: NormalizationMode::Track,
+            preamp_db: 18.0,
+        };
+        assert_eq!(boosted.gain_for(&loudness), 2.0);
+
+        assert_eq!(Normalization::default().gain_for(&loudness), 1.0);
+        assert_eq!(album.gain_for(&TrackLoudness::default()), 1.0);
+    }
+}
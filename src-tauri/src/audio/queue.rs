@@ -0,0 +1,414 @@
+//! Play queue with shuffle and repeat handling
+//!
+//! The queue is kept in playback order: turning shuffle on reorders the
+//! upcoming entries in place and remembers the original order so it can be
+//! restored when shuffle is turned off again.
+
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::loudness::TrackLoudness;
+use crate::silence::TrackSilence;
+
+/// A single queue entry, shaped like `LibraryTrack` so the frontend can pass
+/// its track objects straight through
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueueItem {
+    pub id: Option<i64>,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    #[serde(default)]
+    pub duration_ms: u64,
+    pub file_path: String,
+    pub artwork_path: Option<String>,
+    #[serde(flatten)]
+    pub loudness: TrackLoudness,
+    #[serde(flatten)]
+    pub silence: TrackSilence,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatMode {
+    #[default]
+    Off,
+    All,
+    One,
+}
+
+/// Serializable view of the queue sent to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueSnapshot {
+    pub items: Vec<QueueItem>,
+    pub current_index: Option<usize>,
+    pub shuffle: bool,
+    pub repeat: RepeatMode,
+}
+
+struct Entry {
+    uid: u64,
+    item: QueueItem,
+}
+
+#[derive(Default)]
+pub struct PlayQueue {
+    entries: Vec<Entry>,
+    current: Option<usize>,
+    // Set when the playing entry was removed: `current` then points at the
+    // entry that took its slot, which has not been played yet
+    detached: bool,
+    shuffle: bool,
+    repeat: RepeatMode,
+    unshuffled: Vec<u64>, // Original uid order, only maintained while shuffled
+    next_uid: u64,
+}
+
+impl PlayQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn make_entries(&mut self, items: Vec<QueueItem>) -> Vec<Entry> {
+        items
+            .into_iter()
+            .map(|item| {
+                self.next_uid += 1;
+                Entry {
+                    uid: self.next_uid,
+                    item,
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the queue and make `start_index` the current entry
+    pub fn set(&mut self, items: Vec<QueueItem>, start_index: usize) {
+        self.entries = self.make_entries(items);
+        self.detached = false;
+        self.current = if self.entries.is_empty() {
+            None
+        } else {
+            Some(start_index.min(self.entries.len() - 1))
+        };
+
+        if self.shuffle {
+            self.unshuffled = self.entries.iter().map(|e| e.uid).collect();
+            self.shuffle_upcoming();
+        }
+    }
+
+    /// Insert items before `index`, or append them when `index` is `None`
+    pub fn insert(&mut self, items: Vec<QueueItem>, index: Option<usize>) {
+        let index = index.unwrap_or(self.entries.len()).min(self.entries.len());
+        let new_entries = self.make_entries(items);
+        let count = new_entries.len();
+        if count == 0 {
+            return;
+        }
+
+        if self.shuffle {
+            // Keep the original order close to where the user put the items
+            let anchor = index
+                .checked_sub(1)
+                .and_then(|i| self.unshuffled.iter().position(|&u| u == self.entries[i].uid))
+                .map(|pos| pos + 1)
+                .unwrap_or(0);
+            for (offset, entry) in new_entries.iter().enumerate() {
+                self.unshuffled.insert(anchor + offset, entry.uid);
+            }
+        }
+
+        self.entries.splice(index..index, new_entries);
+
+        match self.current {
+            Some(cur) if cur >= index && !(self.detached && cur == index) => {
+                self.current = Some(cur + count)
+            }
+            None => {
+                // Queue was empty: the first inserted entry is up next
+                self.current = Some(0);
+                self.detached = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Move the entry at `from` so it ends up at `to`
+    pub fn move_item(&mut self, from: usize, to: usize) -> bool {
+        let len = self.entries.len();
+        if from >= len || to >= len {
+            return false;
+        }
+        if from == to {
+            return true;
+        }
+
+        let entry = self.entries.remove(from);
+        self.entries.insert(to, entry);
+
+        if let Some(cur) = self.current {
+            self.current = Some(if cur == from {
+                to
+            } else if from < cur && cur <= to {
+                cur - 1
+            } else if to <= cur && cur < from {
+                cur + 1
+            } else {
+                cur
+            });
+        }
+        true
+    }
+
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+
+        let entry = self.entries.remove(index);
+        if self.shuffle {
+            self.unshuffled.retain(|&u| u != entry.uid);
+        }
+
+        if self.entries.is_empty() {
+            self.current = None;
+            self.detached = false;
+            return true;
+        }
+
+        if let Some(cur) = self.current {
+            if index < cur {
+                self.current = Some(cur - 1);
+            } else if index == cur {
+                self.detached = true;
+            }
+        }
+        true
+    }
+
+    /// Drop everything except the playing entry
+    pub fn clear_upcoming(&mut self) {
+        match self.current_entry_index() {
+            Some(cur) => {
+                let entry = self.entries.swap_remove(cur);
+                if self.shuffle {
+                    self.unshuffled = vec![entry.uid];
+                }
+                self.entries = vec![entry];
+                self.current = Some(0);
+            }
+            None => {
+                self.entries.clear();
+                self.unshuffled.clear();
+                self.current = None;
+                self.detached = false;
+            }
+        }
+    }
+
+    pub fn set_shuffle(&mut self, enabled: bool) {
+        if enabled == self.shuffle {
+            return;
+        }
+        self.shuffle = enabled;
+
+        if enabled {
+            self.unshuffled = self.entries.iter().map(|e| e.uid).collect();
+            self.shuffle_upcoming();
+        } else {
+            let current_uid = self.current.and_then(|i| self.entries.get(i)).map(|e| e.uid);
+            let order: HashMap<u64, usize> = std::mem::take(&mut self.unshuffled)
+                .into_iter()
+                .enumerate()
+                .map(|(pos, uid)| (uid, pos))
+                .collect();
+            self.entries.sort_by_key(|e| order.get(&e.uid).copied());
+            if let Some(uid) = current_uid {
+                self.current = self.entries.iter().position(|e| e.uid == uid);
+            }
+        }
+    }
+
+    pub fn set_repeat(&mut self, mode: RepeatMode) {
+        self.repeat = mode;
+    }
+
+    fn shuffle_upcoming(&mut self) {
+        let start = match self.current {
+            Some(cur) if self.detached => cur,
+            Some(cur) => cur + 1,
+            None => 0,
+        };
+        if start < self.entries.len() {
+            self.entries[start..].shuffle(&mut rand::thread_rng());
+        }
+    }
+
+    /// Index of the entry that is playing, if it is still in the queue
+    fn current_entry_index(&self) -> Option<usize> {
+        if self.detached {
+            None
+        } else {
+            self.current
+        }
+    }
+
+    pub fn current(&self) -> Option<(usize, &QueueItem)> {
+        self.current_entry_index()
+            .and_then(|i| self.entries.get(i).map(|e| (i, &e.item)))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&QueueItem> {
+        self.entries.get(index).map(|e| &e.item)
+    }
+
+    /// The entry that would play after the current one. `auto` is true when
+    /// the current track ended on its own, which is when repeat-one applies.
+    pub fn peek_next(&self, auto: bool) -> Option<usize> {
+        let len = self.entries.len();
+        let cur = self.current?;
+
+        if self.detached {
+            return if cur < len {
+                Some(cur)
+            } else if self.repeat == RepeatMode::All {
+                Some(0)
+            } else {
+                None
+            };
+        }
+
+        if auto && self.repeat == RepeatMode::One {
+            Some(cur)
+        } else if cur + 1 < len {
+            Some(cur + 1)
+        } else if self.repeat == RepeatMode::All {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    /// Advance to the next entry and return its index
+    pub fn advance(&mut self, auto: bool) -> Option<usize> {
+        let next = self.peek_next(auto)?;
+        self.jump_to(next);
+        Some(next)
+    }
+
+    /// Step back to the previous entry and return its index
+    pub fn go_previous(&mut self) -> Option<usize> {
+        let len = self.entries.len();
+        let cur = self.current?;
+
+        let target = match cur.checked_sub(1) {
+            Some(i) => i,
+            None if self.repeat == RepeatMode::All => len - 1,
+            None => 0,
+        };
+        self.jump_to(target);
+        Some(target)
+    }
+
+    pub fn jump_to(&mut self, index: usize) -> bool {
+        if index >= self.entries.len() {
+            return false;
+        }
+        self.current = Some(index);
+        self.detached = false;
+        true
+    }
+
+    pub fn snapshot(&self) -> QueueSnapshot {
+        QueueSnapshot {
+            items: self.entries.iter().map(|e| e.item.clone()).collect(),
+            current_index: self.current_entry_index(),
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(n: i64) -> QueueItem {
+        QueueItem {
+            id: Some(n),
+            title: format!("Track {}", n),
+            artist: None,
+            album: None,
+            duration_ms: 0,
+            file_path: format!("/music/{}.flac", n),
+            artwork_path: None,
+            loudness: TrackLoudness::default(),
+            silence: TrackSilence::default(),
+        }
+    }
+
+    fn ids(queue: &PlayQueue) -> Vec<i64> {
+        queue.snapshot().items.iter().filter_map(|i| i.id).collect()
+    }
+
+    #[test]
+    fn test_advance_and_repeat() {
+        let mut queue = PlayQueue::new();
+        queue.set(vec![item(1), item(2), item(3)], 1);
+
+        assert_eq!(queue.advance(true), Some(2));
+        assert_eq!(queue.advance(true), None);
+
+        queue.set_repeat(RepeatMode::All);
+        assert_eq!(queue.advance(true), Some(0));
+
+        queue.set_repeat(RepeatMode::One);
+        assert_eq!(queue.peek_next(true), Some(0));
+        assert_eq!(queue.peek_next(false), Some(1));
+    }
+
+    #[test]
+    fn test_remove_current_keeps_following_entry() {
+        let mut queue = PlayQueue::new();
+        queue.set(vec![item(1), item(2), item(3)], 1);
+
+        assert!(queue.remove(1));
+        assert!(queue.current().is_none());
+        assert_eq!(queue.advance(true), Some(1));
+        assert_eq!(queue.current().and_then(|(_, i)| i.id), Some(3));
+    }
+
+    #[test]
+    fn test_move_tracks_current() {
+        let mut queue = PlayQueue::new();
+        queue.set(vec![item(1), item(2), item(3), item(4)], 1);
+
+        assert!(queue.move_item(3, 0));
+        assert_eq!(ids(&queue), vec![4, 1, 2, 3]);
+        assert_eq!(queue.current().map(|(i, _)| i), Some(2));
+
+        assert!(queue.move_item(2, 3));
+        assert_eq!(ids(&queue), vec![4, 1, 3, 2]);
+        assert_eq!(queue.current().map(|(i, _)| i), Some(3));
+    }
+
+    #[test]
+    fn test_shuffle_restores_original_order() {
+        let mut queue = PlayQueue::new();
+        queue.set((1..=20).map(item).collect(), 4);
+
+        queue.set_shuffle(true);
+        assert_eq!(queue.current().and_then(|(_, i)| i.id), Some(5));
+        assert_eq!(&ids(&queue)[..5], &[1, 2, 3, 4, 5]);
+
+        queue.insert(vec![item(99)], Some(5));
+        queue.set_shuffle(false);
+
+        let mut expected: Vec<i64> = (1..=20).collect();
+        expected.insert(5, 99);
+        assert_eq!(ids(&queue), expected);
+        assert_eq!(queue.current().and_then(|(_, i)| i.id), Some(5));
+    }
+}
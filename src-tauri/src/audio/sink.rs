@@ -0,0 +1,501 @@
+//! Output sinks: where audio goes once it leaves the ring buffer
+//!
+//! The worker only ever talks to an `OutputSink`. `CpalSink` plays through
+//! the sound card; `NullSink` and `WavSink` pull from the ring buffer on their
+//! own clock so the engine can run (and be tested) without any audio device.
+
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{Stream, StreamConfig};
+use log::error;
+use ringbuf::{
+    traits::{Consumer, Split},
+    HeapCons, HeapProd, HeapRb,
+};
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::channel_mix::{self, ChannelMix};
+
+/// How much audio the offline sinks pull per tick
+const OFFLINE_PERIOD_MS: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+/// Flags shared between the worker and whatever renders its ring buffer
+#[derive(Clone)]
+pub struct OutputControls {
+    pub volume: Arc<AtomicU64>,
+    pub channel_mix: Arc<AtomicU64>, // A packed `ChannelMix`
+    pub is_playing: Arc<AtomicBool>,
+    pub flush_pending: Arc<AtomicBool>, // Set by a seek, cleared once the renderer drained the buffer
+    pub device_error: Arc<AtomicBool>,
+    pub samples_rendered: Arc<AtomicU64>, // Samples taken out of the current ring buffer
+}
+
+impl Default for OutputControls {
+    fn default() -> Self {
+        Self {
+            volume: Arc::new(AtomicU64::new(f32::to_bits(1.0) as u64)),
+            channel_mix: Arc::new(AtomicU64::new(ChannelMix::default().to_bits())),
+            is_playing: Arc::new(AtomicBool::new(false)),
+            flush_pending: Arc::new(AtomicBool::new(false)),
+            device_error: Arc::new(AtomicBool::new(false)),
+            samples_rendered: Arc::new(AtomicU64::new(0)),
+        }
+    }
+}
+
+/// Consumer side of the ring buffer, run from the sink's own thread
+pub struct Renderer {
+    consumer: HeapCons<f32>,
+    controls: OutputControls,
+    channels: usize,
+}
+
+impl Renderer {
+    /// Create a ring buffer holding one second of `format` and its renderer
+    pub fn new(format: StreamFormat, controls: OutputControls) -> (HeapProd<f32>, Self) {
+        let buffer_size = format.sample_rate as usize * format.channels as usize; // 1 sec
+        let (producer, consumer) = HeapRb::<f32>::new(buffer_size).split();
+
+        // A fresh buffer has nothing stale to flush
+        controls.flush_pending.store(false, Ordering::Relaxed);
+        controls.samples_rendered.store(0, Ordering::Relaxed);
+
+        let channels = format.channels as usize;
+        (
+            producer,
+            Self {
+                consumer,
+                controls,
+                channels,
+            },
+        )
+    }
+
+    /// Fill `data` with the next samples, or silence while paused or starved
+    pub fn render(&mut self, data: &mut [f32]) {
+        if self.controls.flush_pending.load(Ordering::Acquire) {
+            // Dropped samples count as taken so the counter stays in step with the worker
+            let dropped = self.consumer.clear();
+            self.controls
+                .samples_rendered
+                .fetch_add(dropped as u64, Ordering::Release);
+            self.controls.flush_pending.store(false, Ordering::Release);
+        }
+
+        if !self.controls.is_playing.load(Ordering::Relaxed) {
+            data.fill(0.0);
+            return;
+        }
+
+        let filled = self.consumer.pop_slice(data);
+        data[filled..].fill(0.0);
+        self.controls
+            .samples_rendered
+            .fetch_add(filled as u64, Ordering::Release);
+
+        let mix = ChannelMix::from_bits(self.controls.channel_mix.load(Ordering::Relaxed));
+        if !mix.is_identity() {
+            channel_mix::apply(&mix.matrix(), data, self.channels);
+        }
+
+        let vol = f32::from_bits(self.controls.volume.load(Ordering::Relaxed) as u32);
+        for sample in data.iter_mut() {
+            *sample *= vol;
+        }
+    }
+}
+
+/// The format a sink ended up opening
+pub struct OpenedOutput {
+    pub format: StreamFormat,
+    pub fallback_reason: Option<String>, // Why the source format could not be used
+}
+
+pub trait OutputSink {
+    /// Format to decode to before any track has opened the output
+    fn default_format(&self) -> Option<StreamFormat>;
+
+    /// Start rendering for a track in `source` format. With `match_source`
+    /// the sink should open at that format when it can. `renderer` builds the
+    /// ring buffer for the format that was actually chosen.
+    fn open(
+        &mut self,
+        source: StreamFormat,
+        match_source: bool,
+        device_name: Option<&str>,
+        renderer: &mut dyn FnMut(StreamFormat) -> Renderer,
+    ) -> Result<OpenedOutput, String>;
+
+    /// Stop rendering and release the output
+    fn close(&mut self);
+}
+
+/// Plays through a CPAL output device
+#[derive(Default)]
+pub struct CpalSink {
+    stream: Option<Stream>,
+}
+
+impl CpalSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn build_stream(
+        device: &cpal::Device,
+        config: &StreamConfig,
+        renderer: &mut dyn FnMut(StreamFormat) -> Renderer,
+    ) -> Result<Stream, cpal::BuildStreamError> {
+        let mut renderer = renderer(StreamFormat {
+            sample_rate: config.sample_rate.0,
+            channels: config.channels,
+        });
+        let device_error = renderer.controls.device_error.clone();
+
+        device.build_output_stream(
+            config,
+            move |data: &mut [f32], _: &cpal::OutputCallbackInfo| renderer.render(data),
+            move |err| {
+                error!("CPAL Error: {}", err);
+                device_error.store(true, Ordering::Relaxed);
+            },
+            None,
+        )
+    }
+}
+
+impl OutputSink for CpalSink {
+    fn default_format(&self) -> Option<StreamFormat> {
+        cpal::default_host()
+            .default_output_device()
+            .and_then(|device| device.default_output_config().ok())
+            .map(|config| StreamFormat {
+                sample_rate: config.sample_rate().0,
+                channels: config.channels(),
+            })
+    }
+
+    fn open(
+        &mut self,
+        source: StreamFormat,
+        match_source: bool,
+        device_name: Option<&str>,
+        renderer: &mut dyn FnMut(StreamFormat) -> Renderer,
+    ) -> Result<OpenedOutput, String> {
+        self.close();
+        let host = cpal::default_host();
+
+        let device = if let Some(name) = device_name {
+            host.output_devices()
+                .ok()
+                .and_then(|mut devices| {
+                    devices.find(|d| d.name().map(|n| n == name).unwrap_or(false))
+                })
+                .or_else(|| host.default_output_device())
+        } else {
+            host.default_output_device()
+        };
+
+        let Some(device) = device else {
+            return Err("No audio output device available".to_string());
+        };
+
+        let default_config: StreamConfig = device
+            .default_output_config()
+            .map_err(|e| format!("Audio device error: {}", e))?
+            .into();
+
+        let mut fallback_reason = None;
+        let mut opened = None;
+
+        if match_source {
+            match find_source_config(&device, source) {
+                Ok(config) => match Self::build_stream(&device, &config, renderer) {
+                    Ok(s) => opened = Some((s, config)),
+                    Err(e) => {
+                        fallback_reason = Some(format!("Device rejected the source format: {}", e))
+                    }
+                },
+                Err(reason) => fallback_reason = Some(reason),
+            }
+        }
+
+        let (stream, config) = match opened {
+            Some(opened) => opened,
+            None => match Self::build_stream(&device, &default_config, renderer) {
+                Ok(s) => (s, default_config),
+                Err(e) => return Err(format!("Failed to initialize audio: {}", e)),
+            },
+        };
+
+        stream
+            .play()
+            .map_err(|e| format!("Failed to start playback: {}", e))?;
+        self.stream = Some(stream);
+
+        Ok(OpenedOutput {
+            format: StreamFormat {
+                sample_rate: config.sample_rate.0,
+                channels: config.channels,
+            },
+            fallback_reason,
+        })
+    }
+
+    fn close(&mut self) {
+        self.stream = None;
+    }
+}
+
+/// Find a float output config for the source format, or say why there is none
+fn find_source_config(device: &cpal::Device, source: StreamFormat) -> Result<StreamConfig, String> {
+    let configs = device
+        .supported_output_configs()
+        .map_err(|e| format!("Could not query supported formats: {}", e))?;
+
+    let mut rate_supported = false;
+    for range in configs {
+        if range.min_sample_rate().0 > source.sample_rate
+            || range.max_sample_rate().0 < source.sample_rate
+        {
+            continue;
+        }
+        rate_supported = true;
+        if range.channels() == source.channels && range.sample_format() == cpal::SampleFormat::F32 {
+            return Ok(range
+                .with_sample_rate(cpal::SampleRate(source.sample_rate))
+                .into());
+        }
+    }
+
+    Err(if rate_supported {
+        format!(
+            "Device has no {}-channel float output at {} Hz",
+            source.channels, source.sample_rate
+        )
+    } else {
+        format!("Device does not support {} Hz", source.sample_rate)
+    })
+}
+
+/// Renders on a background thread in real time, like a sound card would,
+/// handing every period to `write`
+struct OfflineClock {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl OfflineClock {
+    fn start<W>(format: StreamFormat, mut renderer: Renderer, mut write: W) -> Self
+    where
+        W: FnMut(&[f32]) + Send + 'static,
+    {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_flag = stop.clone();
+        let period = Duration::from_millis(OFFLINE_PERIOD_MS);
+        let samples = (format.sample_rate as u64 * OFFLINE_PERIOD_MS / 1000) as usize
+            * format.channels as usize;
+
+        let thread = thread::spawn(move || {
+            let mut buffer = vec![0.0f32; samples];
+            let mut next_tick = Instant::now() + period;
+            while !stop_flag.load(Ordering::Relaxed) {
+                if let Some(wait) = next_tick.checked_duration_since(Instant::now()) {
+                    thread::sleep(wait);
+                }
+                next_tick += period;
+                renderer.render(&mut buffer);
+                write(&buffer);
+            }
+        });
+
+        Self {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for OfflineClock {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Offline sinks render at one fixed format
+fn fixed_format_output(format: StreamFormat, source: StreamFormat, match_source: bool) -> OpenedOutput {
+    let fallback_reason = (match_source && source != format).then(|| {
+        format!(
+            "Output is fixed at {} Hz, {} channels",
+            format.sample_rate, format.channels
+        )
+    });
+    OpenedOutput {
+        format,
+        fallback_reason,
+    }
+}
+
+/// Discards everything it renders
+pub struct NullSink {
+    format: StreamFormat,
+    clock: Option<OfflineClock>,
+}
+
+impl NullSink {
+    pub fn new(format: StreamFormat) -> Self {
+        Self {
+            format,
+            clock: None,
+        }
+    }
+}
+
+impl OutputSink for NullSink {
+    fn default_format(&self) -> Option<StreamFormat> {
+        Some(self.format)
+    }
+
+    fn open(
+        &mut self,
+        source: StreamFormat,
+        match_source: bool,
+        _device_name: Option<&str>,
+        renderer: &mut dyn FnMut(StreamFormat) -> Renderer,
+    ) -> Result<OpenedOutput, String> {
+        self.close();
+        self.clock = Some(OfflineClock::start(self.format, renderer(self.format), |_| {}));
+        Ok(fixed_format_output(self.format, source, match_source))
+    }
+
+    fn close(&mut self) {
+        self.clock = None;
+    }
+}
+
+/// Records everything it renders, silence included, to a 32-bit float WAV file
+pub struct WavSink {
+    format: StreamFormat,
+    writer: Arc<Mutex<WavWriter>>,
+    clock: Option<OfflineClock>,
+}
+
+impl WavSink {
+    pub fn create(path: impl AsRef<Path>, format: StreamFormat) -> io::Result<Self> {
+        Ok(Self {
+            format,
+            writer: Arc::new(Mutex::new(WavWriter::create(path.as_ref(), format)?)),
+            clock: None,
+        })
+    }
+}
+
+impl OutputSink for WavSink {
+    fn default_format(&self) -> Option<StreamFormat> {
+        Some(self.format)
+    }
+
+    fn open(
+        &mut self,
+        source: StreamFormat,
+        match_source: bool,
+        _device_name: Option<&str>,
+        renderer: &mut dyn FnMut(StreamFormat) -> Renderer,
+    ) -> Result<OpenedOutput, String> {
+        self.close();
+        let writer = self.writer.clone();
+        self.clock = Some(OfflineClock::start(
+            self.format,
+            renderer(self.format),
+            move |samples| {
+                if let Err(e) = writer.lock().unwrap().write(samples) {
+                    error!("Failed to write WAV output: {}", e);
+                }
+            },
+        ));
+        Ok(fixed_format_output(self.format, source, match_source))
+    }
+
+    fn close(&mut self) {
+        self.clock = None;
+        // Keep the file playable after every stop, not just at the end
+        if let Err(e) = self.writer.lock().unwrap().finalize() {
+            error!("Failed to finalize WAV output: {}", e);
+        }
+    }
+}
+
+impl Drop for WavSink {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+struct WavWriter {
+    file: BufWriter<File>,
+    format: StreamFormat,
+    data_bytes: u64,
+}
+
+impl WavWriter {
+    fn create(path: &Path, format: StreamFormat) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            format,
+            data_bytes: 0,
+        };
+        writer.write_header()?;
+        Ok(writer)
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        let channels = self.format.channels as u32;
+        let block_align = channels * 4;
+        // Recordings past the RIFF limit keep their audio but cap the sizes
+        let riff_bytes = u32::try_from(36 + self.data_bytes).unwrap_or(u32::MAX);
+        let data_bytes = u32::try_from(self.data_bytes).unwrap_or(u32::MAX);
+        let f = &mut self.file;
+        f.write_all(b"RIFF")?;
+        f.write_all(&riff_bytes.to_le_bytes())?;
+        f.write_all(b"WAVEfmt ")?;
+        f.write_all(&16u32.to_le_bytes())?;
+        f.write_all(&3u16.to_le_bytes())?; // IEEE float
+        f.write_all(&self.format.channels.to_le_bytes())?;
+        f.write_all(&self.format.sample_rate.to_le_bytes())?;
+        f.write_all(&(self.format.sample_rate * block_align).to_le_bytes())?;
+        f.write_all(&(block_align as u16).to_le_bytes())?;
+        f.write_all(&32u16.to_le_bytes())?;
+        f.write_all(b"data")?;
+        f.write_all(&data_bytes.to_le_bytes())
+    }
+
+    fn write(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            self.file.write_all(&sample.to_le_bytes())?;
+        }
+        self.data_bytes += samples.len() as u64 * 4;
+        Ok(())
+    }
+
+    /// Patch the chunk sizes for what has been written so far
+    fn finalize(&mut self) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.write_header()?;
+        self.file.seek(SeekFrom::End(0))?;
+        self.file.flush()
+    }
+}
@@ -0,0 +1,163 @@
+//! Sleep timer that fades playback out and stops it
+//!
+//! The timer only keeps count; the worker asks it for a gain on every pass
+//! and stops playback once it expires.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub const DEFAULT_FADE_MS: u64 = 10000;
+
+/// How often the countdown is reported
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepTimerMode {
+    /// Stop after this many minutes
+    Minutes(u32),
+    /// Stop when the current track ends
+    EndOfTrack,
+    /// Stop when this many tracks, counting the current one, have ended
+    Tracks(u32),
+}
+
+/// Payload of the sleep timer event
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepTimerStatus {
+    pub active: bool,
+    pub mode: Option<SleepTimerMode>,
+    /// Time until playback stops, when known
+    pub remaining_ms: Option<u64>,
+    /// Tracks left to end, counting the current one
+    pub tracks_remaining: Option<u32>,
+    pub fading: bool,
+}
+
+impl SleepTimerStatus {
+    pub fn inactive() -> Self {
+        Self {
+            active: false,
+            mode: None,
+            remaining_ms: None,
+            tracks_remaining: None,
+            fading: false,
+        }
+    }
+}
+
+pub struct SleepTimer {
+    mode: SleepTimerMode,
+    fade_ms: u64,
+    deadline: Option<Instant>,
+    tracks_left: u32,
+    last_report: Option<Instant>,
+}
+
+impl SleepTimer {
+    pub fn new(mode: SleepTimerMode, fade_ms: u64, now: Instant) -> Self {
+        let (deadline, tracks_left) = match mode {
+            SleepTimerMode::Minutes(minutes) => {
+                (Some(now + Duration::from_secs(minutes as u64 * 60)), 0)
+            }
+            SleepTimerMode::EndOfTrack => (None, 1),
+            SleepTimerMode::Tracks(count) => (None, count.max(1)),
+        };
+        Self {
+            mode,
+            fade_ms,
+            deadline,
+            tracks_left,
+            last_report: None,
+        }
+    }
+
+    /// Whether playback should stop when the current track ends
+    pub fn ends_with_current_track(&self) -> bool {
+        self.deadline.is_none() && self.tracks_left <= 1
+    }
+
+    /// Count a track that played to its end
+    pub fn track_finished(&mut self) {
+        if self.deadline.is_none() {
+            self.tracks_left = self.tracks_left.saturating_sub(1);
+        }
+    }
+
+    /// Time until the timer expires. `track_remaining_ms` is what is left of
+    /// the current track, which decides it in the track modes.
+    pub fn remaining_ms(&self, now: Instant, track_remaining_ms: Option<u64>) -> Option<u64> {
+        match self.deadline {
+            Some(deadline) => Some(deadline.saturating_duration_since(now).as_millis() as u64),
+            None if self.ends_with_current_track() => track_remaining_ms,
+            None => None,
+        }
+    }
+
+    pub fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| now >= deadline)
+    }
+
+    pub fn is_fading(&self, remaining_ms: Option<u64>) -> bool {
+        remaining_ms.is_some_and(|ms| ms < self.fade_ms)
+    }
+
+    /// Volume factor for the fade-out, 1.0 until it starts
+    pub fn gain(&self, remaining_ms: Option<u64>) -> f32 {
+        match remaining_ms {
+            Some(ms) if ms < self.fade_ms => {
+                // Squared so the fade sounds even instead of lingering near full level
+                let x = ms as f32 / self.fade_ms as f32;
+                x * x
+            }
+            _ => 1.0,
+        }
+    }
+
+    /// Whether a countdown event is due, marking it as sent
+    pub fn report_due(&mut self, now: Instant) -> bool {
+        if self
+            .last_report
+            .is_some_and(|last| now.duration_since(last) < REPORT_INTERVAL)
+        {
+            return false;
+        }
+        self.last_report = Some(now);
+        true
+    }
+
+    pub fn status(&self, remaining_ms: Option<u64>) -> SleepTimerStatus {
+        SleepTimerStatus {
+            active: true,
+            mode: Some(self.mode),
+            remaining_ms,
+            tracks_remaining: self.deadline.is_none().then_some(self.tracks_left),
+            fading: self.is_fading(remaining_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_counts_down_and_fades() {
+        let now = Instant::now();
+        let timer = SleepTimer::new(SleepTimerMode::Minutes(1), 10000, now);
+        let later = now + Duration::from_secs(55);
+        assert_eq!(timer.remaining_ms(later, None), Some(5000));
+        assert_eq!(timer.gain(Some(5000)), 0.25);
+        assert_eq!(timer.gain(Some(20000)), 1.0);
+        assert!(timer.is_expired(now + Duration::from_secs(60)));
+        assert!(!timer.ends_with_current_track());
+
+        let mut tracks = SleepTimer::new(SleepTimerMode::Tracks(2), 10000, now);
+        assert!(!tracks.ends_with_current_track());
+        assert_eq!(tracks.remaining_ms(now, Some(3000)), None);
+        tracks.track_finished();
+        assert!(tracks.ends_with_current_track());
+        assert_eq!(tracks.remaining_ms(now, Some(3000)), Some(3000));
+        assert!(!tracks.is_expired(now + Duration::from_secs(3600)));
+    }
+}
@@ -0,0 +1,90 @@
+//! Playback speed, applied through FFmpeg's filter graph
+
+use serde::{Deserialize, Serialize};
+
+pub const MIN_RATE: f32 = 0.5;
+pub const MAX_RATE: f32 = 3.0;
+
+/// Largest factor a single `atempo` instance accepts on older FFmpeg builds
+const ATEMPO_MAX: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpeedMode {
+    /// Time-stretch so the pitch stays the same
+    #[default]
+    Preserve,
+    /// Resample so the pitch follows the speed, like a turntable
+    Vinyl,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlaybackSpeed {
+    pub rate: f32,
+    pub mode: SpeedMode,
+}
+
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        Self {
+            rate: 1.0,
+            mode: SpeedMode::Preserve,
+        }
+    }
+}
+
+impl PlaybackSpeed {
+    pub fn new(rate: f32, mode: SpeedMode) -> Self {
+        Self {
+            rate: rate.clamp(MIN_RATE, MAX_RATE),
+            mode,
+        }
+    }
+
+    pub fn is_normal(&self) -> bool {
+        (self.rate - 1.0).abs() < 0.001
+    }
+
+    /// FFmpeg `-af` graph for this speed when decoding to `sample_rate`
+    pub fn filter(&self, sample_rate: u32) -> Option<String> {
+        if self.is_normal() {
+            return None;
+        }
+
+        match self.mode {
+            SpeedMode::Preserve => {
+                let mut stages = Vec::new();
+                let mut remaining = self.rate;
+                while remaining > ATEMPO_MAX {
+                    stages.push(format!("atempo={}", ATEMPO_MAX));
+                    remaining /= ATEMPO_MAX;
+                }
+                stages.push(format!("atempo={:.4}", remaining));
+                Some(stages.join(","))
+            }
+            SpeedMode::Vinyl => {
+                // Relabel the rate so playback runs faster, then -ar resamples it back
+                let stretched = (sample_rate as f64 * self.rate as f64).round() as u64;
+                Some(format!("aresample={},asetrate={}", sample_rate, stretched))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_speed_filters() {
+        assert_eq!(PlaybackSpeed::default().filter(48000), None);
+
+        let fast = PlaybackSpeed::new(3.0, SpeedMode::Preserve);
+        assert_eq!(fast.filter(48000).unwrap(), "atempo=2,atempo=1.5000");
+
+        let vinyl = PlaybackSpeed::new(0.5, SpeedMode::Vinyl);
+        assert_eq!(vinyl.filter(44100).unwrap(), "aresample=44100,asetrate=22050");
+
+        assert_eq!(PlaybackSpeed::new(10.0, SpeedMode::Vinyl).rate, MAX_RATE);
+    }
+}
@@ -0,0 +1,307 @@
+//! In-process decoding with Symphonia, resampled with rubato when needed
+
+use rubato::{FftFixedIn, Resampler};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{Decoder as CodecDecoder, DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader, SeekMode, SeekTo};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+
+use super::decoder::Decoder;
+use crate::ffmpeg::AudioMetadata;
+
+/// Input frames handed to the resampler per call
+const RESAMPLER_CHUNK: usize = 1024;
+
+fn to_io_error(e: SymphoniaError) -> io::Error {
+    match e {
+        SymphoniaError::IoError(e) => e,
+        other => io::Error::new(io::ErrorKind::InvalidData, other.to_string()),
+    }
+}
+
+fn open_format(path: &str) -> io::Result<Box<dyn FormatReader>> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let format_opts = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+
+    symphonia::default::get_probe()
+        .format(&hint, mss, &format_opts, &MetadataOptions::default())
+        .map(|probed| probed.format)
+        .map_err(to_io_error)
+}
+
+fn default_track(format: &dyn FormatReader) -> io::Result<&symphonia::core::formats::Track> {
+    format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "No audio track found"))
+}
+
+pub fn probe(path: &str) -> io::Result<AudioMetadata> {
+    let format = open_format(path)?;
+    let params = &default_track(format.as_ref())?.codec_params;
+
+    let sample_rate = params
+        .sample_rate
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Unknown sample rate"))?;
+    let channels = params.channels.map(|c| c.count() as u16).unwrap_or(2);
+
+    let duration_ms = match (params.time_base, params.n_frames) {
+        (Some(time_base), Some(frames)) => {
+            let time = time_base.calc_time(frames);
+            time.seconds * 1000 + (time.frac * 1000.0) as u64
+        }
+        (None, Some(frames)) => frames * 1000 / sample_rate as u64,
+        _ => 0,
+    };
+
+    Ok(AudioMetadata {
+        duration_ms,
+        sample_rate,
+        channels,
+    })
+}
+
+pub struct SymphoniaDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn CodecDecoder>,
+    track_id: u32,
+    source_channels: usize,
+    output_channels: usize,
+    resampler: Option<FftFixedIn<f32>>,
+    resampler_input: Vec<Vec<f32>>, // Planar source frames waiting for a full chunk
+    resampler_delay: usize,         // Output frames of filter delay still to discard
+    skip_frames: usize,             // Frames to drop after a coarse seek
+    pending: VecDeque<f32>,         // Interleaved output ready to hand out
+    finished: bool,
+}
+
+impl SymphoniaDecoder {
+    pub fn open(
+        path: &str,
+        sample_rate: u32,
+        channels: u16,
+        seek_ms: Option<u64>,
+    ) -> io::Result<Self> {
+        let mut format = open_format(path)?;
+        let track = default_track(format.as_ref())?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+
+        let decoder = symphonia::default::get_codecs()
+            .make(&params, &DecoderOptions::default())
+            .map_err(to_io_error)?;
+
+        let source_rate = params
+            .sample_rate
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Unknown sample rate"))?;
+        let source_channels = params.channels.map(|c| c.count()).unwrap_or(2).max(1);
+        let output_channels = channels.max(1) as usize;
+
+        // Surround needs a proper downmix matrix, which FFmpeg applies for `-ac`
+        if source_channels > 2 && source_channels != output_channels {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "{} source channels need downmixing to {}",
+                    source_channels, output_channels
+                ),
+            ));
+        }
+
+        let mut skip_frames = 0;
+        if let Some(ms) = seek_ms.filter(|ms| *ms > 0) {
+            let seeked = format
+                .seek(
+                    SeekMode::Accurate,
+                    SeekTo::Time {
+                        time: Time::from(ms as f64 / 1000.0),
+                        track_id: Some(track_id),
+                    },
+                )
+                .map_err(to_io_error)?;
+            skip_frames = seeked.required_ts.saturating_sub(seeked.actual_ts) as usize;
+        }
+
+        let (resampler, resampler_delay) = if source_rate != sample_rate {
+            let resampler = FftFixedIn::<f32>::new(
+                source_rate as usize,
+                sample_rate as usize,
+                RESAMPLER_CHUNK,
+                2,
+                output_channels,
+            )
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+            let delay = resampler.output_delay();
+            (Some(resampler), delay)
+        } else {
+            (None, 0)
+        };
+
+        Ok(Self {
+            format,
+            decoder,
+            track_id,
+            source_channels,
+            output_channels,
+            resampler,
+            resampler_input: vec![Vec::new(); output_channels],
+            resampler_delay,
+            skip_frames,
+            pending: VecDeque::new(),
+            finished: false,
+        })
+    }
+
+    /// Decode the next packet into `pending`; false once the stream is exhausted
+    fn decode_next(&mut self) -> io::Result<bool> {
+        let packet = loop {
+            match self.format.next_packet() {
+                Ok(packet) if packet.track_id() == self.track_id => break packet,
+                Ok(_) => continue,
+                Err(SymphoniaError::IoError(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                    return Ok(false)
+                }
+                Err(SymphoniaError::ResetRequired) => return Ok(false),
+                Err(e) => return Err(to_io_error(e)),
+            }
+        };
+
+        let decoded = match self.decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // A corrupt packet is skipped rather than ending the track
+            Err(SymphoniaError::DecodeError(msg)) => {
+                log::warn!("Skipping undecodable packet: {}", msg);
+                return Ok(true);
+            }
+            Err(e) => return Err(to_io_error(e)),
+        };
+
+        let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+        samples.copy_interleaved_ref(decoded);
+
+        let mut frames: Vec<&[f32]> = samples.samples().chunks(self.source_channels).collect();
+        let skip = self.skip_frames.min(frames.len());
+        self.skip_frames -= skip;
+        frames.drain(..skip);
+
+        if self.resampler.is_some() {
+            for frame in frames {
+                for (ch, input) in self.resampler_input.iter_mut().enumerate() {
+                    input.push(map_channel(frame, ch, self.output_channels));
+                }
+            }
+            self.run_resampler(false)?;
+        } else {
+            for frame in frames {
+                for ch in 0..self.output_channels {
+                    self.pending
+                        .push_back(map_channel(frame, ch, self.output_channels));
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Resample every complete chunk, or everything left when `flush` is set
+    fn run_resampler(&mut self, flush: bool) -> io::Result<()> {
+        let Some(resampler) = self.resampler.as_mut() else {
+            return Ok(());
+        };
+
+        loop {
+            let needed = resampler.input_frames_next();
+            let available = self.resampler_input[0].len();
+            let mut drained = false;
+
+            let result = if available >= needed {
+                let chunk: Vec<Vec<f32>> = self
+                    .resampler_input
+                    .iter_mut()
+                    .map(|input| input.drain(..needed).collect())
+                    .collect();
+                resampler.process(&chunk, None)
+            } else if flush && available > 0 {
+                let chunk: Vec<Vec<f32>> =
+                    self.resampler_input.iter_mut().map(std::mem::take).collect();
+                resampler.process_partial(Some(&chunk), None)
+            } else if flush {
+                // Input is gone, push out what is left in the filter
+                drained = true;
+                resampler.process_partial::<Vec<f32>>(None, None)
+            } else {
+                return Ok(());
+            };
+
+            let output =
+                result.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            let skip = self.resampler_delay.min(output[0].len());
+            self.resampler_delay -= skip;
+            interleave_into(&mut self.pending, &output, skip);
+
+            if drained {
+                self.resampler = None;
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Decoder for SymphoniaDecoder {
+    fn read_samples(&mut self, buffer: &mut [f32]) -> io::Result<usize> {
+        while self.pending.len() < buffer.len() && !self.finished {
+            if !self.decode_next()? {
+                self.finished = true;
+                self.run_resampler(true)?;
+            }
+        }
+
+        let count = self.pending.len().min(buffer.len());
+        for (dst, src) in buffer.iter_mut().zip(self.pending.drain(..count)) {
+            *dst = src;
+        }
+        Ok(count)
+    }
+}
+
+/// Sample for output channel `ch` from a mono or stereo source frame
+fn map_channel(frame: &[f32], ch: usize, output_channels: usize) -> f32 {
+    if frame.len() == output_channels {
+        frame[ch]
+    } else if output_channels == 1 {
+        frame.iter().sum::<f32>() / frame.len() as f32
+    } else if frame.len() == 1 {
+        frame[0]
+    } else {
+        // Stereo on a wider output: fill the front pair, leave the rest silent
+        frame.get(ch).copied().unwrap_or(0.0)
+    }
+}
+
+fn interleave_into(pending: &mut VecDeque<f32>, planar: &[Vec<f32>], skip: usize) {
+    let frames = planar.first().map(|c| c.len()).unwrap_or(0);
+    for i in skip..frames {
+        for channel in planar {
+            pending.push_back(channel[i]);
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Spectrum and level metering for audio-reactive visuals
+//!
+//! The worker keeps a copy of the samples it pushes to the output. Frames are
+//! analyzed at the point the output is currently playing, which lags the
+//! decoder by whatever is still buffered, so the visuals line up with what
+//! is heard.
+
+use realfft::{RealFftPlanner, RealToComplex};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::sink::StreamFormat;
+
+/// Frames per FFT, about 46 ms at 44.1 kHz
+const FFT_SIZE: usize = 2048;
+
+/// Lowest frequency shown in the spectrum
+const MIN_FREQUENCY: f32 = 20.0;
+const MAX_FREQUENCY: f32 = 20000.0;
+
+/// Band magnitudes map this range of decibels onto 0..1
+const SPECTRUM_FLOOR_DB: f32 = -80.0;
+
+const MAX_RATE_HZ: u32 = 120;
+const MAX_BANDS: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VisualizerSettings {
+    /// Frames emitted per second
+    pub rate_hz: u32,
+    /// Number of spectrum bands, spaced logarithmically
+    pub bands: usize,
+}
+
+impl Default for VisualizerSettings {
+    fn default() -> Self {
+        Self {
+            rate_hz: 30,
+            bands: 32,
+        }
+    }
+}
+
+impl VisualizerSettings {
+    fn clamped(self) -> Self {
+        Self {
+            rate_hz: self.rate_hz.clamp(1, MAX_RATE_HZ),
+            bands: self.bands.clamp(1, MAX_BANDS),
+        }
+    }
+}
+
+/// Payload of the visualization event
+#[derive(Debug, Clone, Serialize)]
+pub struct VisualizationFrame {
+    /// Band magnitudes from low to high frequency, 0..1
+    pub bands: Vec<f32>,
+    /// Linear RMS level per channel
+    pub rms: Vec<f32>,
+    /// Linear peak level per channel
+    pub peak: Vec<f32>,
+}
+
+pub struct Visualizer {
+    settings: VisualizerSettings,
+    format: StreamFormat,
+    history: VecDeque<f32>, // Interleaved samples most recently pushed to the output
+    history_limit: usize,
+    last_frame: Option<Instant>,
+    fft: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    window_gain: f32,
+    input: Vec<f32>,
+    spectrum: Vec<realfft::num_complex::Complex<f32>>,
+    scratch: Vec<realfft::num_complex::Complex<f32>>,
+    band_edges: Vec<usize>, // FFT bin where each band starts, plus the end of the last
+}
+
+impl Visualizer {
+    pub fn new(settings: VisualizerSettings, format: StreamFormat) -> Self {
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(FFT_SIZE);
+        let window: Vec<f32> = (0..FFT_SIZE)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / FFT_SIZE as f32).cos())
+            .collect();
+        let window_gain = window.iter().sum::<f32>();
+
+        let mut visualizer = Self {
+            settings: settings.clamped(),
+            format,
+            history: VecDeque::new(),
+            history_limit: 0,
+            last_frame: None,
+            input: fft.make_input_vec(),
+            spectrum: fft.make_output_vec(),
+            scratch: fft.make_scratch_vec(),
+            fft,
+            window,
+            window_gain,
+            band_edges: Vec::new(),
+        };
+        visualizer.set_format(format);
+        visualizer
+    }
+
+    pub fn set_settings(&mut self, settings: VisualizerSettings) {
+        self.settings = settings.clamped();
+        self.band_edges = band_edges(self.settings.bands, self.format.sample_rate);
+    }
+
+    /// Follow the output format; buffered audio from the old stream is dropped
+    pub fn set_format(&mut self, format: StreamFormat) {
+        self.format = format;
+        let channels = format.channels.max(1) as usize;
+        // Everything the output may still have queued, plus one analysis window
+        self.history_limit = (format.sample_rate as usize + FFT_SIZE) * channels;
+        self.band_edges = band_edges(self.settings.bands, format.sample_rate);
+        self.clear();
+    }
+
+    /// Record samples as they are pushed to the output
+    pub fn push(&mut self, samples: &[f32]) {
+        self.history.extend(samples);
+        let excess = self.history.len().saturating_sub(self.history_limit);
+        self.history.drain(..excess);
+    }
+
+    /// Forget pushed audio, e.g. after the output was flushed
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    /// Analyze the audio being played now, if a frame is due.
+    /// `queued` is the number of samples pushed but not yet played.
+    pub fn frame(&mut self, queued: usize) -> Option<VisualizationFrame> {
+        let now = Instant::now();
+        let interval = Duration::from_secs(1) / self.settings.rate_hz;
+        if self
+            .last_frame
+            .is_some_and(|last| now.duration_since(last) < interval)
+        {
+            return None;
+        }
+        self.last_frame = Some(now);
+
+        let channels = self.format.channels.max(1) as usize;
+        let playing = self.history.len().saturating_sub(queued);
+        let playing = playing - playing % channels;
+        let frames = (playing / channels).min(FFT_SIZE);
+        let start = playing - frames * channels;
+
+        let mut sum_squares = vec![0.0f32; channels];
+        let mut peak = vec![0.0f32; channels];
+        self.input.fill(0.0);
+        // Right-align the window so the newest frame is always analyzed
+        let offset = FFT_SIZE - frames;
+        for frame in 0..frames {
+            let mut mono = 0.0;
+            for (channel, (squares, peak)) in
+                sum_squares.iter_mut().zip(peak.iter_mut()).enumerate()
+            {
+                let sample = self.history[start + frame * channels + channel];
+                *squares += sample * sample;
+                *peak = peak.max(sample.abs());
+                mono += sample;
+            }
+            self.input[offset + frame] = mono / channels as f32 * self.window[offset + frame];
+        }
+
+        let rms = sum_squares
+            .iter()
+            .map(|sum| {
+                if frames > 0 {
+                    (sum / frames as f32).sqrt()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let bands = if self
+            .fft
+            .process_with_scratch(&mut self.input, &mut self.spectrum, &mut self.scratch)
+            .is_ok()
+        {
+            self.band_edges
+                .windows(2)
+                .map(|edge| {
+                    let magnitude = self.spectrum[edge[0]..edge[1]]
+                        .iter()
+                        .fold(0.0f32, |m, bin| m.max(bin.norm()));
+                    // A full-scale sine reads as 0 dB
+                    let db = 20.0 * (2.0 * magnitude / self.window_gain).max(1e-9).log10();
+                    ((db - SPECTRUM_FLOOR_DB) / -SPECTRUM_FLOOR_DB).clamp(0.0, 1.0)
+                })
+                .collect()
+        } else {
+            vec![0.0; self.settings.bands]
+        };
+
+        Some(VisualizationFrame { bands, rms, peak })
+    }
+}
+
+/// Logarithmically spaced FFT bin ranges, each at least one bin wide
+fn band_edges(bands: usize, sample_rate: u32) -> Vec<usize> {
+    let bins = FFT_SIZE / 2 + 1;
+    let bin_hz = sample_rate.max(1) as f32 / FFT_SIZE as f32;
+    let max_frequency = MAX_FREQUENCY.min(sample_rate as f32 / 2.0);
+    let ratio = (max_frequency / MIN_FREQUENCY).max(1.0);
+
+    let mut edges = Vec::with_capacity(bands + 1);
+    let mut previous = 0;
+    for band in 0..=bands {
+        let frequency = MIN_FREQUENCY * ratio.powf(band as f32 / bands as f32);
+        let bin = ((frequency / bin_hz).round() as usize).clamp(1, bins - 1);
+        let edge = if band == 0 {
+            bin
+        } else {
+            bin.max(previous + 1).min(bins)
+        };
+        edges.push(edge);
+        previous = edge;
+    }
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FORMAT: StreamFormat = StreamFormat {
+        sample_rate: 44100,
+        channels: 2,
+    };
+
+    #[test]
+    fn test_sine_lands_in_its_band_and_meters_levels() {
+        let mut visualizer = Visualizer::new(VisualizerSettings::default(), FORMAT);
+        // 1 kHz at half scale on the left, silence on the right
+        let samples: Vec<f32> = (0..FFT_SIZE * 2)
+            .flat_map(|i| {
+                let t = i as f32 / FORMAT.sample_rate as f32;
+                [0.5 * (2.0 * PI * 1000.0 * t).sin(), 0.0]
+            })
+            .collect();
+        visualizer.push(&samples);
+        // Anything still queued is ignored
+        visualizer.push(&vec![1.0; 1000]);
+
+        let frame = visualizer.frame(1000).unwrap();
+        assert!((frame.peak[0] - 0.5).abs() < 0.01, "{:?}", frame.peak);
+        assert!(
+            (frame.rms[0] - 0.5 / 2f32.sqrt()).abs() < 0.01,
+            "{:?}",
+            frame.rms
+        );
+        assert_eq!(frame.peak[1], 0.0);
+
+        let edges = band_edges(32, FORMAT.sample_rate);
+        let bin = (1000.0 * FFT_SIZE as f32 / FORMAT.sample_rate as f32).round() as usize;
+        let loudest = frame
+            .bands
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(band, _)| band)
+            .unwrap();
+        assert!((edges[loudest]..edges[loudest + 1]).contains(&bin));
+        assert_eq!(frame.bands.len(), 32);
+
+        // The next frame waits for the configured rate
+        assert!(visualizer.frame(0).is_none());
+    }
+}
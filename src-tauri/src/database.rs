@@ -1,5 +1,16 @@
+use crate::audio::ab_loop::SavedLoop;
+use crate::audio::channel_mix::ChannelMix;
+use crate::audio::equalizer::EqPreset;
+use crate::audio::history::PlayRecord;
+use crate::library::ArtistSort;
+use crate::paging::{AlbumQuery, Page, PlaylistQuery, TrackQuery};
+use crate::playlist_folders::{self, PlaylistFolder};
 use crate::scanner::TrackMetadata;
-use rusqlite::{params, Connection, Result, Transaction};
+use crate::search::{self, SearchResults, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::smart_playlist::{escape_like, SmartRules};
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Result, Transaction};
+use std::collections::HashMap;
 use std::path::Path;
 use log::warn;
 
@@ -36,26 +47,101 @@ impl DbHelper {
         if !table_exists {
             warn!("Database tables missing in {:?}. Applying initial schema...", path);
             conn.execute_batch(include_str!("../migrations/001_initial_schema.sql"))?;
-        } else {
-            // Manual migration check for artwork_path to ensure it exists even if plugin migration is skipped
-            let has_artwork: i64 = conn
-                .query_row(
-                    "SELECT COUNT(*) FROM pragma_table_info('playlists') WHERE name='artwork_path'",
-                    [],
-                    |row| row.get(0),
-                )
-                .unwrap_or(0);
+        }
 
-            if has_artwork == 0 {
-                warn!("Applying missing column artwork_path to playlists...");
-                // We ignore error here just in case, but usually it should work
-                let _ = conn.execute("ALTER TABLE playlists ADD COLUMN artwork_path TEXT", []);
-            }
+        // Manual migration checks so later migrations exist even if the plugin migrations are skipped
+        Self::apply_missing_migration(
+            &conn,
+            "playlists",
+            "artwork_path",
+            include_str!("../migrations/002_add_playlist_artwork.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "tracks",
+            "replay_gain_db",
+            include_str!("../migrations/003_add_loudness.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "eq_presets",
+            "bands",
+            include_str!("../migrations/004_add_eq_presets.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "tracks",
+            "silence_start_ms",
+            include_str!("../migrations/005_add_silence.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "track_loops",
+            "start_ms",
+            include_str!("../migrations/006_add_track_loops.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "output_device_settings",
+            "balance",
+            include_str!("../migrations/007_add_device_settings.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "playlists",
+            "smart_rules",
+            include_str!("../migrations/008_add_smart_playlists.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "playlists",
+            "folder_id",
+            include_str!("../migrations/010_add_playlist_folders.sql"),
+        );
+        Self::apply_missing_migration(
+            &conn,
+            "tracks_fts",
+            "title",
+            include_str!("../migrations/011_add_search_index.sql"),
+        );
+
+        // 009 adds no column, so look for the constraint it removes instead
+        let has_unique_entries: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master
+                WHERE type = 'table' AND name = 'playlist_tracks'
+                AND sql LIKE '%UNIQUE(playlist_id, track_id)%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+        if has_unique_entries > 0 {
+            warn!("Rebuilding playlist_tracks to allow duplicate entries...");
+            let tx = conn.unchecked_transaction()?;
+            tx.execute_batch(include_str!("../migrations/009_playlist_entries.sql"))?;
+            tx.commit()?;
         }
 
         Ok(Self { conn })
     }
 
+    /// Run `sql` if `table` does not have `column` yet
+    fn apply_missing_migration(conn: &Connection, table: &str, column: &str, sql: &str) {
+        let has_column: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM pragma_table_info(?) WHERE name = ?",
+                params![table, column],
+                |row| row.get(0),
+            )
+            .unwrap_or(0);
+
+        if has_column == 0 {
+            warn!("Applying missing column {} to {}...", column, table);
+            // We ignore error here just in case, but usually it should work
+            let _ = conn.execute_batch(sql);
+        }
+    }
+
     pub fn get_or_create_artist(tx: &Transaction, name: &str) -> Result<i64> {
         {
             let mut stmt = tx.prepare("SELECT id FROM artists WHERE name = ?")?;
@@ -147,8 +233,28 @@ impl DbHelper {
         };
 
         let track_id = if exists {
-            let mut stmt = tx.prepare("SELECT id FROM tracks WHERE file_path = ?")?;
-            let id: i64 = stmt.query_row(params![metadata.file_path], |row| row.get(0))?;
+            let mut stmt =
+                tx.prepare("SELECT id, file_size, duration_ms FROM tracks WHERE file_path = ?")?;
+            let (id, old_size, old_duration): (i64, Option<u64>, Option<u64>) = stmt
+                .query_row(params![metadata.file_path], |row| {
+                    Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+                })?;
+
+            // The audio changed, so measured silence and loudness no longer apply
+            if old_size != Some(metadata.file_size) || old_duration != Some(metadata.duration_ms) {
+                // Album gain derived from this track has to be measured again too
+                tx.execute(
+                    "UPDATE albums SET replay_gain_db = NULL, replay_peak = NULL
+                    WHERE id = (SELECT album_id FROM tracks WHERE id = ?)",
+                    params![id],
+                )?;
+                tx.execute(
+                    "UPDATE tracks SET silence_start_ms = NULL, silence_end_ms = NULL,
+                    silence_threshold_db = NULL, replay_gain_db = NULL, replay_peak = NULL
+                    WHERE id = ?",
+                    params![id],
+                )?;
+            }
 
             tx.execute(
                 "UPDATE tracks SET 
@@ -156,6 +262,8 @@ impl DbHelper {
                     track_number = ?, disc_number = ?, duration_ms = ?, 
                     file_size = ?, file_format = ?, sample_rate = ?, 
                     bit_rate = ?, channels = ?, genre = ?, year = ?, 
+                    replay_gain_db = COALESCE(?, replay_gain_db),
+                    replay_peak = COALESCE(?, replay_peak),
                     updated_at = CURRENT_TIMESTAMP 
                 WHERE id = ?",
                 params![
@@ -173,6 +281,8 @@ impl DbHelper {
                     metadata.channels,
                     metadata.genre,
                     metadata.year,
+                    metadata.loudness.track_gain_db,
+                    metadata.loudness.track_peak,
                     id
                 ],
             )?;
@@ -183,8 +293,8 @@ impl DbHelper {
                     title, artist_id, album_id, album_artist, 
                     track_number, disc_number, duration_ms, 
                     file_path, file_size, file_format, sample_rate, 
-                    bit_rate, channels, genre, year
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    bit_rate, channels, genre, year, replay_gain_db, replay_peak
+                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 params![
                     metadata.title.as_deref().unwrap_or(&metadata.file_name),
                     artist_id,
@@ -200,12 +310,26 @@ impl DbHelper {
                     metadata.bit_rate,
                     metadata.channels,
                     metadata.genre,
-                    metadata.year
+                    metadata.year,
+                    metadata.loudness.track_gain_db,
+                    metadata.loudness.track_peak
                 ],
             )?;
             tx.last_insert_rowid()
         };
 
+        if let Some((silence, threshold_db)) = &metadata.silence {
+            Self::set_track_silence(tx, track_id, silence, *threshold_db)?;
+        }
+
+        // Album gain tags describe the whole album, so any tagged track can set it
+        if let (Some(album_id), Some(gain)) = (album_id, metadata.loudness.album_gain_db) {
+            tx.execute(
+                "UPDATE albums SET replay_gain_db = ?, replay_peak = COALESCE(?, replay_peak) WHERE id = ?",
+                params![gain, metadata.loudness.album_peak, album_id],
+            )?;
+        }
+
         // Handle multiple artists (track_artists junction table)
         // First, clear existing associations for this track (simplest update strategy)
         tx.execute(
@@ -224,6 +348,21 @@ impl DbHelper {
             )?;
         }
 
+        Self::index_track(tx, track_id)?;
+
+        Ok(())
+    }
+
+    /// Refresh the search index entry of a track from its current tags
+    pub fn index_track(tx: &Transaction, track_id: i64) -> Result<()> {
+        tx.execute("DELETE FROM tracks_fts WHERE rowid = ?", params![track_id])?;
+        tx.execute(
+            &format!(
+                "INSERT INTO tracks_fts (rowid, title, artists, album, album_artist, genre) {}",
+                search::TRACK_INDEX_SELECT
+            ),
+            params![track_id],
+        )?;
         Ok(())
     }
 
@@ -246,6 +385,107 @@ impl DbHelper {
         Ok(paths)
     }
 
+    /// Tracks that have no ReplayGain value yet, as (id, path)
+    pub fn get_tracks_missing_loudness(&self) -> Result<Vec<(i64, String)>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT id, file_path FROM tracks WHERE replay_gain_db IS NULL")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut tracks = Vec::new();
+        for row in rows {
+            tracks.push(row?);
+        }
+        Ok(tracks)
+    }
+
+    pub fn set_track_loudness(tx: &Transaction, id: i64, gain_db: f32, peak: f32) -> Result<()> {
+        tx.execute(
+            "UPDATE tracks SET replay_gain_db = ?, replay_peak = ? WHERE id = ?",
+            params![gain_db, peak, id],
+        )?;
+        Ok(())
+    }
+
+    /// Derive album gain for albums without one whose tracks are all measured
+    pub fn update_album_loudness(tx: &Transaction) -> Result<usize> {
+        let album_ids: Vec<i64> = {
+            let mut stmt = tx.prepare(
+                "SELECT al.id FROM albums al
+                WHERE al.replay_gain_db IS NULL
+                AND NOT EXISTS (
+                    SELECT 1 FROM tracks t WHERE t.album_id = al.id AND t.replay_gain_db IS NULL
+                )",
+            )?;
+            let rows = stmt.query_map([], |row| row.get(0))?;
+            rows.collect::<Result<_>>()?
+        };
+
+        let mut updated = 0;
+        for album_id in album_ids {
+            let tracks: Vec<(f32, f32, u64)> = {
+                let mut stmt = tx.prepare(
+                    "SELECT replay_gain_db, COALESCE(replay_peak, 1.0), duration_ms
+                    FROM tracks WHERE album_id = ?",
+                )?;
+                let rows =
+                    stmt.query_map(params![album_id], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+                rows.collect::<Result<_>>()?
+            };
+
+            if let Some((gain, peak)) = crate::loudness::album_loudness(&tracks) {
+                tx.execute(
+                    "UPDATE albums SET replay_gain_db = ?, replay_peak = ? WHERE id = ?",
+                    params![gain, peak, album_id],
+                )?;
+                updated += 1;
+            }
+        }
+        Ok(updated)
+    }
+
+    /// Tracks whose silence was never measured at `threshold_db`, as (id, path)
+    pub fn get_tracks_missing_silence(&self, threshold_db: f32) -> Result<Vec<(i64, String)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, file_path FROM tracks
+            WHERE silence_threshold_db IS NULL OR silence_threshold_db != ?",
+        )?;
+        let rows = stmt.query_map(params![threshold_db], |row| Ok((row.get(0)?, row.get(1)?)))?;
+
+        let mut tracks = Vec::new();
+        for row in rows {
+            tracks.push(row?);
+        }
+        Ok(tracks)
+    }
+
+    /// Files whose silence was measured at `threshold_db`, with the size and
+    /// duration they had then
+    pub fn get_silence_measured(&self, threshold_db: f32) -> Result<HashMap<String, (u64, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT file_path, file_size, duration_ms FROM tracks
+            WHERE silence_threshold_db = ? AND file_size IS NOT NULL AND duration_ms IS NOT NULL",
+        )?;
+        let rows = stmt.query_map(params![threshold_db], |row| {
+            Ok((row.get(0)?, (row.get(1)?, row.get(2)?)))
+        })?;
+        rows.collect()
+    }
+
+    pub fn set_track_silence(
+        tx: &Transaction,
+        id: i64,
+        silence: &crate::silence::TrackSilence,
+        threshold_db: f32,
+    ) -> Result<()> {
+        tx.execute(
+            "UPDATE tracks SET silence_start_ms = ?, silence_end_ms = ?, silence_threshold_db = ?
+            WHERE id = ?",
+            params![silence.silence_start_ms, silence.silence_end_ms, threshold_db, id],
+        )?;
+        Ok(())
+    }
+
     pub fn delete_tracks(tx: &Transaction, ids: &[i64]) -> Result<()> {
         // SQLite doesn't have a clean WHERE IN (?) for array binding in rusqlite readily available without dynamic SQL construction
         // or using a series of statements.
@@ -257,8 +497,11 @@ impl DbHelper {
         }
 
         let mut stmt = tx.prepare("DELETE FROM tracks WHERE id = ?")?;
+        let mut index_stmt = tx.prepare("DELETE FROM tracks_fts WHERE rowid = ?")?;
         for id in ids {
+            Self::remove_track_entries(tx, *id)?;
             stmt.execute(params![id])?;
+            index_stmt.execute(params![id])?;
         }
 
         Ok(())
@@ -273,38 +516,117 @@ impl DbHelper {
     }
 
     pub fn delete_track(&self, id: i64) -> Result<()> {
+        Self::remove_track_entries(&self.conn, id)?;
         self.conn
             .execute("DELETE FROM tracks WHERE id = ?", params![id])?;
+        self.conn
+            .execute("DELETE FROM tracks_fts WHERE rowid = ?", params![id])?;
         Ok(())
     }
 
+    /// Columns `read_track` expects, with tracks joined as `t`, their artist
+    /// as `ar` and their album as `al`
+    const TRACK_COLUMNS: &'static str = "t.id,
+        t.title,
+        ar.name as artist,
+        al.title as album,
+        t.duration_ms,
+        t.file_path,
+        al.artwork_path,
+        t.replay_gain_db,
+        t.replay_peak,
+        al.replay_gain_db,
+        al.replay_peak,
+        t.silence_start_ms,
+        t.silence_end_ms,
+        (SELECT json_group_array(name) FROM (
+            SELECT a.name FROM track_artists ta JOIN artists a ON a.id = ta.artist_id
+            WHERE ta.track_id = t.id
+            ORDER BY ta.rowid
+        )) as artists,
+        t.track_number,
+        t.disc_number,
+        t.year,
+        t.genre,
+        t.file_format,
+        t.sample_rate,
+        t.bit_rate,
+        t.channels,
+        t.file_size,
+        (SELECT COUNT(*) FROM play_history ph
+            WHERE ph.track_id = t.id AND ph.completed) as play_count,
+        (SELECT COUNT(*) FROM play_history ph
+            WHERE ph.track_id = t.id AND NOT COALESCE(ph.completed, FALSE)) as skip_count,
+        (SELECT MAX(ph.played_at) FROM play_history ph
+            WHERE ph.track_id = t.id) as last_played_at";
+
+    /// Disc by disc, tracks without a disc number on disc 1 and tracks without
+    /// a track number last on their disc
+    const DISC_ORDER: &'static str = "COALESCE(t.disc_number, 1) ASC,
+        t.track_number IS NULL ASC,
+        t.track_number ASC,
+        t.title COLLATE NOCASE ASC";
+
+    fn read_track(row: &rusqlite::Row) -> Result<crate::library::LibraryTrack> {
+        let artist: Option<String> = row.get(2)?;
+        let mut artists: Vec<String> = serde_json::from_str(&row.get::<_, String>(13)?)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(13, Type::Text, Box::new(e)))?;
+        if artists.is_empty() {
+            artists.extend(artist.clone());
+        }
+
+        Ok(crate::library::LibraryTrack {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            artist,
+            album: row.get(3)?,
+            duration_ms: row.get(4)?,
+            file_path: row.get(5)?,
+            artwork_path: row.get(6)?,
+            loudness: crate::loudness::TrackLoudness {
+                track_gain_db: row.get(7)?,
+                track_peak: row.get(8)?,
+                album_gain_db: row.get(9)?,
+                album_peak: row.get(10)?,
+            },
+            silence: crate::silence::TrackSilence {
+                silence_start_ms: row.get(11)?,
+                silence_end_ms: row.get(12)?,
+            },
+            tags: crate::library::TrackTags {
+                artists,
+                track_number: row.get(14)?,
+                disc_number: row.get(15)?,
+                year: row.get(16)?,
+                genre: row.get(17)?,
+            },
+            format: crate::library::TrackFormat {
+                file_format: row.get(18)?,
+                sample_rate: row.get(19)?,
+                bit_rate: row.get(20)?,
+                channels: row.get(21)?,
+                file_size: row.get(22)?,
+            },
+            plays: crate::library::TrackPlays {
+                play_count: row.get(23)?,
+                skip_count: row.get(24)?,
+                last_played_at: row.get(25)?,
+            },
+        })
+    }
+
     pub fn get_all_tracks(&self) -> Result<Vec<crate::library::LibraryTrack>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT 
-                t.id, 
-                t.title, 
-                ar.name as artist, 
-                al.title as album, 
-                t.duration_ms, 
-                t.file_path, 
-                al.artwork_path 
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {}
             FROM tracks t
             LEFT JOIN artists ar ON t.artist_id = ar.id
             LEFT JOIN albums al ON t.album_id = al.id
             ORDER BY t.created_at DESC",
-        )?;
+            Self::TRACK_COLUMNS
+        ))?;
 
-        let track_iter = stmt.query_map([], |row| {
-            Ok(crate::library::LibraryTrack {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                artist: row.get(2)?,
-                album: row.get(3)?,
-                duration_ms: row.get(4)?,
-                file_path: row.get(5)?,
-                artwork_path: row.get(6)?,
-            })
-        })?;
+        let track_iter = stmt.query_map([], Self::read_track)?;
 
         let mut tracks = Vec::new();
         for track in track_iter {
@@ -324,7 +646,9 @@ impl DbHelper {
                 al.year,
                 al.artwork_path,
                 COUNT(t.id) as track_count,
-                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms
+                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms,
+                COUNT(DISTINCT CASE WHEN t.id IS NOT NULL THEN COALESCE(t.disc_number, 1) END)
+                    as disc_count
             FROM albums al
             LEFT JOIN artists ar ON al.artist_id = ar.id
             LEFT JOIN tracks t ON t.album_id = al.id
@@ -342,6 +666,7 @@ impl DbHelper {
                 artwork_path: row.get(5)?,
                 track_count: row.get(6)?,
                 total_duration_ms: row.get(7)?,
+                disc_count: row.get(8)?,
             })
         })?;
 
@@ -363,7 +688,9 @@ impl DbHelper {
                 al.year,
                 al.artwork_path,
                 COUNT(t.id) as track_count,
-                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms
+                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms,
+                COUNT(DISTINCT CASE WHEN t.id IS NOT NULL THEN COALESCE(t.disc_number, 1) END)
+                    as disc_count
             FROM albums al
             LEFT JOIN artists ar ON al.artist_id = ar.id
             LEFT JOIN tracks t ON t.album_id = al.id
@@ -382,40 +709,219 @@ impl DbHelper {
                 artwork_path: row.get(5)?,
                 track_count: row.get(6)?,
                 total_duration_ms: row.get(7)?,
+                disc_count: row.get(8)?,
             }))
         } else {
             Ok(None)
         }
     }
 
+    /// Tracks of an album disc by disc. Tracks without a disc number belong to
+    /// disc 1, and tracks without a track number come last on their disc.
     pub fn get_album_tracks(&self, album_id: i64) -> Result<Vec<crate::library::LibraryTrack>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT 
-                t.id, 
-                t.title, 
-                ar.name as artist, 
-                al.title as album, 
-                t.duration_ms, 
-                t.file_path, 
-                al.artwork_path 
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {}
             FROM tracks t
             LEFT JOIN artists ar ON t.artist_id = ar.id
             LEFT JOIN albums al ON t.album_id = al.id
             WHERE t.album_id = ?
-            ORDER BY t.disc_number ASC, t.track_number ASC, t.title ASC",
+            ORDER BY {}",
+            Self::TRACK_COLUMNS,
+            Self::DISC_ORDER
+        ))?;
+
+        let track_iter = stmt.query_map(params![album_id], Self::read_track)?;
+
+        let mut tracks = Vec::new();
+        for track in track_iter {
+            tracks.push(track?);
+        }
+
+        Ok(tracks)
+    }
+
+    /// Artists with their track and album counts. A track counts for its track
+    /// artist and every credited artist; an album counts for its album artist
+    /// and every artist with a track on it. Only artists whose `artist_id`
+    /// meets `scope` are counted, so a single artist is looked up without
+    /// counting the whole library.
+    fn artist_select(scope: &str) -> String {
+        format!(
+            "WITH
+            artist_tracks AS (
+                SELECT id AS track_id, artist_id FROM tracks
+                WHERE artist_id IS NOT NULL AND {0}
+                UNION
+                SELECT track_id, artist_id FROM track_artists WHERE {0}
+            ),
+            artist_albums AS (
+                SELECT id AS album_id, artist_id FROM albums
+                WHERE artist_id IS NOT NULL AND {0}
+                UNION
+                SELECT t.album_id, at.artist_id
+                FROM artist_tracks at
+                JOIN tracks t ON t.id = at.track_id
+                WHERE t.album_id IS NOT NULL
+            ),
+            track_counts AS (
+                SELECT artist_id, COUNT(*) AS n FROM artist_tracks GROUP BY artist_id
+            ),
+            album_counts AS (
+                SELECT artist_id, COUNT(*) AS n FROM artist_albums GROUP BY artist_id
+            )
+        SELECT
+            ar.id,
+            ar.name,
+            COALESCE(tc.n, 0) AS track_count,
+            COALESCE(ac.n, 0) AS album_count,
+            (SELECT al.artwork_path FROM albums al
+                WHERE al.artist_id = ar.id AND al.artwork_path IS NOT NULL
+                ORDER BY al.year DESC LIMIT 1) AS artwork_path
+        FROM artists ar
+        LEFT JOIN track_counts tc ON tc.artist_id = ar.id
+        LEFT JOIN album_counts ac ON ac.artist_id = ar.id
+        WHERE (tc.n IS NOT NULL OR ac.n IS NOT NULL)",
+            scope
+        )
+    }
+
+    fn read_artist(row: &rusqlite::Row) -> Result<crate::library::LibraryArtist> {
+        Ok(crate::library::LibraryArtist {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            track_count: row.get(2)?,
+            album_count: row.get(3)?,
+            artwork_path: row.get(4)?,
+        })
+    }
+
+    /// Artists that have tracks or albums, with names containing `filter` if given
+    pub fn get_all_artists(
+        &self,
+        sort: ArtistSort,
+        descending: bool,
+        filter: Option<&str>,
+    ) -> Result<Vec<crate::library::LibraryArtist>> {
+        let direction = if descending { "DESC" } else { "ASC" };
+        let order = match sort {
+            ArtistSort::Name => format!("ar.name COLLATE NOCASE {}", direction),
+            ArtistSort::TrackCount => {
+                format!("track_count {}, ar.name COLLATE NOCASE", direction)
+            }
+            ArtistSort::AlbumCount => {
+                format!("album_count {}, ar.name COLLATE NOCASE", direction)
+            }
+        };
+        let sql = format!(
+            "{} AND (?1 IS NULL OR ar.name LIKE ?1 ESCAPE '\\') ORDER BY {}",
+            Self::artist_select("1"),
+            order
+        );
+        let pattern = filter
+            .map(str::trim)
+            .filter(|text| !text.is_empty())
+            .map(|text| format!("%{}%", escape_like(text)));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let artist_iter = stmt.query_map(params![pattern], Self::read_artist)?;
+
+        let mut artists = Vec::new();
+        for artist in artist_iter {
+            artists.push(artist?);
+        }
+
+        Ok(artists)
+    }
+
+    pub fn get_artist_by_id(&self, id: i64) -> Result<Option<crate::library::LibraryArtist>> {
+        let sql = format!("{} AND ar.id = ?1", Self::artist_select("artist_id = ?1"));
+        self.conn
+            .query_row(&sql, params![id], Self::read_artist)
+            .optional()
+    }
+
+    /// Albums by the artist, and other albums they have tracks on, oldest first
+    pub fn get_artist_albums(&self, artist_id: i64) -> Result<crate::library::ArtistAlbums> {
+        let albums = self.query_albums("al.artist_id = ?1", artist_id)?;
+        let appears_on = self.query_albums(
+            "al.artist_id IS NOT ?1 AND EXISTS (
+                SELECT 1 FROM tracks on_album WHERE on_album.album_id = al.id AND (
+                    on_album.artist_id = ?1 OR EXISTS (
+                        SELECT 1 FROM track_artists ta
+                        WHERE ta.track_id = on_album.id AND ta.artist_id = ?1
+                    )
+                )
+            )",
+            artist_id,
         )?;
 
-        let track_iter = stmt.query_map(params![album_id], |row| {
-            Ok(crate::library::LibraryTrack {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                artist: row.get(2)?,
-                album: row.get(3)?,
-                duration_ms: row.get(4)?,
-                file_path: row.get(5)?,
-                artwork_path: row.get(6)?,
-            })
-        })?;
+        Ok(crate::library::ArtistAlbums { albums, appears_on })
+    }
+
+    fn query_albums(&self, condition: &str, id: i64) -> Result<Vec<crate::library::LibraryAlbum>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                al.id,
+                al.title,
+                al.artist_id,
+                ar.name as artist_name,
+                al.year,
+                al.artwork_path,
+                COUNT(t.id) as track_count,
+                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms,
+                COUNT(DISTINCT CASE WHEN t.id IS NOT NULL THEN COALESCE(t.disc_number, 1) END)
+                    as disc_count
+            FROM albums al
+            LEFT JOIN artists ar ON al.artist_id = ar.id
+            LEFT JOIN tracks t ON t.album_id = al.id
+            WHERE {}
+            GROUP BY al.id
+            ORDER BY al.year ASC, al.title COLLATE NOCASE ASC",
+            condition
+        ))?;
+
+        let album_iter = stmt.query_map(params![id], Self::read_album)?;
+
+        let mut albums = Vec::new();
+        for album in album_iter {
+            albums.push(album?);
+        }
+
+        Ok(albums)
+    }
+
+    fn read_album(row: &rusqlite::Row) -> Result<crate::library::LibraryAlbum> {
+        Ok(crate::library::LibraryAlbum {
+            id: row.get(0)?,
+            title: row.get(1)?,
+            artist_id: row.get(2)?,
+            artist_name: row.get(3)?,
+            year: row.get(4)?,
+            artwork_path: row.get(5)?,
+            track_count: row.get(6)?,
+            total_duration_ms: row.get(7)?,
+            disc_count: row.get(8)?,
+        })
+    }
+
+    /// Tracks the artist performs or is credited on, grouped by album
+    pub fn get_artist_tracks(&self, artist_id: i64) -> Result<Vec<crate::library::LibraryTrack>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {}
+            FROM tracks t
+            LEFT JOIN artists ar ON t.artist_id = ar.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            WHERE t.artist_id = ?1 OR EXISTS (
+                SELECT 1 FROM track_artists ta WHERE ta.track_id = t.id AND ta.artist_id = ?1
+            )
+            ORDER BY al.year ASC, al.title COLLATE NOCASE ASC, {}",
+            Self::TRACK_COLUMNS,
+            Self::DISC_ORDER
+        ))?;
+
+        let track_iter = stmt.query_map(params![artist_id], Self::read_track)?;
 
         let mut tracks = Vec::new();
         for track in track_iter {
@@ -425,12 +931,155 @@ impl DbHelper {
         Ok(tracks)
     }
 
+    pub fn get_tracks_page(
+        &self,
+        query: &TrackQuery,
+    ) -> Result<Page<crate::library::LibraryTrack>> {
+        let listing = query.to_sql();
+        let from = "FROM tracks t
+            LEFT JOIN artists ar ON t.artist_id = ar.id
+            LEFT JOIN albums al ON t.album_id = al.id";
+
+        let total = self.conn.query_row(
+            &format!("SELECT COUNT(*) {} {}", from, listing.conditions),
+            rusqlite::params_from_iter(&listing.params),
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {}
+            {} {}
+            ORDER BY {}
+            LIMIT {} OFFSET {}",
+            Self::TRACK_COLUMNS,
+            from,
+            listing.conditions,
+            listing.order,
+            listing.limit,
+            query.offset
+        ))?;
+
+        let track_iter = stmt.query_map(
+            rusqlite::params_from_iter(&listing.params),
+            Self::read_track,
+        )?;
+
+        let mut items = Vec::new();
+        for track in track_iter {
+            items.push(track?);
+        }
+
+        Ok(Page {
+            items,
+            total,
+            offset: query.offset,
+        })
+    }
+
+    pub fn get_albums_page(
+        &self,
+        query: &AlbumQuery,
+    ) -> Result<Page<crate::library::LibraryAlbum>> {
+        let listing = query.to_sql();
+
+        let total = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM albums al
+                LEFT JOIN artists ar ON al.artist_id = ar.id
+                {}",
+                listing.conditions
+            ),
+            rusqlite::params_from_iter(&listing.params),
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                al.id,
+                al.title,
+                al.artist_id,
+                ar.name as artist_name,
+                al.year,
+                al.artwork_path,
+                COUNT(t.id) as track_count,
+                COALESCE(SUM(t.duration_ms), 0) as total_duration_ms,
+                COUNT(DISTINCT CASE WHEN t.id IS NOT NULL THEN COALESCE(t.disc_number, 1) END)
+                    as disc_count
+            FROM albums al
+            LEFT JOIN artists ar ON al.artist_id = ar.id
+            LEFT JOIN tracks t ON t.album_id = al.id
+            {}
+            GROUP BY al.id
+            ORDER BY {}
+            LIMIT {} OFFSET {}",
+            listing.conditions, listing.order, listing.limit, query.offset
+        ))?;
+
+        let album_iter = stmt.query_map(
+            rusqlite::params_from_iter(&listing.params),
+            Self::read_album,
+        )?;
+
+        let mut items = Vec::new();
+        for album in album_iter {
+            items.push(album?);
+        }
+
+        Ok(Page {
+            items,
+            total,
+            offset: query.offset,
+        })
+    }
+
     pub fn create_playlist(
         &self,
         name: String,
         description: Option<String>,
     ) -> Result<crate::playlists::Playlist> {
-        let mut stmt = self.conn.prepare(
+        Self::insert_playlist(&self.conn, name, description)
+    }
+
+    /// Create a manual playlist holding `track_ids`, or nothing if any insert
+    /// fails. A taken name gets a number, as in "Name (2)".
+    pub fn create_playlist_with_tracks(
+        &mut self,
+        name: String,
+        track_ids: &[i64],
+    ) -> Result<crate::playlists::Playlist> {
+        let tx = self.conn.transaction()?;
+        let name = Self::unused_playlist_name(&tx, name)?;
+        let mut playlist = Self::insert_playlist(&tx, name, None)?;
+        Self::insert_playlist_entries(&tx, playlist.id, track_ids, None)?;
+        tx.commit()?;
+
+        playlist.track_count = track_ids.len() as i64;
+        Ok(playlist)
+    }
+
+    fn unused_playlist_name(conn: &Connection, name: String) -> Result<String> {
+        let mut candidate = name.clone();
+        for n in 2.. {
+            let taken: bool = conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM playlists WHERE name = ?)",
+                params![candidate],
+                |row| row.get(0),
+            )?;
+            if !taken {
+                break;
+            }
+            candidate = format!("{} ({})", name, n);
+        }
+        Ok(candidate)
+    }
+
+    fn insert_playlist(
+        conn: &Connection,
+        name: String,
+        description: Option<String>,
+    ) -> Result<crate::playlists::Playlist> {
+        let mut stmt = conn.prepare(
             "INSERT INTO playlists (name, description) VALUES (?, ?) RETURNING id, name, description, created_at",
         )?;
 
@@ -443,12 +1092,102 @@ impl DbHelper {
                 artwork_path: None, // New playlists have no artwork
                 track_count: 0,
                 created_at: row.get::<_, String>(3)?,
+                smart_rules: None,
+                folder_id: None,
             })
         })?;
 
         Ok(playlist)
     }
 
+    pub fn create_smart_playlist(
+        &self,
+        name: String,
+        description: Option<String>,
+        rules: &SmartRules,
+    ) -> Result<crate::playlists::Playlist> {
+        let rules_json = serde_json::to_string(rules)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        let mut playlist = self.conn.query_row(
+            "INSERT INTO playlists (name, description, smart_rules) VALUES (?, ?, ?) RETURNING id, name, description, created_at",
+            params![name, description, rules_json],
+            |row| {
+                Ok(crate::playlists::Playlist {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    description: row.get(2)?,
+                    artwork_path: None,
+                    track_count: 0,
+                    created_at: row.get::<_, String>(3)?,
+                    smart_rules: Some(rules.clone()),
+                    folder_id: None,
+                })
+            },
+        )?;
+        playlist.track_count = self.count_smart_playlist_tracks(rules)?;
+
+        Ok(playlist)
+    }
+
+    pub fn update_playlist_rules(&self, id: i64, rules: &SmartRules) -> Result<()> {
+        let rules_json = serde_json::to_string(rules)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+
+        self.conn.execute(
+            "UPDATE playlists SET smart_rules = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![rules_json, id],
+        )?;
+        Ok(())
+    }
+
+    /// Rules of a smart playlist, `None` for a playlist with manual membership
+    pub fn get_playlist_rules(&self, id: i64) -> Result<Option<SmartRules>> {
+        let rules: Option<String> = self
+            .conn
+            .query_row(
+                "SELECT smart_rules FROM playlists WHERE id = ?",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()?
+            .flatten();
+        rules.map(|json| parse_smart_rules(&json, 0)).transpose()
+    }
+
+    /// Evaluate smart playlist rules against the current library
+    pub fn get_smart_playlist_tracks(
+        &self,
+        rules: &SmartRules,
+    ) -> Result<Vec<crate::library::LibraryTrack>> {
+        let query = rules
+            .to_sql(Self::TRACK_COLUMNS)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+        let mut stmt = self.conn.prepare(&query.sql)?;
+
+        let track_iter =
+            stmt.query_map(rusqlite::params_from_iter(query.params), Self::read_track)?;
+
+        let mut tracks = Vec::new();
+        for track in track_iter {
+            tracks.push(track?);
+        }
+
+        Ok(tracks)
+    }
+
+    fn count_smart_playlist_tracks(&self, rules: &SmartRules) -> Result<i64> {
+        let query = rules
+            .to_sql("t.id")
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(e.into()))?;
+
+        self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM ({})", query.sql),
+            rusqlite::params_from_iter(query.params),
+            |row| row.get(0),
+        )
+    }
+
     pub fn delete_playlist(&self, id: i64) -> Result<()> {
         self.conn
             .execute("DELETE FROM playlists WHERE id = ?", params![id])?;
@@ -477,62 +1216,332 @@ impl DbHelper {
                 p.description, 
                 p.artwork_path,
                 p.created_at,
-                COUNT(pt.id) as track_count
+                COUNT(pt.id) as track_count,
+                p.smart_rules,
+                p.folder_id
             FROM playlists p
             LEFT JOIN playlist_tracks pt ON p.id = pt.playlist_id
             GROUP BY p.id
             ORDER BY p.name ASC",
         )?;
 
-        let playlist_iter = stmt.query_map([], |row| {
-            Ok(crate::playlists::Playlist {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                description: row.get(2)?,
-                artwork_path: row.get(3)?,
-                created_at: row.get(4)?,
-                track_count: row.get(5)?,
-            })
-        })?;
+        let playlist_iter = stmt.query_map([], Self::read_playlist)?;
 
         let mut playlists = Vec::new();
         for playlist in playlist_iter {
-            playlists.push(playlist?);
+            let mut playlist = playlist?;
+            if let Some(rules) = &playlist.smart_rules {
+                playlist.track_count = self.count_smart_playlist_tracks(rules)?;
+            }
+            playlists.push(playlist);
         }
 
         Ok(playlists)
     }
 
-    pub fn get_playlist_tracks(
+    pub fn get_playlist_by_id(&self, id: i64) -> Result<Option<crate::playlists::Playlist>> {
+        let playlist = self
+            .conn
+            .query_row(
+                "SELECT
+                    p.id,
+                    p.name,
+                    p.description,
+                    p.artwork_path,
+                    p.created_at,
+                    COUNT(pt.id) as track_count,
+                    p.smart_rules,
+                    p.folder_id
+                FROM playlists p
+                LEFT JOIN playlist_tracks pt ON p.id = pt.playlist_id
+                WHERE p.id = ?
+                GROUP BY p.id",
+                params![id],
+                Self::read_playlist,
+            )
+            .optional()?;
+
+        match playlist {
+            Some(mut playlist) => {
+                if let Some(rules) = &playlist.smart_rules {
+                    playlist.track_count = self.count_smart_playlist_tracks(rules)?;
+                }
+                Ok(Some(playlist))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_playlists_page(
         &self,
-        playlist_id: i64,
-    ) -> Result<Vec<crate::library::LibraryTrack>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT 
-                t.id, 
-                t.title, 
-                ar.name as artist, 
-                al.title as album, 
-                t.duration_ms, 
-                t.file_path, 
-                al.artwork_path 
+        query: &PlaylistQuery,
+    ) -> Result<Page<crate::playlists::Playlist>> {
+        let listing = query.to_sql();
+
+        let total = self.conn.query_row(
+            &format!("SELECT COUNT(*) FROM playlists p {}", listing.conditions),
+            rusqlite::params_from_iter(&listing.params),
+            |row| row.get(0),
+        )?;
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                p.id,
+                p.name,
+                p.description,
+                p.artwork_path,
+                p.created_at,
+                COUNT(pt.id) as track_count,
+                p.smart_rules,
+                p.folder_id
+            FROM playlists p
+            LEFT JOIN playlist_tracks pt ON p.id = pt.playlist_id
+            {}
+            GROUP BY p.id
+            ORDER BY {}
+            LIMIT {} OFFSET {}",
+            listing.conditions, listing.order, listing.limit, query.offset
+        ))?;
+
+        let playlist_iter = stmt.query_map(
+            rusqlite::params_from_iter(&listing.params),
+            Self::read_playlist,
+        )?;
+
+        let mut items = Vec::new();
+        for playlist in playlist_iter {
+            let mut playlist = playlist?;
+            if let Some(rules) = &playlist.smart_rules {
+                playlist.track_count = self.count_smart_playlist_tracks(rules)?;
+            }
+            items.push(playlist);
+        }
+
+        Ok(Page {
+            items,
+            total,
+            offset: query.offset,
+        })
+    }
+
+    fn read_playlist(row: &rusqlite::Row) -> Result<crate::playlists::Playlist> {
+        Ok(crate::playlists::Playlist {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            artwork_path: row.get(3)?,
+            created_at: row.get(4)?,
+            track_count: row.get(5)?,
+            smart_rules: row
+                .get::<_, Option<String>>(6)?
+                .map(|json| parse_smart_rules(&json, 6))
+                .transpose()?,
+            folder_id: row.get(7)?,
+        })
+    }
+
+    /// Put a playlist into a folder, or at the top level with `None`
+    pub fn move_playlist(&mut self, id: i64, folder_id: Option<i64>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        if let Some(folder_id) = folder_id {
+            Self::ensure_folder_exists(&tx, folder_id)?;
+        }
+        tx.execute(
+            "UPDATE playlists SET folder_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![folder_id, id],
+        )?;
+        tx.commit()
+    }
+
+    /// Fail unless a folder with this id exists
+    fn ensure_folder_exists(conn: &Connection, id: i64) -> Result<()> {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM playlist_folders WHERE id = ?)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if exists {
+            Ok(())
+        } else {
+            Err(rusqlite::Error::ToSqlConversionFailure(
+                "Folder not found".into(),
+            ))
+        }
+    }
+
+    pub fn create_playlist_folder(
+        &mut self,
+        name: String,
+        parent_id: Option<i64>,
+    ) -> Result<PlaylistFolder> {
+        let tx = self.conn.transaction()?;
+        if let Some(parent_id) = parent_id {
+            Self::ensure_folder_exists(&tx, parent_id)?;
+        }
+        let folder = tx.query_row(
+            "INSERT INTO playlist_folders (name, parent_id) VALUES (?, ?) RETURNING id, name, parent_id, created_at",
+            params![name, parent_id],
+            |row| {
+                Ok(PlaylistFolder {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    parent_id: row.get(2)?,
+                    created_at: row.get(3)?,
+                })
+            },
+        )?;
+        tx.commit()?;
+        Ok(folder)
+    }
+
+    pub fn get_playlist_folders(&self) -> Result<Vec<PlaylistFolder>> {
+        Self::read_folders(&self.conn)
+    }
+
+    fn read_folders(conn: &Connection) -> Result<Vec<PlaylistFolder>> {
+        let mut stmt = conn.prepare(
+            "SELECT id, name, parent_id, created_at FROM playlist_folders ORDER BY name ASC",
+        )?;
+
+        let folder_iter = stmt.query_map([], |row| {
+            Ok(PlaylistFolder {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                parent_id: row.get(2)?,
+                created_at: row.get(3)?,
+            })
+        })?;
+
+        let mut folders = Vec::new();
+        for folder in folder_iter {
+            folders.push(folder?);
+        }
+
+        Ok(folders)
+    }
+
+    pub fn rename_playlist_folder(&self, id: i64, name: String) -> Result<()> {
+        self.conn.execute(
+            "UPDATE playlist_folders SET name = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![name, id],
+        )?;
+        Ok(())
+    }
+
+    /// Move a folder under `parent_id`, refusing to put it inside itself
+    pub fn move_playlist_folder(&mut self, id: i64, parent_id: Option<i64>) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        if let Some(parent_id) = parent_id {
+            Self::ensure_folder_exists(&tx, parent_id)?;
+            let folders = Self::read_folders(&tx)?;
+            if playlist_folders::would_create_cycle(&folders, id, parent_id) {
+                return Err(rusqlite::Error::ToSqlConversionFailure(
+                    "A folder cannot be moved into itself".into(),
+                ));
+            }
+        }
+        tx.execute(
+            "UPDATE playlist_folders SET parent_id = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?",
+            params![parent_id, id],
+        )?;
+        tx.commit()
+    }
+
+    /// Delete a folder together with its sub-folders and their playlists
+    pub fn delete_playlist_folder_cascade(&mut self, id: i64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        let mut folder_ids = Vec::new();
+        {
+            let mut stmt = tx.prepare(
+                "WITH RECURSIVE subtree(id) AS (
+                    SELECT ?
+                    UNION
+                    SELECT f.id FROM playlist_folders f JOIN subtree s ON f.parent_id = s.id
+                )
+                SELECT id FROM subtree",
+            )?;
+            let folder_iter = stmt.query_map(params![id], |row| row.get::<_, i64>(0))?;
+            for folder_id in folder_iter {
+                folder_ids.push(folder_id?);
+            }
+        }
+
+        for folder_id in &folder_ids {
+            tx.execute(
+                "DELETE FROM playlist_tracks WHERE playlist_id IN (SELECT id FROM playlists WHERE folder_id = ?)",
+                params![folder_id],
+            )?;
+            tx.execute(
+                "DELETE FROM playlists WHERE folder_id = ?",
+                params![folder_id],
+            )?;
+        }
+        // Only after the playlists, since removing a folder would move them out
+        for folder_id in &folder_ids {
+            tx.execute(
+                "DELETE FROM playlist_folders WHERE id = ?",
+                params![folder_id],
+            )?;
+        }
+
+        tx.commit()
+    }
+
+    /// Delete a folder, moving what it holds up into its parent
+    pub fn delete_playlist_folder_reparent(&mut self, id: i64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        let parent_id: Option<i64> = tx.query_row(
+            "SELECT parent_id FROM playlist_folders WHERE id = ?",
+            params![id],
+            |row| row.get(0),
+        )?;
+        tx.execute(
+            "UPDATE playlist_folders SET parent_id = ? WHERE parent_id = ?",
+            params![parent_id, id],
+        )?;
+        tx.execute(
+            "UPDATE playlists SET folder_id = ? WHERE folder_id = ?",
+            params![parent_id, id],
+        )?;
+        tx.execute("DELETE FROM playlist_folders WHERE id = ?", params![id])?;
+        tx.commit()
+    }
+
+    pub fn get_playlist_tracks(
+        &self,
+        playlist_id: i64,
+    ) -> Result<Vec<crate::playlists::PlaylistTrack>> {
+        // Smart playlists are evaluated on every read so they follow library changes
+        if let Some(rules) = self.get_playlist_rules(playlist_id)? {
+            let tracks = self.get_smart_playlist_tracks(&rules)?;
+            return Ok(tracks
+                .into_iter()
+                .map(|track| crate::playlists::PlaylistTrack {
+                    entry_id: None,
+                    track,
+                })
+                .collect());
+        }
+
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {},
+                pt.id as entry_id
             FROM tracks t
             JOIN playlist_tracks pt ON t.id = pt.track_id
             LEFT JOIN artists ar ON t.artist_id = ar.id
             LEFT JOIN albums al ON t.album_id = al.id
             WHERE pt.playlist_id = ?
-            ORDER BY pt.position ASC",
-        )?;
+            ORDER BY pt.position ASC, pt.id ASC",
+            Self::TRACK_COLUMNS
+        ))?;
 
         let track_iter = stmt.query_map(params![playlist_id], |row| {
-            Ok(crate::library::LibraryTrack {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                artist: row.get(2)?,
-                album: row.get(3)?,
-                duration_ms: row.get(4)?,
-                file_path: row.get(5)?,
-                artwork_path: row.get(6)?,
+            let track = Self::read_track(row)?;
+            Ok(crate::playlists::PlaylistTrack {
+                entry_id: Some(row.get("entry_id")?),
+                track,
             })
         })?;
 
@@ -544,28 +1553,731 @@ impl DbHelper {
         Ok(tracks)
     }
 
-    pub fn add_track_to_playlist(&self, playlist_id: i64, track_id: i64) -> Result<()> {
-        // Get current max position
-        let count: i64 = self.conn.query_row(
+    pub fn add_track_to_playlist(&mut self, playlist_id: i64, track_id: i64) -> Result<()> {
+        self.add_tracks_to_playlist(playlist_id, &[track_id], None)
+    }
+
+    /// Insert `track_ids` in order at `index`, or append them when `index` is
+    /// `None` or past the end
+    pub fn add_tracks_to_playlist(
+        &mut self,
+        playlist_id: i64,
+        track_ids: &[i64],
+        index: Option<u32>,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        Self::insert_playlist_entries(&tx, playlist_id, track_ids, index)?;
+        tx.commit()
+    }
+
+    /// Insert entries at `index`, or at the end with `None`
+    fn insert_playlist_entries(
+        tx: &Transaction,
+        playlist_id: i64,
+        track_ids: &[i64],
+        index: Option<u32>,
+    ) -> Result<()> {
+        let count: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = ?",
+            params![playlist_id],
+            |row| row.get(0),
+        )?;
+        let index = index.map_or(count, |index| (index as i64).min(count));
+
+        // Make room for the new entries
+        tx.execute(
+            "UPDATE playlist_tracks SET position = position + ? WHERE playlist_id = ? AND position >= ?",
+            params![track_ids.len() as i64, playlist_id, index],
+        )?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?, ?, ?)",
+            )?;
+            for (offset, track_id) in track_ids.iter().enumerate() {
+                stmt.execute(params![playlist_id, track_id, index + offset as i64])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Move an entry so it ends up at `index`, shifting the entries in between
+    pub fn move_playlist_entry(
+        &mut self,
+        playlist_id: i64,
+        entry_id: i64,
+        index: u32,
+    ) -> Result<()> {
+        let tx = self.conn.transaction()?;
+
+        let from: i64 = tx.query_row(
+            "SELECT position FROM playlist_tracks WHERE playlist_id = ? AND id = ?",
+            params![playlist_id, entry_id],
+            |row| row.get(0),
+        )?;
+        let count: i64 = tx.query_row(
             "SELECT COUNT(*) FROM playlist_tracks WHERE playlist_id = ?",
             params![playlist_id],
             |row| row.get(0),
         )?;
+        let to = (index as i64).min(count - 1);
+
+        if to > from {
+            tx.execute(
+                "UPDATE playlist_tracks SET position = position - 1
+                WHERE playlist_id = ? AND position > ? AND position <= ?",
+                params![playlist_id, from, to],
+            )?;
+        } else if to < from {
+            tx.execute(
+                "UPDATE playlist_tracks SET position = position + 1
+                WHERE playlist_id = ? AND position >= ? AND position < ?",
+                params![playlist_id, to, from],
+            )?;
+        }
+        tx.execute(
+            "UPDATE playlist_tracks SET position = ? WHERE id = ?",
+            params![to, entry_id],
+        )?;
+
+        tx.commit()
+    }
+
+    pub fn remove_playlist_entries(&mut self, playlist_id: i64, entry_ids: &[i64]) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt =
+                tx.prepare("DELETE FROM playlist_tracks WHERE playlist_id = ? AND id = ?")?;
+            for entry_id in entry_ids {
+                stmt.execute(params![playlist_id, entry_id])?;
+            }
+        }
+        Self::compact_playlist_positions(&tx, playlist_id)?;
+        tx.commit()
+    }
+
+    /// Drop a track's playlist entries, so positions keep matching what
+    /// playlist reads return
+    fn remove_track_entries(conn: &Connection, track_id: i64) -> Result<()> {
+        let playlist_ids: Vec<i64> = {
+            let mut stmt = conn
+                .prepare("SELECT DISTINCT playlist_id FROM playlist_tracks WHERE track_id = ?")?;
+            let rows = stmt.query_map(params![track_id], |row| row.get(0))?;
+            rows.collect::<Result<_>>()?
+        };
+
+        conn.execute(
+            "DELETE FROM playlist_tracks WHERE track_id = ?",
+            params![track_id],
+        )?;
+        for playlist_id in playlist_ids {
+            Self::compact_playlist_positions(conn, playlist_id)?;
+        }
+        Ok(())
+    }
+
+    /// Renumber entries from 0 so removals leave no gaps
+    fn compact_playlist_positions(conn: &Connection, playlist_id: i64) -> Result<()> {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM playlist_tracks WHERE playlist_id = ? ORDER BY position ASC, id ASC",
+        )?;
+        let entry_iter = stmt.query_map(params![playlist_id], |row| row.get::<_, i64>(0))?;
+
+        let mut entry_ids = Vec::new();
+        for entry_id in entry_iter {
+            entry_ids.push(entry_id?);
+        }
+
+        let mut update = conn.prepare("UPDATE playlist_tracks SET position = ? WHERE id = ?")?;
+        for (position, entry_id) in entry_ids.iter().enumerate() {
+            update.execute(params![position as i64, entry_id])?;
+        }
+        Ok(())
+    }
+
+    /// Ranked tracks, albums, artists and playlists matching `query`, at most
+    /// `limit` of each
+    pub fn search_library(&self, query: &str, limit: u32) -> Result<SearchResults> {
+        let Some(terms) = search::match_query(query) else {
+            return Ok(SearchResults::default());
+        };
+
+        // Title counts most, then artist and album, then the rest
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT
+                {},
+                highlight(tracks_fts, 0, ?1, ?2) as title_highlight,
+                snippet(tracks_fts, -1, ?1, ?2, '…', 12) as snippet
+            FROM tracks_fts
+            JOIN tracks t ON t.id = tracks_fts.rowid
+            LEFT JOIN artists ar ON t.artist_id = ar.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            WHERE tracks_fts MATCH ?3
+            ORDER BY bm25(tracks_fts, 10.0, 5.0, 4.0, 2.0, 1.0)
+            LIMIT ?4",
+            Self::TRACK_COLUMNS
+        ))?;
+        let track_iter = stmt.query_map(
+            params![HIGHLIGHT_START, HIGHLIGHT_END, terms, limit],
+            |row| {
+                Ok(search::TrackHit {
+                    track: Self::read_track(row)?,
+                    title_highlight: row.get("title_highlight")?,
+                    snippet: row.get("snippet")?,
+                })
+            },
+        )?;
+        let mut tracks = Vec::new();
+        for track in track_iter {
+            tracks.push(track?);
+        }
+
+        // An album ranks by its best matching track. The ranking functions
+        // only work on plain rows, so the matches are gathered before grouping.
+        let mut stmt = self.conn.prepare(
+            "WITH matches AS MATERIALIZED (
+                SELECT
+                    rowid as track_id,
+                    highlight(tracks_fts, 2, ?1, ?2) as highlight,
+                    bm25(tracks_fts, 0.0, 0.0, 4.0, 2.0, 0.0) as score
+                FROM tracks_fts
+                WHERE tracks_fts MATCH ?3
+            )
+            SELECT
+                al.id,
+                al.title,
+                ar.name,
+                al.artwork_path,
+                m.highlight,
+                MIN(m.score) as score
+            FROM matches m
+            JOIN tracks t ON t.id = m.track_id
+            JOIN albums al ON t.album_id = al.id
+            LEFT JOIN artists ar ON al.artist_id = ar.id
+            GROUP BY al.id
+            ORDER BY score
+            LIMIT ?4",
+        )?;
+        let album_iter = stmt.query_map(
+            params![
+                HIGHLIGHT_START,
+                HIGHLIGHT_END,
+                format!("{{album album_artist}} : ({})", terms),
+                limit
+            ],
+            |row| {
+                Ok(search::AlbumHit {
+                    id: row.get(0)?,
+                    title: row.get(1)?,
+                    artist_name: row.get(2)?,
+                    artwork_path: row.get(3)?,
+                    highlight: row.get(4)?,
+                })
+            },
+        )?;
+        let mut albums = Vec::new();
+        for album in album_iter {
+            albums.push(album?);
+        }
+
+        // Artists without any tracks or albums left are not worth showing
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                ar.id,
+                ar.name,
+                (SELECT COUNT(*) FROM (
+                    SELECT id FROM tracks WHERE artist_id = ar.id
+                    UNION
+                    SELECT track_id FROM track_artists WHERE artist_id = ar.id
+                )),
+                highlight(artists_fts, 0, ?1, ?2)
+            FROM artists_fts
+            JOIN artists ar ON ar.id = artists_fts.rowid
+            WHERE artists_fts MATCH ?3
+            AND (
+                EXISTS (SELECT 1 FROM tracks WHERE artist_id = ar.id)
+                OR EXISTS (SELECT 1 FROM track_artists WHERE artist_id = ar.id)
+                OR EXISTS (SELECT 1 FROM albums WHERE artist_id = ar.id)
+            )
+            ORDER BY bm25(artists_fts), 3 DESC
+            LIMIT ?4",
+        )?;
+        let artist_iter = stmt.query_map(
+            params![HIGHLIGHT_START, HIGHLIGHT_END, terms, limit],
+            |row| {
+                Ok(search::ArtistHit {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    track_count: row.get(2)?,
+                    highlight: row.get(3)?,
+                })
+            },
+        )?;
+        let mut artists = Vec::new();
+        for artist in artist_iter {
+            artists.push(artist?);
+        }
+
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                p.id,
+                p.name,
+                p.artwork_path,
+                highlight(playlists_fts, 0, ?1, ?2)
+            FROM playlists_fts
+            JOIN playlists p ON p.id = playlists_fts.rowid
+            WHERE playlists_fts MATCH ?3
+            ORDER BY bm25(playlists_fts, 5.0, 1.0)
+            LIMIT ?4",
+        )?;
+        let playlist_iter = stmt.query_map(
+            params![HIGHLIGHT_START, HIGHLIGHT_END, terms, limit],
+            |row| {
+                Ok(search::PlaylistHit {
+                    id: row.get(0)?,
+                    name: row.get(1)?,
+                    artwork_path: row.get(2)?,
+                    highlight: row.get(3)?,
+                })
+            },
+        )?;
+        let mut playlists = Vec::new();
+        for playlist in playlist_iter {
+            playlists.push(playlist?);
+        }
+
+        Ok(SearchResults {
+            tracks,
+            albums,
+            artists,
+            playlists,
+        })
+    }
+
+    pub fn get_eq_presets(&self) -> Result<Vec<EqPreset>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT name, preamp_db, bands FROM eq_presets ORDER BY name ASC")?;
+
+        let preset_iter = stmt.query_map([], |row| {
+            let bands: String = row.get(2)?;
+            Ok(EqPreset {
+                name: row.get(0)?,
+                preamp_db: row.get(1)?,
+                bands: serde_json::from_str(&bands).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(2, Type::Text, Box::new(e))
+                })?,
+            })
+        })?;
+
+        let mut presets = Vec::new();
+        for preset in preset_iter {
+            presets.push(preset?);
+        }
+
+        Ok(presets)
+    }
+
+    /// Insert a preset, replacing any existing preset with the same name
+    pub fn save_eq_preset(&self, preset: &EqPreset) -> Result<()> {
+        let bands = serde_json::to_string(&preset.bands)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
 
         self.conn.execute(
-            "INSERT INTO playlist_tracks (playlist_id, track_id, position) VALUES (?, ?, ?)",
-            params![playlist_id, track_id, count],
+            "INSERT INTO eq_presets (name, preamp_db, bands) VALUES (?, ?, ?)
+            ON CONFLICT(name) DO UPDATE SET
+                preamp_db = excluded.preamp_db,
+                bands = excluded.bands,
+                updated_at = CURRENT_TIMESTAMP",
+            params![preset.name, preset.preamp_db, bands],
         )?;
+        Ok(())
+    }
 
+    pub fn delete_eq_preset(&self, name: &str) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM eq_presets WHERE name = ?", params![name])?;
         Ok(())
     }
 
-    pub fn remove_track_from_playlist(&self, playlist_id: i64, track_id: i64) -> Result<()> {
+    pub fn get_track_loops(&self, track_id: i64) -> Result<Vec<SavedLoop>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, track_id, name, start_ms, end_ms FROM track_loops
+            WHERE track_id = ? ORDER BY start_ms ASC",
+        )?;
+
+        let loop_iter = stmt.query_map(params![track_id], |row| {
+            Ok(SavedLoop {
+                id: row.get(0)?,
+                track_id: row.get(1)?,
+                name: row.get(2)?,
+                start_ms: row.get(3)?,
+                end_ms: row.get(4)?,
+            })
+        })?;
+
+        let mut loops = Vec::new();
+        for saved in loop_iter {
+            loops.push(saved?);
+        }
+
+        Ok(loops)
+    }
+
+    pub fn save_track_loop(
+        &self,
+        track_id: i64,
+        name: Option<&str>,
+        start_ms: u64,
+        end_ms: u64,
+    ) -> Result<SavedLoop> {
         self.conn.execute(
+            "INSERT INTO track_loops (track_id, name, start_ms, end_ms) VALUES (?, ?, ?, ?)",
+            params![track_id, name, start_ms, end_ms],
+        )?;
+
+        Ok(SavedLoop {
+            id: self.conn.last_insert_rowid(),
+            track_id,
+            name: name.map(str::to_string),
+            start_ms,
+            end_ms,
+        })
+    }
+
+    pub fn delete_track_loop(&self, id: i64) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM track_loops WHERE id = ?", params![id])?;
+        Ok(())
+    }
+
+    pub fn get_channel_mix(&self, device_name: &str) -> Result<Option<ChannelMix>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT balance, mono, swap_channels FROM output_device_settings WHERE device_name = ?",
+        )?;
+        let mut rows = stmt.query(params![device_name])?;
+
+        match rows.next()? {
+            Some(row) => Ok(Some(ChannelMix::new(row.get(0)?, row.get(1)?, row.get(2)?))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_channel_mix(&self, device_name: &str, mix: &ChannelMix) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO output_device_settings (device_name, balance, mono, swap_channels)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(device_name) DO UPDATE SET
+                balance = excluded.balance,
+                mono = excluded.mono,
+                swap_channels = excluded.swap_channels,
+                updated_at = CURRENT_TIMESTAMP",
+            params![device_name, mix.balance, mix.mono, mix.swap],
+        )?;
+        Ok(())
+    }
+
+    pub fn insert_play(&self, play: &PlayRecord) -> Result<()> {
+        self.conn.execute(
+            "INSERT INTO play_history (track_id, play_duration_ms, completed) VALUES (?, ?, ?)",
+            params![play.track_id, play.listened_ms, play.completed],
+        )?;
+        Ok(())
+    }
+
+    /// Most recent plays first
+    pub fn get_play_history(&self, limit: u32) -> Result<Vec<crate::library::HistoryEntry>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                h.id,
+                h.track_id,
+                t.title,
+                ar.name as artist,
+                al.title as album,
+                al.artwork_path,
+                h.played_at,
+                COALESCE(h.play_duration_ms, 0),
+                h.completed
+            FROM play_history h
+            JOIN tracks t ON h.track_id = t.id
+            LEFT JOIN artists ar ON t.artist_id = ar.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            ORDER BY h.played_at DESC, h.id DESC
+            LIMIT ?",
+        )?;
+
+        let entry_iter = stmt.query_map(params![limit], |row| {
+            Ok(crate::library::HistoryEntry {
+                id: row.get(0)?,
+                track_id: row.get(1)?,
+                title: row.get(2)?,
+                artist: row.get(3)?,
+                album: row.get(4)?,
+                artwork_path: row.get(5)?,
+                played_at: row.get(6)?,
+                play_duration_ms: row.get(7)?,
+                completed: row.get(8)?,
+            })
+        })?;
+
+        let mut entries = Vec::new();
+        for entry in entry_iter {
+            entries.push(entry?);
+        }
+
+        Ok(entries)
+    }
+
+    /// Play counts and last play of every track that has been played
+    pub fn get_track_play_stats(&self) -> Result<Vec<crate::library::TrackPlayStats>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT
+                track_id,
+                SUM(CASE WHEN completed THEN 1 ELSE 0 END) as play_count,
+                SUM(CASE WHEN completed THEN 0 ELSE 1 END) as skip_count,
+                MAX(played_at) as last_played_at
+            FROM play_history
+            GROUP BY track_id",
+        )?;
+
+        let stats_iter = stmt.query_map([], |row| {
+            Ok(crate::library::TrackPlayStats {
+                track_id: row.get(0)?,
+                play_count: row.get(1)?,
+                skip_count: row.get(2)?,
+                last_played_at: row.get(3)?,
+            })
+        })?;
+
+        let mut stats = Vec::new();
+        for stat in stats_iter {
+            stats.push(stat?);
+        }
+
+        Ok(stats)
+    }
+
+    /// Remove every entry of `track_id` from the playlist
+    pub fn remove_track_from_playlist(&mut self, playlist_id: i64, track_id: i64) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
             "DELETE FROM playlist_tracks WHERE playlist_id = ? AND track_id = ?",
             params![playlist_id, track_id],
         )?;
-        // Optional: Reorder positions? Not strictly necessary for basic functionality.
-        Ok(())
+        Self::compact_playlist_positions(&tx, playlist_id)?;
+        tx.commit()
+    }
+}
+
+fn parse_smart_rules(json: &str, column: usize) -> Result<SmartRules> {
+    serde_json::from_str(json)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(column, Type::Text, Box::new(e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(db: &DbHelper, playlist_id: i64) -> Vec<(i64, i64)> {
+        db.get_playlist_tracks(playlist_id)
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.entry_id.unwrap(), entry.track.id))
+            .collect()
+    }
+
+    #[test]
+    fn test_playlist_entries_keep_order_and_duplicates() {
+        let mut db = DbHelper::new(":memory:").unwrap();
+        db.get_conn_mut()
+            .execute_batch(
+                "INSERT INTO tracks (id, title, file_path) VALUES
+                    (1, 'One', '/1.flac'), (2, 'Two', '/2.flac'), (3, 'Three', '/3.flac');
+                INSERT INTO playlists (id, name) VALUES (1, 'Mix');",
+            )
+            .unwrap();
+
+        db.add_tracks_to_playlist(1, &[1, 2, 1], None).unwrap();
+        db.add_tracks_to_playlist(1, &[3], Some(1)).unwrap();
+        let ids: Vec<i64> = entries(&db, 1).iter().map(|e| e.1).collect();
+        assert_eq!(ids, vec![1, 3, 2, 1]);
+
+        // Move the first entry to the end, then drop the copy of track 1 now in front
+        let first = entries(&db, 1)[0].0;
+        db.move_playlist_entry(1, first, 10).unwrap();
+        let moved = entries(&db, 1);
+        assert_eq!(
+            moved.iter().map(|e| e.1).collect::<Vec<_>>(),
+            vec![3, 2, 1, 1]
+        );
+        assert_eq!(moved[3].0, first);
+
+        db.remove_playlist_entries(1, &[moved[2].0]).unwrap();
+        db.add_track_to_playlist(1, 2).unwrap();
+        let ids: Vec<i64> = entries(&db, 1).iter().map(|e| e.1).collect();
+        assert_eq!(ids, vec![3, 2, 1, 2]);
+
+        // Deleting a track takes its entries along and closes the gap
+        db.delete_track(3).unwrap();
+        let ids: Vec<i64> = entries(&db, 1).iter().map(|e| e.1).collect();
+        assert_eq!(ids, vec![2, 1, 2]);
+
+        let positions: Vec<i64> = db
+            .get_conn_mut()
+            .prepare("SELECT position FROM playlist_tracks ORDER BY position")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(|p| p.unwrap())
+            .collect();
+        assert_eq!(positions, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn test_imported_playlists_get_unused_names() {
+        let mut db = DbHelper::new(":memory:").unwrap();
+        db.get_conn_mut()
+            .execute_batch(
+                "INSERT INTO tracks (id, title, file_path) VALUES (1, 'One', '/1.flac');
+                INSERT INTO playlists (name) VALUES ('Mix'), ('Mix (2)');",
+            )
+            .unwrap();
+
+        let imported = db.create_playlist_with_tracks("Mix".into(), &[1]).unwrap();
+        assert_eq!(imported.name, "Mix (3)");
+        assert_eq!(imported.track_count, 1);
+        let fresh = db.create_playlist_with_tracks("New".into(), &[]).unwrap();
+        assert_eq!(fresh.name, "New");
+    }
+
+    #[test]
+    fn test_deleting_folders_cascades_or_reparents() {
+        let mut db = DbHelper::new(":memory:").unwrap();
+        let root = db.create_playlist_folder("Root".into(), None).unwrap();
+        let mid = db
+            .create_playlist_folder("Mid".into(), Some(root.id))
+            .unwrap();
+        let leaf = db
+            .create_playlist_folder("Leaf".into(), Some(mid.id))
+            .unwrap();
+        let in_mid = db.create_playlist("In mid".into(), None).unwrap();
+        let in_leaf = db.create_playlist("In leaf".into(), None).unwrap();
+        db.move_playlist(in_mid.id, Some(mid.id)).unwrap();
+        db.move_playlist(in_leaf.id, Some(leaf.id)).unwrap();
+
+        // Folders cannot go inside themselves, and targets must exist
+        assert!(db.move_playlist_folder(root.id, Some(leaf.id)).is_err());
+        assert!(db.move_playlist(in_mid.id, Some(999)).is_err());
+        assert!(db.create_playlist_folder("Lost".into(), Some(999)).is_err());
+        assert_eq!(db.get_playlist_folders().unwrap().len(), 3);
+        assert!(db
+            .get_playlists()
+            .unwrap()
+            .iter()
+            .any(|p| p.id == in_mid.id && p.folder_id == Some(mid.id)));
+
+        // Removing Mid hands its contents to Root
+        db.delete_playlist_folder_reparent(mid.id).unwrap();
+        let folders = db.get_playlist_folders().unwrap();
+        assert_eq!(folders.len(), 2);
+        assert!(folders
+            .iter()
+            .any(|f| f.id == leaf.id && f.parent_id == Some(root.id)));
+        let playlists = db.get_playlists().unwrap();
+        assert!(playlists
+            .iter()
+            .any(|p| p.id == in_mid.id && p.folder_id == Some(root.id)));
+
+        // Removing Root takes everything below it along
+        db.delete_playlist_folder_cascade(root.id).unwrap();
+        assert!(db.get_playlist_folders().unwrap().is_empty());
+        assert!(db.get_playlists().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_artists_include_credits_and_guest_albums() {
+        let db = DbHelper::new(":memory:").unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO artists (id, name) VALUES (1, 'Alice'), (2, 'Bob'), (3, 'Nobody');
+                INSERT INTO albums (id, title, artist_id, year) VALUES
+                    (1, 'First', 1, 2001), (2, 'Bob Sings', 2, 2005);
+                INSERT INTO tracks (id, title, artist_id, album_id, file_path, duration_ms) VALUES
+                    (1, 'A1', 1, 1, '/1.flac', 1000),
+                    (2, 'A2', 1, 1, '/2.flac', 1000),
+                    (3, 'B1', 2, 2, '/3.flac', 1000),
+                    (4, 'Duet', 2, 2, '/4.flac', 1000);
+                INSERT INTO track_artists (track_id, artist_id) VALUES (4, 1), (4, 2);",
+            )
+            .unwrap();
+
+        // Artists without tracks or albums are left out
+        let artists = db.get_all_artists(ArtistSort::Name, false, None).unwrap();
+        let names: Vec<&str> = artists.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Alice", "Bob"]);
+        assert_eq!((artists[0].track_count, artists[0].album_count), (3, 2));
+
+        let by_tracks = db
+            .get_all_artists(ArtistSort::TrackCount, true, Some("o"))
+            .unwrap();
+        assert_eq!(by_tracks.len(), 1);
+        assert_eq!(by_tracks[0].name, "Bob");
+        assert!(db
+            .get_all_artists(ArtistSort::Name, false, Some("%"))
+            .unwrap()
+            .is_empty());
+
+        let albums = db.get_artist_albums(1).unwrap();
+        assert_eq!(albums.albums[0].title, "First");
+        assert_eq!(albums.appears_on[0].title, "Bob Sings");
+        assert!(db.get_artist_albums(2).unwrap().appears_on.is_empty());
+
+        let ids: Vec<i64> = db
+            .get_artist_tracks(1)
+            .unwrap()
+            .iter()
+            .map(|t| t.id)
+            .collect();
+        assert_eq!(ids, vec![1, 2, 4]);
+        assert_eq!(db.get_artist_by_id(2).unwrap().unwrap().track_count, 2);
+        // Looking up one artist still counts the albums they guest on
+        let alice = db.get_artist_by_id(1).unwrap().unwrap();
+        assert_eq!((alice.track_count, alice.album_count), (3, 2));
+        assert!(db.get_artist_by_id(3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_album_tracks_follow_discs_with_details() {
+        let db = DbHelper::new(":memory:").unwrap();
+        db.conn
+            .execute_batch(
+                "INSERT INTO artists (id, name) VALUES (1, 'Duo'), (2, 'Guest');
+                INSERT INTO albums (id, title, artist_id) VALUES (1, 'Double', 1);
+                INSERT INTO tracks
+                    (id, title, artist_id, album_id, file_path, disc_number, track_number,
+                    genre, file_format, sample_rate, channels) VALUES
+                    (1, 'Two One', 1, 1, '/1.flac', 2, 1, 'Rock', 'flac', 44100, 2),
+                    (2, 'One Two', 1, 1, '/2.flac', 1, 2, NULL, 'flac', 44100, 2),
+                    (3, 'Bonus', 1, 1, '/3.flac', 1, NULL, NULL, 'flac', 44100, 2),
+                    (4, 'One One', 1, 1, '/4.flac', NULL, 1, NULL, 'flac', 44100, 2);
+                INSERT INTO track_artists (track_id, artist_id) VALUES (1, 2), (1, 1);
+                INSERT INTO play_history (track_id, completed) VALUES (1, 1), (1, 1), (1, 0);",
+            )
+            .unwrap();
+
+        let tracks = db.get_album_tracks(1).unwrap();
+        let titles: Vec<&str> = tracks.iter().map(|t| t.title.as_str()).collect();
+        assert_eq!(titles, vec!["One One", "One Two", "Bonus", "Two One"]);
+        assert_eq!(db.get_album_by_id(1).unwrap().unwrap().disc_count, 2);
+
+        let last = &tracks[3];
+        assert_eq!(last.tags.artists, vec!["Guest", "Duo"]);
+        assert_eq!(last.tags.disc_number, Some(2));
+        assert_eq!(last.tags.genre.as_deref(), Some("Rock"));
+        assert_eq!(last.format.sample_rate, Some(44100));
+        assert_eq!((last.plays.play_count, last.plays.skip_count), (2, 1));
+        assert!(last.plays.last_played_at.is_some());
+        // Without credits the track artist stands in
+        assert_eq!(tracks[0].tags.artists, vec!["Duo"]);
+        assert_eq!(tracks[0].plays.play_count, 0);
     }
 }
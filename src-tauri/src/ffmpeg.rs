@@ -3,8 +3,53 @@
 //! This module provides utilities to spawn FFmpeg as a child process
 //! and read raw PCM audio data from its stdout pipe.
 
-use std::io::{self, BufReader, Read};
+use std::io::{self, BufRead, BufReader, Read};
 use std::process::{Child, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// How much of FFmpeg's stderr is kept for error reports
+const STDERR_LIMIT: usize = 4096;
+
+/// Formats whose demuxers estimate timestamps (e.g. VBR MP3 without a TOC,
+/// raw ADTS AAC). Seeking them with `-ss` before `-i` can land off-target, so
+/// they are input-seeked a little early and trimmed on their real timestamps.
+const ACCURATE_SEEK_EXTENSIONS: &[&str] = &["mp3", "aac"];
+
+/// How far ahead of the target an inaccurate format is input-seeked, so the
+/// demuxer lands before the target and the trim has something to cut
+const ACCURATE_SEEK_PREROLL_MS: u64 = 5000;
+
+/// How a seek is carried out: a fast input seek plus an optional exact trim
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SeekPlan {
+    /// Position passed to `-ss` before `-i`
+    input_ms: Option<u64>,
+    /// Media time cut with `atrim`; timestamps are kept with `-copyts` so the
+    /// trim counts from the file's start, not from where the demuxer landed
+    trim_ms: Option<u64>,
+}
+
+impl SeekPlan {
+    fn new(path: &str, seek_ms: Option<u64>) -> Self {
+        let Some(ms) = seek_ms else {
+            return Self {
+                input_ms: None,
+                trim_ms: None,
+            };
+        };
+        if !needs_accurate_seek(path) {
+            return Self {
+                input_ms: Some(ms),
+                trim_ms: None,
+            };
+        }
+        let input_ms = ms.saturating_sub(ACCURATE_SEEK_PREROLL_MS);
+        Self {
+            input_ms: (input_ms > 0).then_some(input_ms),
+            trim_ms: Some(ms),
+        }
+    }
+}
 
 /// Metadata about an audio file, obtained via ffprobe
 #[derive(Debug, Clone)]
@@ -18,6 +63,7 @@ pub struct AudioMetadata {
 pub struct FFmpegProcess {
     child: Child,
     stdout: BufReader<ChildStdout>,
+    stderr: Arc<Mutex<String>>,
 }
 
 impl FFmpegProcess {
@@ -82,28 +128,20 @@ impl FFmpegProcess {
         name.to_string()
     }
 
-    /// Spawn FFmpeg to decode audio file to raw PCM f32le
-    ///
-    /// # Arguments
-    /// * `path` - Path to the audio file
-    /// * `sample_rate` - Target sample rate (e.g., 44100)
-    /// * `channels` - Target channel count (e.g., 2 for stereo)
-    pub fn spawn(path: &str, sample_rate: u32, channels: u16) -> io::Result<Self> {
-        Self::spawn_at(path, sample_rate, channels, None)
-    }
-
-    /// Spawn FFmpeg with optional seek position
+    /// Spawn FFmpeg to decode audio file to raw PCM f32le, optionally seeking
     ///
     /// # Arguments
     /// * `path` - Path to the audio file
     /// * `sample_rate` - Target sample rate
     /// * `channels` - Target channel count
     /// * `seek_ms` - Optional seek position in milliseconds
+    /// * `filter` - Optional FFmpeg audio filter graph (`-af`)
     pub fn spawn_at(
         path: &str,
         sample_rate: u32,
         channels: u16,
         seek_ms: Option<u64>,
+        filter: Option<&str>,
     ) -> io::Result<Self> {
         let ffmpeg = Self::ffmpeg_path();
 
@@ -112,15 +150,36 @@ impl FFmpegProcess {
         // Hide banner and reduce logging
         cmd.args(["-hide_banner", "-loglevel", "error"]);
 
-        // Add seek position BEFORE input (faster seeking with -ss before -i)
-        if let Some(ms) = seek_ms {
+        let seek = SeekPlan::new(path, seek_ms);
+
+        // Seek BEFORE input (faster); inaccurate formats land a little early
+        if let Some(ms) = seek.input_ms {
             let seconds = ms as f64 / 1000.0;
             cmd.args(["-ss", &format!("{:.3}", seconds)]);
         }
+        if seek.trim_ms.is_some() {
+            cmd.arg("-copyts");
+        }
 
         // Input file
         cmd.args(["-i", path]);
 
+        let mut filters = Vec::new();
+        if let Some(ms) = seek.trim_ms {
+            // Trim ahead of any tempo filters so the offset stays in media
+            // time; `-ss` after `-i` would count in the sped-up output time
+            filters.push(format!(
+                "atrim=start={:.3},asetpts=PTS-STARTPTS",
+                ms as f64 / 1000.0
+            ));
+        }
+        if let Some(filter) = filter {
+            filters.push(filter.to_string());
+        }
+        if !filters.is_empty() {
+            cmd.args(["-af", &filters.join(",")]);
+        }
+
         // Output format: raw PCM f32le (little-endian float)
         cmd.args([
             "-f",
@@ -128,15 +187,15 @@ impl FFmpegProcess {
             "-acodec",
             "pcm_f32le", // PCM codec
             "-ar",
-            &sample_rate.to_string(),
+            &sample_rate.to_string(), // No resampling happens when this equals the source rate
             "-ac",
             &channels.to_string(),
             "-", // Output to stdout
         ]);
 
-        // Configure pipes
+        // Configure pipes; stderr is drained on a thread so it can never block
         cmd.stdout(Stdio::piped());
-        cmd.stderr(Stdio::null()); // Suppress errors to avoid blocking
+        cmd.stderr(Stdio::piped());
 
         // On Windows, hide the console window
         #[cfg(target_os = "windows")]
@@ -152,16 +211,32 @@ impl FFmpegProcess {
             .take()
             .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "Failed to capture stdout"))?;
 
+        let stderr = Arc::new(Mutex::new(String::new()));
+        if let Some(pipe) = child.stderr.take() {
+            let stderr = stderr.clone();
+            std::thread::spawn(move || {
+                for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+                    let mut log = stderr.lock().unwrap();
+                    if log.len() < STDERR_LIMIT {
+                        log.push_str(line.trim());
+                        log.push('\n');
+                    }
+                }
+            });
+        }
+
         Ok(Self {
             child,
             stdout: BufReader::with_capacity(65536, stdout), // 64KB buffer
+            stderr,
         })
     }
 
     /// Read samples into buffer
     ///
     /// Returns the number of samples actually read.
-    /// Returns 0 when EOF is reached (track finished).
+    /// Returns 0 when EOF is reached (track finished), or an error carrying
+    /// FFmpeg's stderr if it exited unsuccessfully.
     pub fn read_samples(&mut self, buffer: &mut [f32]) -> io::Result<usize> {
         // Each f32 sample is 4 bytes
         let byte_buffer_size = buffer.len() * 4;
@@ -170,6 +245,15 @@ impl FFmpegProcess {
         let bytes_read = self.stdout.read(&mut byte_buffer)?;
 
         if bytes_read == 0 {
+            let status = self.child.wait()?;
+            if !status.success() {
+                let stderr = self.stderr.lock().unwrap();
+                return Err(io::Error::other(format!(
+                    "FFmpeg exited with {}: {}",
+                    status,
+                    stderr.trim()
+                )));
+            }
             return Ok(0); // EOF
         }
 
@@ -206,6 +290,14 @@ impl Drop for FFmpegProcess {
     }
 }
 
+fn needs_accurate_seek(path: &str) -> bool {
+    std::path::Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ACCURATE_SEEK_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
 /// Probe an audio file for metadata using ffprobe
 ///
 /// Returns duration, sample rate, and channel count
@@ -306,4 +398,83 @@ mod tests {
         assert_eq!(metadata.channels, 2);
         assert_eq!(metadata.duration_ms, 180500);
     }
+
+    /// Encode `seconds` of a 440 Hz tone that starts at `tone_from` as a VBR
+    /// MP3 without a TOC, the case where the demuxer estimates seek positions
+    fn write_mp3(name: &str, seconds: u32, tone_from: u32) -> Option<std::path::PathBuf> {
+        let path = std::env::temp_dir().join(format!("vibemusic-{}-{}", std::process::id(), name));
+        let source = format!(
+            "aevalsrc='if(gte(t,{}),0.5*sin(2*PI*440*t),0)':s=44100:d={}",
+            tone_from, seconds
+        );
+        let status = Command::new(FFmpegProcess::ffmpeg_path())
+            .args(["-hide_banner", "-loglevel", "error", "-y", "-f", "lavfi"])
+            .args(["-i", &source, "-c:a", "libmp3lame", "-q:a", "4"])
+            .args(["-write_xing", "0"])
+            .arg(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => Some(path),
+            _ => {
+                eprintln!("FFmpeg with libmp3lame is not available, skipping");
+                None
+            }
+        }
+    }
+
+    /// Milliseconds of silence before the tone in a decode starting at `seek_ms`
+    fn lead_in_ms(path: &std::path::Path, seek_ms: u64) -> u64 {
+        let mut process =
+            FFmpegProcess::spawn_at(path.to_str().unwrap(), 44100, 1, Some(seek_ms), None).unwrap();
+        let mut buffer = vec![0.0f32; 4096];
+        let mut frames = 0u64;
+        loop {
+            let read = process.read_samples(&mut buffer).unwrap();
+            assert!(read > 0, "no tone after seeking to {}ms", seek_ms);
+            if let Some(pos) = buffer[..read].iter().position(|s| s.abs() > 0.05) {
+                return (frames + pos as u64) * 1000 / 44100;
+            }
+            frames += read as u64;
+        }
+    }
+
+    #[test]
+    fn test_seek_into_inaccurate_mp3_starts_at_target() {
+        // The tone starts at 50 s, so the silence left after seeking shows
+        // where playback really started
+        let Some(path) = write_mp3("seek.mp3", 60, 50) else {
+            return;
+        };
+        for (seek_ms, expected) in [(48_000, 2000), (49_700, 300), (2_000, 48_000)] {
+            let lead_in = lead_in_ms(&path, seek_ms);
+            assert!(
+                lead_in.abs_diff(expected) < 60,
+                "seek to {}ms started {}ms before the tone",
+                seek_ms,
+                lead_in
+            );
+        }
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_seek_plan_near_start_and_indexed_formats() {
+        let plan = SeekPlan::new("mix.mp3", Some(3_600_000));
+        assert_eq!(plan.input_ms, Some(3_600_000 - ACCURATE_SEEK_PREROLL_MS));
+        assert_eq!(plan.trim_ms, Some(3_600_000));
+
+        let plan = SeekPlan::new("song.MP3", Some(2_000));
+        assert_eq!(plan.input_ms, None);
+        assert_eq!(plan.trim_ms, Some(2_000));
+
+        let plan = SeekPlan::new("song.flac", Some(90_000));
+        assert_eq!(plan.input_ms, Some(90_000));
+        assert_eq!(plan.trim_ms, None);
+
+        let plan = SeekPlan::new("song.mp3", None);
+        assert_eq!(plan.input_ms, None);
+        assert_eq!(plan.trim_ms, None);
+    }
 }
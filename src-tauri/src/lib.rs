@@ -1,15 +1,23 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 
 mod artwork;
-mod audio;
+pub mod audio;
 mod database;
 mod error;
 mod ffmpeg;
 mod library;
+mod loudness;
+mod paging;
+mod playlist_folders;
+mod playlist_io;
 mod playlists;
 mod profile;
 mod scanner;
+mod search;
+mod silence;
+mod smart_playlist;
 mod updater;
+mod waveform;
 
 use audio::{AudioEngine, AudioState};
 use profile::ProfileState;
@@ -42,6 +50,60 @@ pub fn run() {
                             sql: include_str!("../migrations/002_add_playlist_artwork.sql"),
                             kind: tauri_plugin_sql::MigrationKind::Up,
                         },
+                        tauri_plugin_sql::Migration {
+                            version: 3,
+                            description: "add_loudness",
+                            sql: include_str!("../migrations/003_add_loudness.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 4,
+                            description: "add_eq_presets",
+                            sql: include_str!("../migrations/004_add_eq_presets.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 5,
+                            description: "add_silence",
+                            sql: include_str!("../migrations/005_add_silence.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 6,
+                            description: "add_track_loops",
+                            sql: include_str!("../migrations/006_add_track_loops.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 7,
+                            description: "add_device_settings",
+                            sql: include_str!("../migrations/007_add_device_settings.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 8,
+                            description: "add_smart_playlists",
+                            sql: include_str!("../migrations/008_add_smart_playlists.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 9,
+                            description: "playlist_entries",
+                            sql: include_str!("../migrations/009_playlist_entries.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 10,
+                            description: "add_playlist_folders",
+                            sql: include_str!("../migrations/010_add_playlist_folders.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
+                        tauri_plugin_sql::Migration {
+                            version: 11,
+                            description: "add_search_index",
+                            sql: include_str!("../migrations/011_add_search_index.sql"),
+                            kind: tauri_plugin_sql::MigrationKind::Up,
+                        },
                     ],
                 )
                 .build(),
@@ -80,9 +142,6 @@ pub fn run() {
             // Initialize media events
             engine.init_media_events(app.handle().clone());
 
-            // Start background progress tracking
-            audio::start_progress_tracking(app.handle().clone(), engine);
-
             // System Tray Setup
             let quit_i = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
             let show_i = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
@@ -125,16 +184,41 @@ pub fn run() {
             greet,
             library::get_all_tracks,
             library::get_all_albums,
+            library::get_tracks_page,
+            library::get_albums_page,
             library::get_album_by_id,
             library::get_album_tracks,
+            library::get_all_artists,
+            library::get_artist_by_id,
+            library::get_artist_albums,
+            library::get_artist_tracks,
             library::delete_track,
+            library::get_play_history,
+            library::get_track_play_stats,
+            library::search_library,
             scanner::get_file_metadata,
             scanner::scan_folder,
             scanner::scan_music_library,
             scanner::check_files_exist,
             scanner::prune_library,
+            scanner::analyze_loudness,
+            scanner::analyze_silence,
+            scanner::get_waveform,
             // Audio commands
             audio::audio_play,
+            audio::audio_enqueue_next,
+            audio::audio_clear_next,
+            audio::audio_queue_set,
+            audio::audio_queue_insert,
+            audio::audio_queue_move,
+            audio::audio_queue_remove,
+            audio::audio_queue_clear,
+            audio::audio_queue_jump,
+            audio::audio_queue_get,
+            audio::audio_set_shuffle,
+            audio::audio_set_repeat,
+            audio::audio_next,
+            audio::audio_previous,
             audio::audio_pause,
             audio::audio_resume,
             audio::audio_stop,
@@ -143,15 +227,52 @@ pub fn run() {
             audio::audio_get_state,
             audio::audio_get_devices,
             audio::audio_set_device,
+            audio::audio_set_channel_mix,
+            audio::audio_get_channel_mix,
+            audio::audio_restore_channel_mix,
             audio::audio_set_crossfade,
+            audio::audio_set_normalization,
+            audio::audio_set_speed,
+            audio::audio_set_match_source_rate,
+            audio::audio_set_decoder_backend,
+            audio::audio_set_skip_silence,
+            audio::audio_set_history_threshold,
+            audio::audio_subscribe_visualization,
+            audio::audio_unsubscribe_visualization,
+            audio::audio_start_sleep_timer,
+            audio::audio_cancel_sleep_timer,
+            audio::audio_set_equalizer,
+            audio::audio_get_equalizer,
+            audio::audio_get_eq_presets,
+            audio::audio_save_eq_preset,
+            audio::audio_delete_eq_preset,
+            audio::audio_set_loop_point,
+            audio::audio_set_loop,
+            audio::audio_get_track_loops,
+            audio::audio_save_track_loop,
+            audio::audio_delete_track_loop,
             // Playlist commands
             playlists::create_playlist,
+            playlists::create_smart_playlist,
+            playlists::update_smart_playlist_rules,
             playlists::delete_playlist,
             playlists::update_playlist,
             playlists::get_playlists,
+            playlists::get_playlists_page,
+            playlists::get_playlist_tree,
+            playlists::move_playlist,
+            playlists::create_playlist_folder,
+            playlists::rename_playlist_folder,
+            playlists::move_playlist_folder,
+            playlists::delete_playlist_folder,
             playlists::get_playlist_tracks,
             playlists::add_track_to_playlist,
+            playlists::add_tracks_to_playlist,
+            playlists::move_playlist_entry,
             playlists::remove_track_from_playlist,
+            playlists::remove_playlist_entries,
+            playlists::export_playlist,
+            playlists::import_playlist,
             // Profile
             profile::set_active_profile,
             profile::delete_profile_data,
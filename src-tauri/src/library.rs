@@ -1,4 +1,5 @@
 use crate::database::DbHelper;
+use crate::paging::{AlbumQuery, Page, TrackQuery};
 use crate::profile::get_library_db_path; // Import helper
 use serde::{Deserialize, Serialize};
 use tauri::{command, AppHandle}; // Removed Manager import if not used
@@ -12,6 +13,46 @@ pub struct LibraryTrack {
     pub duration_ms: u64,
     pub file_path: String,
     pub artwork_path: Option<String>,
+    #[serde(flatten)]
+    pub loudness: crate::loudness::TrackLoudness,
+    #[serde(flatten)]
+    pub silence: crate::silence::TrackSilence,
+    #[serde(flatten)]
+    pub tags: TrackTags,
+    #[serde(flatten)]
+    pub format: TrackFormat,
+    #[serde(flatten)]
+    pub plays: TrackPlays,
+}
+
+/// Tags of a track beyond title, artist and album
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackTags {
+    /// Every credited artist in tag order, or just the track artist when the
+    /// tags credit nobody else
+    pub artists: Vec<String>,
+    pub track_number: Option<u32>,
+    pub disc_number: Option<u32>,
+    pub year: Option<i32>,
+    pub genre: Option<String>,
+}
+
+/// Properties of the audio file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackFormat {
+    pub file_format: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub bit_rate: Option<u32>,
+    pub channels: Option<u32>,
+    pub file_size: Option<u64>,
+}
+
+/// Listening history of a track, counted the same way as `TrackPlayStats`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackPlays {
+    pub play_count: i64,
+    pub skip_count: i64,
+    pub last_played_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -24,6 +65,59 @@ pub struct LibraryAlbum {
     pub artwork_path: Option<String>,
     pub track_count: i64,
     pub total_duration_ms: u64,
+    /// Discs the album's tracks are spread over, tracks without a disc number
+    /// counting as disc 1
+    pub disc_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibraryArtist {
+    pub id: i64,
+    pub name: String,
+    /// Tracks where the artist is the track artist or one of the credited artists
+    pub track_count: i64,
+    /// Albums the artist released or appears on
+    pub album_count: i64,
+    pub artwork_path: Option<String>,
+}
+
+/// Albums of an artist, split by whether they are the album artist
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArtistAlbums {
+    pub albums: Vec<LibraryAlbum>,
+    /// Other artists' albums with tracks by this artist
+    pub appears_on: Vec<LibraryAlbum>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtistSort {
+    #[default]
+    Name,
+    TrackCount,
+    AlbumCount,
+}
+
+/// One play from the listening history
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub track_id: i64,
+    pub title: String,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub artwork_path: Option<String>,
+    pub played_at: String,
+    pub play_duration_ms: u64,
+    pub completed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrackPlayStats {
+    pub track_id: i64,
+    pub play_count: i64, // Completed plays
+    pub skip_count: i64,
+    pub last_played_at: Option<String>,
 }
 
 #[command]
@@ -42,6 +136,23 @@ pub fn get_all_albums(app: AppHandle) -> Result<Vec<LibraryAlbum>, String> {
         .map_err(|e| format!("Failed to fetch albums: {}", e))
 }
 
+/// One page of tracks, filtered and sorted by SQLite
+#[command]
+pub fn get_tracks_page(app: AppHandle, query: TrackQuery) -> Result<Page<LibraryTrack>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_tracks_page(&query)
+        .map_err(|e| format!("Failed to fetch tracks: {}", e))
+}
+
+#[command]
+pub fn get_albums_page(app: AppHandle, query: AlbumQuery) -> Result<Page<LibraryAlbum>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_albums_page(&query)
+        .map_err(|e| format!("Failed to fetch albums: {}", e))
+}
+
 #[command]
 pub fn get_album_by_id(app: AppHandle, id: i64) -> Result<Option<LibraryAlbum>, String> {
     let db_path = get_library_db_path(&app)?;
@@ -58,6 +169,48 @@ pub fn get_album_tracks(app: AppHandle, album_id: i64) -> Result<Vec<LibraryTrac
         .map_err(|e| format!("Failed to fetch album tracks: {}", e))
 }
 
+/// Artists with at least one track or album, optionally filtered by name
+#[command]
+pub fn get_all_artists(
+    app: AppHandle,
+    sort: Option<ArtistSort>,
+    descending: Option<bool>,
+    filter: Option<String>,
+) -> Result<Vec<LibraryArtist>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_all_artists(
+        sort.unwrap_or_default(),
+        descending.unwrap_or(false),
+        filter.as_deref(),
+    )
+    .map_err(|e| format!("Failed to fetch artists: {}", e))
+}
+
+#[command]
+pub fn get_artist_by_id(app: AppHandle, id: i64) -> Result<Option<LibraryArtist>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_artist_by_id(id)
+        .map_err(|e| format!("Failed to fetch artist: {}", e))
+}
+
+#[command]
+pub fn get_artist_albums(app: AppHandle, artist_id: i64) -> Result<ArtistAlbums, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_artist_albums(artist_id)
+        .map_err(|e| format!("Failed to fetch artist albums: {}", e))
+}
+
+#[command]
+pub fn get_artist_tracks(app: AppHandle, artist_id: i64) -> Result<Vec<LibraryTrack>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_artist_tracks(artist_id)
+        .map_err(|e| format!("Failed to fetch artist tracks: {}", e))
+}
+
 #[command]
 pub fn delete_track(app: AppHandle, track_id: i64) -> Result<(), String> {
     let db_path = get_library_db_path(&app)?;
@@ -65,3 +218,32 @@ pub fn delete_track(app: AppHandle, track_id: i64) -> Result<(), String> {
     db.delete_track(track_id)
         .map_err(|e| format!("Failed to delete track: {}", e))
 }
+
+#[command]
+pub fn get_play_history(app: AppHandle, limit: Option<u32>) -> Result<Vec<HistoryEntry>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_play_history(limit.unwrap_or(100))
+        .map_err(|e| format!("Failed to fetch play history: {}", e))
+}
+
+#[command]
+pub fn get_track_play_stats(app: AppHandle) -> Result<Vec<TrackPlayStats>, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.get_track_play_stats()
+        .map_err(|e| format!("Failed to fetch play counts: {}", e))
+}
+
+/// Ranked tracks, albums, artists and playlists for the search box
+#[command]
+pub fn search_library(
+    app: AppHandle,
+    query: String,
+    limit: Option<u32>,
+) -> Result<crate::search::SearchResults, String> {
+    let db_path = get_library_db_path(&app)?;
+    let db = DbHelper::new(&db_path).map_err(|e| format!("Failed to open database: {}", e))?;
+    db.search_library(&query, limit.unwrap_or(20))
+        .map_err(|e| format!("Failed to search library: {}", e))
+}
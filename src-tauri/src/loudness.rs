@@ -0,0 +1,339 @@
+//! Loudness measurement and ReplayGain helpers
+//!
+//! Integrated loudness follows EBU R128 / ITU-R BS.1770 (K-weighting, 400ms
+//! blocks with 75% overlap, absolute and relative gating). True peak is
+//! estimated by 4x oversampling. Gains are expressed in ReplayGain 2.0 terms,
+//! i.e. relative to a -18 LUFS reference.
+
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+use std::io;
+
+use crate::audio::decoder;
+
+/// ReplayGain 2.0 reference level
+const REFERENCE_LUFS: f64 = -18.0;
+/// R128 tags are relative to -23 LUFS, 5 dB quieter than ReplayGain
+const R128_OFFSET_DB: f32 = 5.0;
+
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+
+/// K-weighting is specified at 48 kHz, and stereo is metered per channel
+const ANALYSIS_SAMPLE_RATE: u32 = 48000;
+const ANALYSIS_CHANNELS: u16 = 2;
+
+/// Loudness values of a track and its album, as stored in the library
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackLoudness {
+    pub track_gain_db: Option<f32>,
+    pub track_peak: Option<f32>,
+    pub album_gain_db: Option<f32>,
+    pub album_peak: Option<f32>,
+}
+
+/// Parse a ReplayGain gain tag such as "-6.54 dB"
+pub fn parse_gain_db(value: &str) -> Option<f32> {
+    let trimmed = value.trim();
+    let number = trimmed
+        .strip_suffix("dB")
+        .or_else(|| trimmed.strip_suffix("db"))
+        .unwrap_or(trimmed);
+    number.trim().parse().ok().filter(|g: &f32| g.is_finite())
+}
+
+/// Parse a ReplayGain peak tag such as "0.988553"
+pub fn parse_peak(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|p: &f32| p.is_finite() && *p >= 0.0)
+}
+
+/// Convert an Opus R128 gain tag (Q7.8 fixed point) to a ReplayGain gain
+pub fn parse_r128_gain(value: &str) -> Option<f32> {
+    let fixed: i32 = value.trim().parse().ok()?;
+    Some(fixed as f32 / 256.0 + R128_OFFSET_DB)
+}
+
+/// Direct form I biquad used for K-weighting
+#[derive(Clone)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+    x: [f64; 2],
+    y: [f64; 2],
+}
+
+impl Biquad {
+    fn new(b: [f64; 3], a: [f64; 3]) -> Self {
+        Self {
+            b,
+            a,
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, input: f64) -> f64 {
+        let output = self.b[0] * input + self.b[1] * self.x[0] + self.b[2] * self.x[1]
+            - self.a[1] * self.y[0]
+            - self.a[2] * self.y[1];
+        self.x = [input, self.x[0]];
+        self.y = [output, self.y[0]];
+        output
+    }
+}
+
+/// BS.1770 K-weighting filter pair, derived for an arbitrary sample rate
+fn k_weighting(sample_rate: u32) -> (Biquad, Biquad) {
+    let fs = sample_rate as f64;
+
+    // Stage 1: high shelf modelling the acoustic effect of the head
+    let f0 = 1681.974450955533;
+    let gain_db = 3.999843853973347;
+    let q = 0.7071752369554196;
+    let k = (PI * f0 / fs).tan();
+    let vh = 10f64.powf(gain_db / 20.0);
+    let vb = vh.powf(0.4996667741545416);
+    let a0 = 1.0 + k / q + k * k;
+    let shelf = Biquad::new(
+        [
+            (vh + vb * k / q + k * k) / a0,
+            2.0 * (k * k - vh) / a0,
+            (vh - vb * k / q + k * k) / a0,
+        ],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    // Stage 2: RLB high-pass
+    let f0 = 38.13547087602444;
+    let q = 0.5003270373238773;
+    let k = (PI * f0 / fs).tan();
+    let a0 = 1.0 + k / q + k * k;
+    let high_pass = Biquad::new(
+        [1.0, -2.0, 1.0],
+        [1.0, 2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    );
+
+    (shelf, high_pass)
+}
+
+const OVERSAMPLE: usize = 4;
+const TAPS_PER_PHASE: usize = 12;
+
+/// Polyphase windowed-sinc interpolator used for true peak detection
+fn interpolation_filter() -> Vec<[f64; TAPS_PER_PHASE]> {
+    let len = OVERSAMPLE * TAPS_PER_PHASE;
+    let center = (len - 1) as f64 / 2.0;
+    let mut phases = vec![[0.0; TAPS_PER_PHASE]; OVERSAMPLE];
+
+    for m in 0..len {
+        let x = (m as f64 - center) / OVERSAMPLE as f64;
+        let sinc = if x.abs() < 1e-9 {
+            1.0
+        } else {
+            (PI * x).sin() / (PI * x)
+        };
+        let window = 0.5 - 0.5 * (2.0 * PI * (m as f64 + 0.5) / len as f64).cos();
+        phases[m % OVERSAMPLE][m / OVERSAMPLE] = sinc * window;
+    }
+    phases
+}
+
+/// Streaming EBU R128 integrated loudness and true peak meter
+pub struct LoudnessMeter {
+    channels: usize,
+    filters: Vec<(Biquad, Biquad)>,
+    segment_len: usize, // Frames per 100ms
+    segment_frames: usize,
+    segment_energy: f64,
+    recent_segments: [f64; 4],
+    segments_seen: usize,
+    block_powers: Vec<f64>,
+    peak_filter: Vec<[f64; TAPS_PER_PHASE]>,
+    peak_history: Vec<[f64; TAPS_PER_PHASE]>,
+    true_peak: f64,
+}
+
+impl LoudnessMeter {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let channels = channels.max(1) as usize;
+        Self {
+            channels,
+            filters: (0..channels).map(|_| k_weighting(sample_rate)).collect(),
+            segment_len: (sample_rate as usize / 10).max(1),
+            segment_frames: 0,
+            segment_energy: 0.0,
+            recent_segments: [0.0; 4],
+            segments_seen: 0,
+            block_powers: Vec::new(),
+            peak_filter: interpolation_filter(),
+            peak_history: vec![[0.0; TAPS_PER_PHASE]; channels],
+            true_peak: 0.0,
+        }
+    }
+
+    /// Feed interleaved samples
+    pub fn process(&mut self, samples: &[f32]) {
+        for frame in samples.chunks_exact(self.channels) {
+            for (ch, &sample) in frame.iter().enumerate() {
+                let input = sample as f64;
+
+                let (shelf, high_pass) = &mut self.filters[ch];
+                let weighted = high_pass.process(shelf.process(input));
+                // All channels are weighted 1.0: analysis always decodes to stereo
+                self.segment_energy += weighted * weighted;
+
+                let history = &mut self.peak_history[ch];
+                history.rotate_right(1);
+                history[0] = input;
+                for phase in &self.peak_filter {
+                    let value: f64 = phase.iter().zip(history.iter()).map(|(h, x)| h * x).sum();
+                    self.true_peak = self.true_peak.max(value.abs());
+                }
+                self.true_peak = self.true_peak.max(input.abs());
+            }
+
+            self.segment_frames += 1;
+            if self.segment_frames == self.segment_len {
+                self.finish_segment();
+            }
+        }
+    }
+
+    fn finish_segment(&mut self) {
+        self.recent_segments.rotate_right(1);
+        self.recent_segments[0] = self.segment_energy;
+        self.segment_energy = 0.0;
+        self.segment_frames = 0;
+        self.segments_seen += 1;
+
+        // A 400ms block spans four 100ms segments
+        if self.segments_seen >= 4 {
+            let energy: f64 = self.recent_segments.iter().sum();
+            self.block_powers
+                .push(energy / (self.segment_len * 4) as f64);
+        }
+    }
+
+    /// Gated integrated loudness in LUFS, or `None` for silent or very short input
+    pub fn integrated_loudness(&self) -> Option<f64> {
+        let loudness = |power: f64| -0.691 + 10.0 * power.log10();
+
+        let above_absolute: Vec<f64> = self
+            .block_powers
+            .iter()
+            .copied()
+            .filter(|&p| p > 0.0 && loudness(p) > ABSOLUTE_GATE_LUFS)
+            .collect();
+        if above_absolute.is_empty() {
+            return None;
+        }
+
+        let mean = above_absolute.iter().sum::<f64>() / above_absolute.len() as f64;
+        let relative_gate = loudness(mean) + RELATIVE_GATE_LU;
+
+        let gated: Vec<f64> = above_absolute
+            .into_iter()
+            .filter(|&p| loudness(p) > relative_gate)
+            .collect();
+        if gated.is_empty() {
+            return None;
+        }
+
+        Some(loudness(gated.iter().sum::<f64>() / gated.len() as f64))
+    }
+
+    /// Linear true peak (1.0 = full scale)
+    pub fn true_peak(&self) -> f32 {
+        self.true_peak as f32
+    }
+}
+
+/// Result of analysing a single file
+#[derive(Debug, Clone, Copy)]
+pub struct LoudnessAnalysis {
+    pub gain_db: f32,
+    pub true_peak: f32,
+}
+
+/// Decode a file and measure its loudness
+pub fn analyze_file(path: &str) -> io::Result<LoudnessAnalysis> {
+    let mut meter = LoudnessMeter::new(ANALYSIS_SAMPLE_RATE, ANALYSIS_CHANNELS);
+    decoder::decode_file(path, ANALYSIS_SAMPLE_RATE, ANALYSIS_CHANNELS, |samples| {
+        meter.process(samples)
+    })?;
+
+    let integrated = meter.integrated_loudness().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Track is silent or too short")
+    })?;
+
+    Ok(LoudnessAnalysis {
+        gain_db: (REFERENCE_LUFS - integrated) as f32,
+        true_peak: meter.true_peak(),
+    })
+}
+
+/// Combine per-track measurements into album loudness, weighting by duration
+pub fn album_loudness(tracks: &[(f32, f32, u64)]) -> Option<(f32, f32)> {
+    let total_ms: u64 = tracks.iter().map(|(_, _, ms)| ms).sum();
+    if total_ms == 0 {
+        return None;
+    }
+
+    // Average in the power domain: gain is the negated loudness offset
+    let power: f64 = tracks
+        .iter()
+        .map(|(gain, _, ms)| 10f64.powf(-(*gain as f64) / 10.0) * *ms as f64)
+        .sum::<f64>()
+        / total_ms as f64;
+    let gain = -10.0 * power.log10();
+    let peak = tracks.iter().map(|(_, peak, _)| *peak).fold(0.0f32, f32::max);
+
+    Some((gain as f32, peak))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sine(freq: f64, amplitude: f64, seconds: f64, sample_rate: u32) -> Vec<f32> {
+        let frames = (seconds * sample_rate as f64) as usize;
+        let mut samples = Vec::with_capacity(frames * 2);
+        for n in 0..frames {
+            let value = (amplitude * (2.0 * PI * freq * n as f64 / sample_rate as f64).sin()) as f32;
+            samples.push(value);
+            samples.push(value);
+        }
+        samples
+    }
+
+    #[test]
+    fn test_reference_sine_loudness() {
+        // EBU Tech 3341: a stereo 1kHz sine at -23 dBFS reads -23 LUFS
+        let amplitude = 10f64.powf(-23.0 / 20.0);
+        let mut meter = LoudnessMeter::new(48000, 2);
+        meter.process(&sine(1000.0, amplitude, 5.0, 48000));
+
+        let loudness = meter.integrated_loudness().unwrap();
+        assert!((loudness + 23.0).abs() < 0.1, "measured {}", loudness);
+        assert!((meter.true_peak() as f64 - amplitude).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_silence_has_no_loudness() {
+        let mut meter = LoudnessMeter::new(44100, 2);
+        meter.process(&vec![0.0; 44100 * 2 * 2]);
+        assert!(meter.integrated_loudness().is_none());
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        assert_eq!(parse_gain_db("-6.54 dB"), Some(-6.54));
+        assert_eq!(parse_gain_db("+1.20"), Some(1.2));
+        assert_eq!(parse_peak("0.988553"), Some(0.988553));
+        assert_eq!(parse_r128_gain("-1280"), Some(0.0));
+    }
+}
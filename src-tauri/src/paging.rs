@@ -0,0 +1,332 @@
+//! Paged listings of tracks, albums and playlists
+//!
+//! Large libraries are too big to send to the UI in one piece, so listings
+//! are filtered, sorted and cut into pages by SQLite. Every page carries the
+//! number of matches across all pages, which virtualized lists need to size
+//! themselves.
+
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+use crate::smart_playlist::escape_like;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 100;
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Matches across all pages
+    pub total: i64,
+    pub offset: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrackSort {
+    Title,
+    Artist,
+    Album,
+    Duration,
+    #[default]
+    DateAdded,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TrackQuery {
+    pub offset: u32,
+    pub limit: Option<u32>,
+    pub sort: TrackSort,
+    pub descending: bool,
+    /// Text contained in the title, artist or album
+    pub filter: Option<String>,
+    /// Tracks by or crediting this artist
+    pub artist_id: Option<i64>,
+    pub album_id: Option<i64>,
+    pub genre: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlbumSort {
+    #[default]
+    Title,
+    Artist,
+    Year,
+    TrackCount,
+    DateAdded,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AlbumQuery {
+    pub offset: u32,
+    pub limit: Option<u32>,
+    pub sort: AlbumSort,
+    pub descending: bool,
+    /// Text contained in the title or album artist
+    pub filter: Option<String>,
+    /// Albums with this album artist
+    pub artist_id: Option<i64>,
+    pub year: Option<i32>,
+}
+
+/// Smart playlists count their tracks on every read, so they cannot be sorted
+/// by track count in SQL
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaylistSort {
+    #[default]
+    Name,
+    CreatedAt,
+    UpdatedAt,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PlaylistQuery {
+    pub offset: u32,
+    pub limit: Option<u32>,
+    pub sort: PlaylistSort,
+    pub descending: bool,
+    /// Text contained in the name or description
+    pub filter: Option<String>,
+    /// Only smart playlists with `true`, only manual ones with `false`
+    pub smart: Option<bool>,
+}
+
+/// Filter and order of a listing. `conditions` is empty or starts with
+/// `WHERE`, and `params` are bound to its placeholders in order.
+pub struct ListingSql {
+    pub conditions: String,
+    pub order: String,
+    pub params: Vec<Value>,
+    pub limit: u32,
+}
+
+impl TrackQuery {
+    /// Tracks are joined as `t`, their artist as `ar` and their album as `al`
+    pub fn to_sql(&self) -> ListingSql {
+        let mut listing = ListingSql::new(self.limit);
+        listing.filter_text(self.filter.as_deref(), &["t.title", "ar.name", "al.title"]);
+        if let Some(artist_id) = self.artist_id {
+            listing.push(
+                "(t.artist_id = ? OR EXISTS (
+                    SELECT 1 FROM track_artists ta WHERE ta.track_id = t.id AND ta.artist_id = ?
+                ))",
+                &[Value::Integer(artist_id), Value::Integer(artist_id)],
+            );
+        }
+        if let Some(album_id) = self.album_id {
+            listing.push("t.album_id = ?", &[Value::Integer(album_id)]);
+        }
+        if let Some(genre) = &self.genre {
+            listing.push("t.genre = ? COLLATE NOCASE", &[Value::Text(genre.clone())]);
+        }
+
+        let direction = direction(self.descending);
+        let order = match self.sort {
+            TrackSort::Title => format!("t.title COLLATE NOCASE {}", direction),
+            TrackSort::Artist => format!(
+                "ar.name COLLATE NOCASE {}, al.title COLLATE NOCASE, t.disc_number, t.track_number",
+                direction
+            ),
+            TrackSort::Album => format!(
+                "al.title COLLATE NOCASE {}, t.disc_number, t.track_number",
+                direction
+            ),
+            TrackSort::Duration => format!("t.duration_ms {}", direction),
+            TrackSort::DateAdded => format!("t.created_at {}", direction),
+        };
+        // Ties are broken by id so rows cannot move between pages
+        listing.order = format!("{}, t.id {}", order, direction);
+        listing
+    }
+}
+
+impl AlbumQuery {
+    /// Albums are joined as `al` and their album artist as `ar`
+    pub fn to_sql(&self) -> ListingSql {
+        let mut listing = ListingSql::new(self.limit);
+        listing.filter_text(self.filter.as_deref(), &["al.title", "ar.name"]);
+        if let Some(artist_id) = self.artist_id {
+            listing.push("al.artist_id = ?", &[Value::Integer(artist_id)]);
+        }
+        if let Some(year) = self.year {
+            listing.push("al.year = ?", &[Value::Integer(year as i64)]);
+        }
+
+        let direction = direction(self.descending);
+        let order = match self.sort {
+            AlbumSort::Title => format!("al.title COLLATE NOCASE {}", direction),
+            AlbumSort::Artist => format!(
+                "ar.name COLLATE NOCASE {}, al.year, al.title COLLATE NOCASE",
+                direction
+            ),
+            AlbumSort::Year => format!("al.year {}, al.title COLLATE NOCASE", direction),
+            AlbumSort::TrackCount => format!("track_count {}", direction),
+            AlbumSort::DateAdded => format!("al.created_at {}", direction),
+        };
+        listing.order = format!("{}, al.id {}", order, direction);
+        listing
+    }
+}
+
+impl PlaylistQuery {
+    /// Playlists are selected as `p`
+    pub fn to_sql(&self) -> ListingSql {
+        let mut listing = ListingSql::new(self.limit);
+        listing.filter_text(self.filter.as_deref(), &["p.name", "p.description"]);
+        match self.smart {
+            Some(true) => listing.push("p.smart_rules IS NOT NULL", &[]),
+            Some(false) => listing.push("p.smart_rules IS NULL", &[]),
+            None => {}
+        }
+
+        let direction = direction(self.descending);
+        let order = match self.sort {
+            PlaylistSort::Name => format!("p.name COLLATE NOCASE {}", direction),
+            PlaylistSort::CreatedAt => format!("p.created_at {}", direction),
+            PlaylistSort::UpdatedAt => format!("p.updated_at {}", direction),
+        };
+        listing.order = format!("{}, p.id {}", order, direction);
+        listing
+    }
+}
+
+impl ListingSql {
+    fn new(limit: Option<u32>) -> Self {
+        ListingSql {
+            conditions: String::new(),
+            order: String::new(),
+            params: Vec::new(),
+            limit: limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE),
+        }
+    }
+
+    fn push(&mut self, condition: &str, params: &[Value]) {
+        let keyword = if self.conditions.is_empty() {
+            "WHERE"
+        } else {
+            " AND"
+        };
+        self.conditions
+            .push_str(&format!("{} {}", keyword, condition));
+        self.params.extend_from_slice(params);
+    }
+
+    /// Match rows where any of `columns` contains `text`
+    fn filter_text(&mut self, text: Option<&str>, columns: &[&str]) {
+        let Some(text) = text.map(str::trim).filter(|text| !text.is_empty()) else {
+            return;
+        };
+        let pattern = Value::Text(format!("%{}%", escape_like(text)));
+        let condition = columns
+            .iter()
+            .map(|column| format!("{} LIKE ? ESCAPE '\\'", column))
+            .collect::<Vec<_>>()
+            .join(" OR ");
+        self.push(&format!("({})", condition), &vec![pattern; columns.len()]);
+    }
+}
+
+fn direction(descending: bool) -> &'static str {
+    if descending {
+        "DESC"
+    } else {
+        "ASC"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DbHelper;
+
+    #[test]
+    fn test_pages_are_filtered_sorted_and_counted() {
+        let mut db = DbHelper::new(":memory:").unwrap();
+        db.get_conn_mut()
+            .execute_batch(
+                "INSERT INTO artists (id, name) VALUES (1, 'Nina'), (2, 'Otis');
+                INSERT INTO albums (id, title, artist_id, year) VALUES
+                    (1, 'Pastel Blues', 1, 1965), (2, 'Otis Blue', 2, 1965), (3, 'Dock', 2, 1968);
+                INSERT INTO tracks (id, title, artist_id, album_id, file_path, duration_ms) VALUES
+                    (1, 'Sinnerman', 1, 1, '/1.flac', 600000),
+                    (2, 'Be My Husband', 1, 1, '/2.flac', 180000),
+                    (3, 'Respect', 2, 2, '/3.flac', 120000),
+                    (4, 'Shake', 2, 2, '/4.flac', 150000),
+                    (5, '100% Yes', 2, 3, '/5.flac', 100000);
+                INSERT INTO playlists (name, smart_rules) VALUES
+                    ('Mornings', NULL), ('Evenings', NULL), ('Long', '{\"rules\": []}');",
+            )
+            .unwrap();
+
+        let query = TrackQuery {
+            limit: Some(2),
+            sort: TrackSort::Duration,
+            descending: true,
+            ..Default::default()
+        };
+        let page = db.get_tracks_page(&query).unwrap();
+        assert_eq!(page.total, 5);
+        let ids: Vec<i64> = page.items.iter().map(|t| t.id).collect();
+        assert_eq!(ids, vec![1, 2]);
+
+        let next = db
+            .get_tracks_page(&TrackQuery { offset: 2, ..query })
+            .unwrap();
+        assert_eq!(next.items[0].id, 4);
+
+        // Filters match the album and artist too, and take wildcards literally
+        let blue = TrackQuery {
+            filter: Some("blue".into()),
+            sort: TrackSort::Title,
+            ..Default::default()
+        };
+        let titles: Vec<String> = db
+            .get_tracks_page(&blue)
+            .unwrap()
+            .items
+            .into_iter()
+            .map(|t| t.title)
+            .collect();
+        assert_eq!(
+            titles,
+            vec!["Be My Husband", "Respect", "Shake", "Sinnerman"]
+        );
+        let percent = TrackQuery {
+            filter: Some("%".into()),
+            ..Default::default()
+        };
+        assert_eq!(db.get_tracks_page(&percent).unwrap().total, 1);
+
+        let albums = db
+            .get_albums_page(&AlbumQuery {
+                sort: AlbumSort::TrackCount,
+                descending: true,
+                artist_id: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(albums.total, 2);
+        assert_eq!(albums.items[0].title, "Otis Blue");
+
+        let playlists = db
+            .get_playlists_page(&PlaylistQuery {
+                smart: Some(false),
+                ..Default::default()
+            })
+            .unwrap();
+        let names: Vec<&str> = playlists.items.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["Evenings", "Mornings"]);
+        let smart = db
+            .get_playlists_page(&PlaylistQuery {
+                smart: Some(true),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(smart.items[0].track_count, 5);
+    }
+}
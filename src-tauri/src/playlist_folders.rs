@@ -0,0 +1,160 @@
+//! Folders that group playlists into a tree
+//!
+//! Folders and playlists are stored flat, each pointing at its parent folder.
+//! The tree is put together here for the UI.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::playlists::Playlist;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistFolder {
+    pub id: i64,
+    pub name: String,
+    /// `None` for folders at the top level
+    pub parent_id: Option<i64>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FolderNode {
+    #[serde(flatten)]
+    pub folder: PlaylistFolder,
+    pub folders: Vec<FolderNode>,
+    pub playlists: Vec<Playlist>,
+}
+
+/// Everything at the top level, with folders holding their contents
+#[derive(Debug, Serialize)]
+pub struct PlaylistTree {
+    pub folders: Vec<FolderNode>,
+    pub playlists: Vec<Playlist>,
+}
+
+/// What happens to the contents of a deleted folder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderDeleteMode {
+    /// Delete sub-folders and playlists along with the folder
+    Cascade,
+    /// Move sub-folders and playlists up into the folder's parent
+    Reparent,
+}
+
+/// Whether putting `folder_id` inside `parent_id` would make it its own ancestor
+pub fn would_create_cycle(folders: &[PlaylistFolder], folder_id: i64, parent_id: i64) -> bool {
+    let parents: HashMap<i64, Option<i64>> = folders.iter().map(|f| (f.id, f.parent_id)).collect();
+
+    let mut current = Some(parent_id);
+    // Bounded by the folder count in case the stored data already has a loop
+    for _ in 0..=folders.len() {
+        match current {
+            Some(id) if id == folder_id => return true,
+            Some(id) => current = parents.get(&id).copied().flatten(),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Arrange folders and playlists into a tree. Anything pointing at a folder
+/// that does not exist is shown at the top level.
+pub fn build_tree(folders: Vec<PlaylistFolder>, playlists: Vec<Playlist>) -> PlaylistTree {
+    let known: HashSet<i64> = folders.iter().map(|f| f.id).collect();
+    let parent_of = |id: Option<i64>| id.filter(|id| known.contains(id));
+
+    let mut child_folders: HashMap<Option<i64>, Vec<PlaylistFolder>> = HashMap::new();
+    for folder in folders {
+        let parent = parent_of(folder.parent_id);
+        child_folders.entry(parent).or_default().push(folder);
+    }
+    let mut child_playlists: HashMap<Option<i64>, Vec<Playlist>> = HashMap::new();
+    for playlist in playlists {
+        let parent = parent_of(playlist.folder_id);
+        child_playlists.entry(parent).or_default().push(playlist);
+    }
+
+    fn nodes(
+        parent: Option<i64>,
+        child_folders: &mut HashMap<Option<i64>, Vec<PlaylistFolder>>,
+        child_playlists: &mut HashMap<Option<i64>, Vec<Playlist>>,
+    ) -> Vec<FolderNode> {
+        let mut folders = child_folders.remove(&parent).unwrap_or_default();
+        folders.sort_by_key(|f| f.name.to_lowercase());
+        folders
+            .into_iter()
+            .map(|folder| {
+                let id = Some(folder.id);
+                FolderNode {
+                    folders: nodes(id, child_folders, child_playlists),
+                    playlists: child_playlists.remove(&id).unwrap_or_default(),
+                    folder,
+                }
+            })
+            .collect()
+    }
+
+    PlaylistTree {
+        folders: nodes(None, &mut child_folders, &mut child_playlists),
+        playlists: child_playlists.remove(&None).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folder(id: i64, name: &str, parent_id: Option<i64>) -> PlaylistFolder {
+        PlaylistFolder {
+            id,
+            name: name.to_string(),
+            parent_id,
+            created_at: String::new(),
+        }
+    }
+
+    fn playlist(id: i64, folder_id: Option<i64>) -> Playlist {
+        Playlist {
+            id,
+            name: format!("Playlist {}", id),
+            description: None,
+            artwork_path: None,
+            track_count: 0,
+            created_at: String::new(),
+            smart_rules: None,
+            folder_id,
+        }
+    }
+
+    #[test]
+    fn test_tree_nests_folders_and_playlists() {
+        let folders = vec![
+            folder(1, "Moods", None),
+            folder(2, "Calm", Some(1)),
+            folder(3, "Archive", None),
+        ];
+        assert!(would_create_cycle(&folders, 1, 2));
+        assert!(would_create_cycle(&folders, 1, 1));
+        assert!(!would_create_cycle(&folders, 2, 3));
+
+        let tree = build_tree(
+            folders,
+            vec![
+                playlist(10, Some(2)),
+                playlist(11, None),
+                playlist(12, Some(99)),
+            ],
+        );
+        let names: Vec<&str> = tree
+            .folders
+            .iter()
+            .map(|n| n.folder.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["Archive", "Moods"]);
+        assert_eq!(tree.folders[1].folders[0].playlists[0].id, 10);
+        // Playlists in a missing folder end up at the top level
+        let top: Vec<i64> = tree.playlists.iter().map(|p| p.id).collect();
+        assert_eq!(top, vec![11, 12]);
+    }
+}
@@ -0,0 +1,607 @@
+//! Reading and writing playlist files: M3U/M3U8, PLS and XSPF
+//!
+//! Entries are matched back to the library by path first. Files that moved
+//! since the playlist was written fall back to artist, title and duration.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+use url::Url;
+
+use crate::library::LibraryTrack;
+
+/// Largest duration difference for a fuzzy match
+const FUZZY_DURATION_TOLERANCE_MS: u64 = 3000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlaylistFormat {
+    /// Extended M3U, always written as UTF-8
+    M3u,
+    Pls,
+    Xspf,
+}
+
+impl PlaylistFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "m3u" | "m3u8" => Some(Self::M3u),
+            "pls" => Some(Self::Pls),
+            "xspf" => Some(Self::Xspf),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlaylistEntry {
+    /// Path or URL exactly as it appears in the file
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub duration_ms: Option<u64>,
+}
+
+impl PlaylistEntry {
+    /// Entry for a library track, located absolutely or relative to `playlist_dir`
+    pub fn for_track(
+        track: &LibraryTrack,
+        format: PlaylistFormat,
+        playlist_dir: &Path,
+        relative: bool,
+    ) -> Self {
+        Self {
+            location: location_for(format, Path::new(&track.file_path), playlist_dir, relative),
+            title: Some(track.title.clone()),
+            artist: track.artist.clone(),
+            duration_ms: Some(track.duration_ms),
+        }
+    }
+
+    /// "Artist - Title", as used by M3U and PLS
+    fn display_name(&self) -> String {
+        match (&self.artist, &self.title) {
+            (Some(artist), Some(title)) => format!("{} - {}", artist, title),
+            (None, Some(title)) => title.clone(),
+            _ => String::new(),
+        }
+    }
+
+    fn set_display_name(&mut self, name: &str) {
+        let name = name.trim();
+        if name.is_empty() {
+            return;
+        }
+        match name.split_once(" - ") {
+            Some((artist, title)) => {
+                self.artist = Some(artist.trim().to_string());
+                self.title = Some(title.trim().to_string());
+            }
+            None => self.title = Some(name.to_string()),
+        }
+    }
+}
+
+pub fn write(format: PlaylistFormat, name: &str, entries: &[PlaylistEntry]) -> String {
+    match format {
+        PlaylistFormat::M3u => write_m3u(name, entries),
+        PlaylistFormat::Pls => write_pls(entries),
+        PlaylistFormat::Xspf => write_xspf(name, entries),
+    }
+}
+
+pub fn parse(format: PlaylistFormat, content: &str) -> Vec<PlaylistEntry> {
+    // Some players start their files with a byte order mark
+    let content = content.trim_start_matches('\u{feff}');
+    match format {
+        PlaylistFormat::M3u => parse_m3u(content),
+        PlaylistFormat::Pls => parse_pls(content),
+        PlaylistFormat::Xspf => parse_xspf(content),
+    }
+}
+
+/// Characters Windows-1252 puts at 0x80..=0x9F, where Latin-1 has control codes
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// Text of a playlist file. Plain .m3u files written by older players are
+/// often Windows-1252 rather than UTF-8, so anything that is not valid UTF-8
+/// is read as Windows-1252.
+pub fn decode(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_string(),
+        Err(_) => bytes
+            .iter()
+            .map(|&byte| match byte {
+                0x80..=0x9f => WINDOWS_1252_HIGH[(byte - 0x80) as usize],
+                _ => byte as char,
+            })
+            .collect(),
+    }
+}
+
+fn duration_secs(entry: &PlaylistEntry) -> i64 {
+    entry
+        .duration_ms
+        .map_or(-1, |ms| ((ms + 500) / 1000) as i64)
+}
+
+fn write_m3u(name: &str, entries: &[PlaylistEntry]) -> String {
+    let mut out = format!("#EXTM3U\n#PLAYLIST:{}\n", name);
+    for entry in entries {
+        out.push_str(&format!(
+            "#EXTINF:{},{}\n{}\n",
+            duration_secs(entry),
+            entry.display_name(),
+            entry.location
+        ));
+    }
+    out
+}
+
+fn parse_m3u(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut pending = PlaylistEntry::default();
+
+    for line in content.lines().map(str::trim) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // "#EXTINF:<seconds> [attributes],<artist> - <title>"
+            let (head, name) = info.split_once(',').unwrap_or((info, ""));
+            let seconds = head
+                .split_whitespace()
+                .next()
+                .and_then(|s| s.parse::<f64>().ok());
+            pending.duration_ms = seconds
+                .filter(|s| *s >= 0.0)
+                .map(|s| (s * 1000.0).round() as u64);
+            pending.set_display_name(name);
+        } else if line.is_empty() || line.starts_with('#') {
+            continue;
+        } else {
+            pending.location = line.to_string();
+            entries.push(std::mem::take(&mut pending));
+        }
+    }
+    entries
+}
+
+fn write_pls(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("[playlist]\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry.location));
+        let name = entry.display_name();
+        if !name.is_empty() {
+            out.push_str(&format!("Title{}={}\n", n, name));
+        }
+        out.push_str(&format!("Length{}={}\n", n, duration_secs(entry)));
+    }
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    out
+}
+
+fn parse_pls(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+
+    for line in content.lines().map(str::trim) {
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let field = key.trim_end_matches(|c: char| c.is_ascii_digit());
+        let Ok(n) = key[field.len()..].parse::<u32>() else {
+            continue;
+        };
+
+        let entry = entries.entry(n).or_default();
+        match field {
+            "file" => entry.location = value.trim().to_string(),
+            "title" => entry.set_display_name(value),
+            "length" => {
+                entry.duration_ms = value
+                    .trim()
+                    .parse::<i64>()
+                    .ok()
+                    .filter(|s| *s >= 0)
+                    .map(|s| s as u64 * 1000)
+            }
+            _ => {}
+        }
+    }
+
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
+
+fn write_xspf(name: &str, entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    out.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        escape_xml(name)
+    ));
+    for entry in entries {
+        out.push_str("    <track>\n");
+        out.push_str(&format!(
+            "      <location>{}</location>\n",
+            escape_xml(&entry.location)
+        ));
+        if let Some(title) = &entry.title {
+            out.push_str(&format!("      <title>{}</title>\n", escape_xml(title)));
+        }
+        if let Some(artist) = &entry.artist {
+            out.push_str(&format!(
+                "      <creator>{}</creator>\n",
+                escape_xml(artist)
+            ));
+        }
+        if let Some(duration_ms) = entry.duration_ms {
+            out.push_str(&format!("      <duration>{}</duration>\n", duration_ms));
+        }
+        out.push_str("    </track>\n");
+    }
+    out.push_str("  </trackList>\n</playlist>\n");
+    out
+}
+
+/// Reads the `<track>` elements of an XSPF file. Only the few elements used
+/// for matching are looked at, so a full XML parser is not needed.
+fn parse_xspf(content: &str) -> Vec<PlaylistEntry> {
+    let mut entries = Vec::new();
+    let mut rest = content;
+
+    while let Some(start) = rest.find("<track>") {
+        let after = &rest[start + "<track>".len()..];
+        let Some(end) = after.find("</track>") else {
+            break;
+        };
+        let track = &after[..end];
+        rest = &after[end..];
+
+        let Some(location) = xml_element(track, "location") else {
+            continue;
+        };
+        entries.push(PlaylistEntry {
+            location,
+            title: xml_element(track, "title"),
+            artist: xml_element(track, "creator"),
+            duration_ms: xml_element(track, "duration").and_then(|d| d.parse().ok()),
+        });
+    }
+    entries
+}
+
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    let text = unescape_xml(xml[start..end].trim());
+    (!text.is_empty()).then_some(text)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Where a playlist file should point to reach `track_path`
+fn location_for(
+    format: PlaylistFormat,
+    track_path: &Path,
+    playlist_dir: &Path,
+    relative: bool,
+) -> String {
+    if format == PlaylistFormat::Xspf {
+        // XSPF locations are URIs, so relative paths are relative URI references
+        if let Ok(track_url) = Url::from_file_path(track_path) {
+            let relative_url = Url::from_directory_path(playlist_dir)
+                .ok()
+                .filter(|_| relative)
+                .and_then(|dir_url| dir_url.make_relative(&track_url));
+            return relative_url.unwrap_or_else(|| track_url.to_string());
+        }
+    } else if relative {
+        if let Some(path) = relative_path(playlist_dir, track_path) {
+            // Forward slashes keep the file usable on every platform
+            return path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+        }
+    }
+    track_path.to_string_lossy().to_string()
+}
+
+/// Local file an entry points to, `None` for streams and other URLs
+fn path_for(format: PlaylistFormat, location: &str, playlist_dir: &Path) -> Option<PathBuf> {
+    if format == PlaylistFormat::Xspf || location.starts_with("file://") {
+        let url = match Url::parse(location) {
+            Ok(url) => url,
+            Err(_) => Url::from_directory_path(playlist_dir)
+                .ok()?
+                .join(location)
+                .ok()?,
+        };
+        return url.to_file_path().ok();
+    }
+    if location.contains("://") {
+        return None;
+    }
+
+    // Playlists written on Windows use backslashes
+    let location = if cfg!(windows) {
+        location.to_string()
+    } else {
+        location.replace('\\', "/")
+    };
+    let path = Path::new(&location);
+    if path.is_absolute() {
+        Some(normalize_path(path))
+    } else {
+        Some(normalize_path(&playlist_dir.join(path)))
+    }
+}
+
+fn relative_path(from_dir: &Path, target: &Path) -> Option<PathBuf> {
+    let from: Vec<Component> = from_dir.components().collect();
+    let to: Vec<Component> = target.components().collect();
+    // Paths on different drives cannot be reached relatively
+    if from.first() != to.first() {
+        return None;
+    }
+
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let mut path = PathBuf::new();
+    for _ in common..from.len() {
+        path.push("..");
+    }
+    for component in &to[common..] {
+        path.push(component);
+    }
+    Some(path)
+}
+
+/// Resolve `.` and `..` without touching the file system, since the file
+/// may no longer exist
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Lowercase letters and digits only, so punctuation and spacing differences
+/// between taggers do not matter
+fn fuzzy_key(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryMatch {
+    /// Found at the path the playlist names
+    Exact(i64),
+    /// Found by artist, title and duration
+    Fuzzy(i64),
+    Unresolved,
+}
+
+/// Matches playlist entries against the tracks of the library
+pub struct TrackResolver<'a> {
+    by_path: HashMap<PathBuf, i64>,
+    /// Tracks with their fuzzy artist key, by fuzzy title key
+    by_title: HashMap<String, Vec<(Option<String>, &'a LibraryTrack)>>,
+}
+
+impl<'a> TrackResolver<'a> {
+    pub fn new(tracks: &'a [LibraryTrack]) -> Self {
+        let by_path = tracks
+            .iter()
+            .map(|track| (normalize_path(Path::new(&track.file_path)), track.id))
+            .collect();
+
+        let mut by_title: HashMap<String, Vec<_>> = HashMap::new();
+        for track in tracks {
+            by_title
+                .entry(fuzzy_key(&track.title))
+                .or_default()
+                .push((track.artist.as_deref().map(fuzzy_key), track));
+        }
+        Self { by_path, by_title }
+    }
+
+    pub fn resolve(
+        &self,
+        format: PlaylistFormat,
+        entry: &PlaylistEntry,
+        playlist_dir: &Path,
+    ) -> EntryMatch {
+        let path = path_for(format, &entry.location, playlist_dir);
+        if let Some(id) = path.as_ref().and_then(|path| self.by_path.get(path)) {
+            return EntryMatch::Exact(*id);
+        }
+
+        // Without tags in the playlist the file name is the best guess
+        let mut guess = entry.clone();
+        if guess.title.is_none() {
+            if let Some(stem) = path.as_ref().and_then(|p| p.file_stem()) {
+                guess.set_display_name(&stem.to_string_lossy());
+            }
+        }
+        match self.fuzzy_match(&guess) {
+            Some(id) => EntryMatch::Fuzzy(id),
+            None => EntryMatch::Unresolved,
+        }
+    }
+
+    fn fuzzy_match(&self, entry: &PlaylistEntry) -> Option<i64> {
+        let candidates = self.by_title.get(&fuzzy_key(entry.title.as_deref()?))?;
+        let artist = entry.artist.as_deref().map(fuzzy_key);
+
+        candidates
+            .iter()
+            .filter(|(track_artist, _)| match (&artist, track_artist) {
+                (Some(artist), Some(track_artist)) => track_artist == artist,
+                _ => true,
+            })
+            .map(|(_, track)| {
+                let diff = entry
+                    .duration_ms
+                    .map_or(0, |ms| ms.abs_diff(track.duration_ms));
+                (diff, track.id)
+            })
+            .filter(|(diff, _)| *diff <= FUZZY_DURATION_TOLERANCE_MS)
+            .min_by_key(|(diff, _)| *diff)
+            .map(|(_, id)| id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(id: i64, title: &str, artist: &str, duration_ms: u64, path: &str) -> LibraryTrack {
+        LibraryTrack {
+            id,
+            title: title.to_string(),
+            artist: Some(artist.to_string()),
+            album: None,
+            duration_ms,
+            file_path: path.to_string(),
+            artwork_path: None,
+            loudness: Default::default(),
+            silence: Default::default(),
+            tags: Default::default(),
+            format: Default::default(),
+            plays: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_formats_round_trip() {
+        let dir = Path::new("/music/playlists");
+        let library = track(
+            1,
+            "So What",
+            "Miles Davis",
+            562_000,
+            "/music/Miles & Co/01 So What.flac",
+        );
+
+        for format in [
+            PlaylistFormat::M3u,
+            PlaylistFormat::Pls,
+            PlaylistFormat::Xspf,
+        ] {
+            for relative in [false, true] {
+                let entry = PlaylistEntry::for_track(&library, format, dir, relative);
+                let parsed = parse(format, &write(format, "Jazz", std::slice::from_ref(&entry)));
+                assert_eq!(parsed, vec![entry], "{:?}", format);
+                assert_eq!(
+                    path_for(format, &parsed[0].location, dir),
+                    Some(PathBuf::from(&library.file_path))
+                );
+            }
+        }
+
+        let relative = PlaylistEntry::for_track(&library, PlaylistFormat::M3u, dir, true);
+        assert_eq!(relative.location, "../Miles & Co/01 So What.flac");
+        let relative = PlaylistEntry::for_track(&library, PlaylistFormat::Xspf, dir, true);
+        assert_eq!(relative.location, "../Miles%20&%20Co/01%20So%20What.flac");
+    }
+
+    #[test]
+    fn test_moved_files_match_by_tags() {
+        let tracks = vec![
+            track(1, "So What", "Miles Davis", 562_000, "/new/01 So What.flac"),
+            track(
+                2,
+                "So What",
+                "Miles Davis",
+                545_000,
+                "/new/live/So What.flac",
+            ),
+            track(
+                3,
+                "Blue in Green",
+                "Miles Davis",
+                337_000,
+                "/new/03 Blue in Green.flac",
+            ),
+        ];
+        let resolver = TrackResolver::new(&tracks);
+        let dir = Path::new("/old");
+
+        let entries = parse(
+            PlaylistFormat::M3u,
+            "#EXTM3U\n\
+             #EXTINF:545,Miles Davis - So What\n\
+             /old/So What.flac\n\
+             /new/live/../03 Blue in Green.flac\n\
+             Miles Davis - Blue in Green.mp3\n\
+             #EXTINF:100,Someone - Unknown\n\
+             unknown.mp3\n",
+        );
+        let matches: Vec<_> = entries
+            .iter()
+            .map(|entry| resolver.resolve(PlaylistFormat::M3u, entry, dir))
+            .collect();
+        assert_eq!(
+            matches,
+            vec![
+                EntryMatch::Fuzzy(2),
+                EntryMatch::Exact(3),
+                EntryMatch::Fuzzy(3),
+                EntryMatch::Unresolved,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_legacy_m3u_paths_keep_their_accents() {
+        let tracks = vec![track(1, "Été", "Zaz", 200_000, "/music/Café/Été.mp3")];
+        let resolver = TrackResolver::new(&tracks);
+
+        // "/music/Café/Été.mp3" and a typographic apostrophe in Windows-1252
+        let content = b"#EXTM3U\n#EXTINF:200,Zaz - \x92\xc9t\xe9\n/music/Caf\xe9/\xc9t\xe9.mp3\n";
+        let entries = parse(PlaylistFormat::M3u, &decode(content));
+        assert_eq!(entries[0].title.as_deref(), Some("\u{2019}Été"));
+        assert_eq!(
+            resolver.resolve(PlaylistFormat::M3u, &entries[0], Path::new("/music")),
+            EntryMatch::Exact(1)
+        );
+
+        let utf8 = "/music/Café/Été.mp3\n";
+        assert_eq!(decode(utf8.as_bytes()), utf8);
+    }
+}
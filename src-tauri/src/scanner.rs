@@ -1,12 +1,17 @@
 use crate::artwork::extract_and_cache_cover;
 use crate::database::DbHelper;
+use crate::loudness::{self, TrackLoudness};
 use crate::profile::get_library_db_path;
+use crate::silence::{self, TrackSilence};
+use crate::waveform::{self, Waveform};
 use lofty::config::{ParseOptions, ParsingMode};
 use lofty::file::{AudioFile, TaggedFileExt};
 use lofty::probe::Probe;
 use lofty::tag::Accessor;
 use rayon::prelude::*;
+use rusqlite::Transaction;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
@@ -15,6 +20,9 @@ use tauri::{command, AppHandle, Emitter};
 use walkdir::WalkDir;
 use log::{info, warn, error};
 
+/// Tracks written per transaction, so an interrupted pass keeps what it saved
+const BATCH_SIZE: usize = 50;
+
 /// Supported audio file extensions
 const AUDIO_EXTENSIONS: &[&str] = &[
     "mp3", "flac", "wav", "ogg", "m4a", "aac", "aiff", "wv", "opus",
@@ -41,6 +49,11 @@ pub struct TrackMetadata {
     pub bit_rate: Option<u32>,
     pub channels: Option<u8>,
     pub artwork_path: Option<String>,
+    pub loudness: TrackLoudness,
+    /// Leading and trailing silence and the threshold it was measured at,
+    /// when the scan analyzed it
+    #[serde(skip)]
+    pub silence: Option<(TrackSilence, f32)>,
 }
 
 /// Progress event emitted during scanning
@@ -91,6 +104,25 @@ fn parse_artists(artist_str: Option<&str>) -> Vec<String> {
     }
 }
 
+/// Read ReplayGain tags, falling back to the Opus R128 gain tags
+fn read_loudness_tags(tag: &lofty::tag::Tag) -> TrackLoudness {
+    use lofty::tag::ItemKey;
+
+    let gain = |key: &ItemKey| tag.get_string(key).and_then(loudness::parse_gain_db);
+    let peak = |key: &ItemKey| tag.get_string(key).and_then(loudness::parse_peak);
+    let r128 = |key: &str| {
+        tag.get_string(&ItemKey::Unknown(key.to_string()))
+            .and_then(loudness::parse_r128_gain)
+    };
+
+    TrackLoudness {
+        track_gain_db: gain(&ItemKey::ReplayGainTrackGain).or_else(|| r128("R128_TRACK_GAIN")),
+        track_peak: peak(&ItemKey::ReplayGainTrackPeak),
+        album_gain_db: gain(&ItemKey::ReplayGainAlbumGain).or_else(|| r128("R128_ALBUM_GAIN")),
+        album_peak: peak(&ItemKey::ReplayGainAlbumPeak),
+    }
+}
+
 /// Extract metadata from a single audio file
 fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, String> {
     let file_path = path.to_string_lossy().to_string();
@@ -189,6 +221,7 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
                     tag.year(),
                     tag.genre().map(|s| s.to_string()),
                     artwork_path,
+                    read_loudness_tags(tag),
                 )
             } else {
                 (
@@ -202,6 +235,7 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
                     None,
                     None,
                     None,
+                    TrackLoudness::default(),
                 )
             };
 
@@ -251,6 +285,7 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
                             None,
                             None,
                             None,
+                            TrackLoudness::default(),
                         ),
                     )
                 }
@@ -276,6 +311,7 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
                             None,
                             None,
                             None,
+                            TrackLoudness::default(),
                         ),
                     )
                 }
@@ -294,6 +330,7 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
         year,
         genre,
         artwork_path,
+        loudness,
     ) = tag_info;
 
     let final_title: Option<String> = title.or_else(|| {
@@ -321,6 +358,8 @@ fn extract_metadata(path: &Path, cache_dir: &Path) -> Result<TrackMetadata, Stri
         bit_rate,
         channels,
         artwork_path,
+        loudness,
+        silence: None,
     })
 }
 
@@ -361,8 +400,19 @@ pub fn scan_folder(path: String) -> Result<Vec<String>, String> {
     Ok(audio_files)
 }
 
+/// Where waveform overviews are cached, next to the covers
+fn waveform_cache_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(app_data_dir.join("waveforms"))
+}
+
 #[command]
-pub async fn scan_music_library(app: AppHandle, folders: Vec<String>) -> Result<ScanStats, String> {
+pub async fn scan_music_library(
+    app: AppHandle,
+    folders: Vec<String>,
+    waveforms: Option<bool>,
+    silence_threshold_db: Option<f32>,
+) -> Result<ScanStats, String> {
     let mut all_files: Vec<String> = Vec::new();
     for folder in &folders {
         match scan_folder(folder.clone()) {
@@ -381,6 +431,18 @@ pub async fn scan_music_library(app: AppHandle, folders: Vec<String>) -> Result<
 
     let app_data_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
     let cache_dir = app_data_dir.join("covers");
+    let waveform_dir = match waveforms {
+        Some(true) => Some(waveform_cache_dir(&app)?),
+        _ => None,
+    };
+
+    // Files whose silence is already known at this threshold, as path -> (size, duration)
+    let measured_silence = match silence_threshold_db {
+        Some(threshold_db) => DbHelper::new(&db_path)
+            .and_then(|db| db.get_silence_measured(threshold_db))
+            .map_err(|e| format!("Failed to read measured silence: {}", e))?,
+        None => HashMap::new(),
+    };
 
     let db_thread = std::thread::spawn(move || {
         let mut db = match DbHelper::new(&db_path) {
@@ -390,7 +452,7 @@ pub async fn scan_music_library(app: AppHandle, folders: Vec<String>) -> Result<
 
         let mut success_count = 0;
         let mut error_count = 0;
-        let mut batch = Vec::with_capacity(50);
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
 
         let process_batch = |db: &mut DbHelper, batch: &Vec<TrackMetadata>| {
             let tx = match db.get_conn_mut().transaction() {
@@ -422,7 +484,7 @@ pub async fn scan_music_library(app: AppHandle, folders: Vec<String>) -> Result<
             match result {
                 Ok(metadata) => {
                     batch.push(metadata);
-                    if batch.len() >= 50 {
+                    if batch.len() >= BATCH_SIZE {
                         let ok_count = process_batch(&mut db, &batch);
                         success_count += ok_count;
                         error_count += batch.len() - ok_count;
@@ -456,8 +518,27 @@ pub async fn scan_music_library(app: AppHandle, folders: Vec<String>) -> Result<
             },
         );
 
-        let metadata = extract_metadata(Path::new(file_path), &cache_dir)
+        let mut metadata = extract_metadata(Path::new(file_path), &cache_dir)
             .map_err(|e| format!("{}: {}", file_path, e));
+
+        // Only new or changed files are measured again
+        if let (Ok(metadata), Some(threshold_db)) = (&mut metadata, silence_threshold_db) {
+            let known = measured_silence.get(file_path)
+                == Some(&(metadata.file_size, metadata.duration_ms));
+            if !known {
+                match silence::analyze_file(file_path, threshold_db) {
+                    Ok(bounds) => metadata.silence = Some((bounds, threshold_db)),
+                    Err(e) => warn!("Failed to analyze silence of {}: {}", file_path, e),
+                }
+            }
+        }
+
+        // Unchanged files keep their cached waveform, so rescans stay cheap
+        if let (Ok(_), Some(dir)) = (&metadata, &waveform_dir) {
+            if let Err(e) = waveform::load_or_generate(dir, file_path, waveform::DEFAULT_RESOLUTION) {
+                warn!("Failed to generate waveform of {}: {}", file_path, e);
+            }
+        }
         let _ = tx.send(metadata);
     });
 
@@ -547,3 +628,146 @@ pub async fn prune_library(app: AppHandle) -> Result<ScanStats, String> {
 
     Ok(stats)
 }
+
+/// Measure the `kind` of `pending` tracks in parallel and store each result
+/// with `save`, committing every `BATCH_SIZE` tracks so an interrupted pass
+/// keeps its progress
+fn analyze_library<T: Send>(
+    app: &AppHandle,
+    db: &mut DbHelper,
+    kind: &str,
+    pending: &[(i64, String)],
+    analyze: impl Fn(&str) -> std::io::Result<T> + Sync,
+    save: impl Fn(&Transaction, i64, &T) -> rusqlite::Result<()>,
+) -> Result<ScanStats, String> {
+    let total = pending.len();
+    let progress_counter = AtomicUsize::new(0);
+    let mut success_count = 0;
+
+    for batch in pending.chunks(BATCH_SIZE) {
+        let results: Vec<Option<(i64, T)>> = batch
+            .par_iter()
+            .map(|(id, file_path)| {
+                let current = progress_counter.fetch_add(1, Ordering::SeqCst) + 1;
+                let _ = app.emit(
+                    "scan-progress",
+                    ScanProgress {
+                        current,
+                        total,
+                        current_file: file_path.clone(),
+                        status: "analyzing".to_string(),
+                    },
+                );
+
+                match analyze(file_path) {
+                    Ok(result) => Some((*id, result)),
+                    Err(e) => {
+                        warn!("Failed to analyze {} of {}: {}", kind, file_path, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let tx = db.get_conn_mut().transaction().map_err(|e| e.to_string())?;
+        for (id, result) in results.into_iter().flatten() {
+            save(&tx, id, &result).map_err(|e| e.to_string())?;
+            success_count += 1;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    let _ = app.emit(
+        "scan-progress",
+        ScanProgress {
+            current: total,
+            total,
+            current_file: String::new(),
+            status: "complete".to_string(),
+        },
+    );
+
+    Ok(ScanStats {
+        scanned_count: total,
+        success_count,
+        error_count: total - success_count,
+    })
+}
+
+/// Measure tracks that have no ReplayGain tags and derive missing album gains
+#[command]
+pub async fn analyze_loudness(app: AppHandle) -> Result<ScanStats, String> {
+    let db_path = get_library_db_path(&app)?;
+
+    let stats = std::thread::spawn(move || -> Result<ScanStats, String> {
+        let mut db = DbHelper::new(&db_path).map_err(|e| e.to_string())?;
+
+        let pending = db.get_tracks_missing_loudness().map_err(|e| e.to_string())?;
+        let stats = analyze_library(
+            &app,
+            &mut db,
+            "loudness",
+            &pending,
+            loudness::analyze_file,
+            |tx, id, analysis| {
+                DbHelper::set_track_loudness(tx, id, analysis.gain_db, analysis.true_peak)
+            },
+        )?;
+
+        let tx = db.get_conn_mut().transaction().map_err(|e| e.to_string())?;
+        let album_count = DbHelper::update_album_loudness(&tx).map_err(|e| e.to_string())?;
+        if album_count > 0 {
+            info!("Computed loudness for {} albums", album_count);
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+
+        Ok(stats)
+    })
+    .join()
+    .map_err(|_| "Thread panicked".to_string())??;
+
+    Ok(stats)
+}
+
+/// Measure leading and trailing silence of tracks not yet analyzed at `threshold_db`
+#[command]
+pub async fn analyze_silence(app: AppHandle, threshold_db: Option<f32>) -> Result<ScanStats, String> {
+    let db_path = get_library_db_path(&app)?;
+    let threshold_db = threshold_db.unwrap_or(silence::DEFAULT_THRESHOLD_DB);
+
+    std::thread::spawn(move || -> Result<ScanStats, String> {
+        let mut db = DbHelper::new(&db_path).map_err(|e| e.to_string())?;
+
+        let pending = db
+            .get_tracks_missing_silence(threshold_db)
+            .map_err(|e| e.to_string())?;
+        analyze_library(
+            &app,
+            &mut db,
+            "silence",
+            &pending,
+            |path| silence::analyze_file(path, threshold_db),
+            |tx, id, bounds| DbHelper::set_track_silence(tx, id, bounds, threshold_db),
+        )
+    })
+    .join()
+    .map_err(|_| "Thread panicked".to_string())?
+}
+
+/// Waveform overview of a track, generated on first request and cached until the file changes
+#[command]
+pub async fn get_waveform(
+    app: AppHandle,
+    path: String,
+    resolution: Option<usize>,
+) -> Result<Waveform, String> {
+    let cache_dir = waveform_cache_dir(&app)?;
+    let resolution = resolution.unwrap_or(waveform::DEFAULT_RESOLUTION);
+
+    std::thread::spawn(move || {
+        waveform::load_or_generate(&cache_dir, &path, resolution)
+            .map_err(|e| format!("Failed to generate waveform of {}: {}", path, e))
+    })
+    .join()
+    .map_err(|_| "Thread panicked".to_string())?
+}
@@ -0,0 +1,150 @@
+//! Full-text search over the library
+//!
+//! Tracks are indexed in the `tracks_fts` FTS5 table, which `DbHelper`
+//! updates whenever a track is written or deleted. Albums are found through
+//! the tracks that carry them; artists and playlists have indexes of their own.
+
+use serde::Serialize;
+
+use crate::library::LibraryTrack;
+
+/// Marks the start of a matched term in highlighted text. Control characters
+/// cannot clash with tag contents, unlike HTML markup.
+pub const HIGHLIGHT_START: &str = "\u{2}";
+pub const HIGHLIGHT_END: &str = "\u{3}";
+
+/// Values `tracks_fts` holds for the track with id `?`
+pub const TRACK_INDEX_SELECT: &str = "SELECT
+        t.id,
+        t.title,
+        (SELECT group_concat(name, ' ') FROM (
+            SELECT name FROM artists WHERE id = t.artist_id
+            UNION
+            SELECT a.name FROM track_artists ta JOIN artists a ON a.id = ta.artist_id
+            WHERE ta.track_id = t.id
+        )),
+        al.title,
+        t.album_artist,
+        t.genre
+    FROM tracks t
+    LEFT JOIN albums al ON t.album_id = al.id
+    WHERE t.id = ?";
+
+#[derive(Debug, Serialize)]
+pub struct TrackHit {
+    #[serde(flatten)]
+    pub track: LibraryTrack,
+    /// Title with matched terms marked
+    pub title_highlight: String,
+    /// Excerpt of whichever field matched best, with matched terms marked
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AlbumHit {
+    pub id: i64,
+    pub title: String,
+    pub artist_name: Option<String>,
+    pub artwork_path: Option<String>,
+    pub highlight: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtistHit {
+    pub id: i64,
+    pub name: String,
+    pub track_count: i64,
+    pub highlight: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistHit {
+    pub id: i64,
+    pub name: String,
+    pub artwork_path: Option<String>,
+    pub highlight: String,
+}
+
+/// Matches of each kind, best first
+#[derive(Debug, Default, Serialize)]
+pub struct SearchResults {
+    pub tracks: Vec<TrackHit>,
+    pub albums: Vec<AlbumHit>,
+    pub artists: Vec<ArtistHit>,
+    pub playlists: Vec<PlaylistHit>,
+}
+
+/// Turn what the user typed into an FTS5 query where every word has to match
+/// the start of a word. `None` if nothing searchable is left.
+///
+/// Words are quoted so characters that mean something to FTS5 are searched
+/// for literally instead of breaking the query.
+pub fn match_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DbHelper;
+
+    #[test]
+    fn test_search_matches_prefixes_without_accents() {
+        assert_eq!(match_query("  "), None);
+        assert_eq!(
+            match_query("so wh\"at -"),
+            Some("\"so\"* \"what\"*".to_string())
+        );
+
+        let mut db = DbHelper::new(":memory:").unwrap();
+        let tx = db.get_conn_mut().transaction().unwrap();
+        tx.execute_batch(
+            "INSERT INTO artists (id, name) VALUES (1, 'Beyoncé'), (2, 'Miles Davis'), (3, 'Bill Evans');
+            INSERT INTO albums (id, title, artist_id) VALUES (1, 'Lemonade', 1), (2, 'Kind of Blue', 2);
+            INSERT INTO tracks (id, title, artist_id, album_id, file_path, genre) VALUES
+                (1, 'Formation', 1, 1, '/1.flac', 'Pop'),
+                (2, 'So What', 2, 2, '/2.flac', 'Jazz'),
+                (3, 'Blue in Green', 2, 2, '/3.flac', 'Jazz');
+            INSERT INTO track_artists (track_id, artist_id, role) VALUES
+                (2, 2, 'main'),
+                (3, 3, 'featured');
+            INSERT INTO playlists (name, description) VALUES ('Sunday Blues', 'slow jazz');",
+        )
+        .unwrap();
+        for id in 1..=3 {
+            DbHelper::index_track(&tx, id).unwrap();
+        }
+        tx.commit().unwrap();
+
+        let results = db.search_library("beyon", 10).unwrap();
+        assert_eq!(results.tracks.len(), 1);
+        assert_eq!(results.artists[0].name, "Beyoncé");
+        assert_eq!(results.artists[0].highlight, "\u{2}Beyoncé\u{3}");
+
+        // Credits count once, and credited-only artists count their tracks
+        let results = db.search_library("miles", 10).unwrap();
+        assert_eq!(results.artists[0].track_count, 2);
+        let results = db.search_library("evans", 10).unwrap();
+        assert_eq!(results.artists[0].name, "Bill Evans");
+        assert_eq!(results.artists[0].track_count, 1);
+
+        // Title matches outrank album matches
+        let results = db.search_library("blue", 10).unwrap();
+        let ids: Vec<i64> = results.tracks.iter().map(|hit| hit.track.id).collect();
+        assert_eq!(ids, vec![3, 2]);
+        assert_eq!(results.albums[0].title, "Kind of Blue");
+        assert_eq!(results.playlists[0].name, "Sunday Blues");
+        assert_eq!(results.tracks[0].title_highlight, "\u{2}Blue\u{3} in Green");
+
+        let tx = db.get_conn_mut().transaction().unwrap();
+        DbHelper::delete_tracks(&tx, &[3]).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(db.search_library("green", 10).unwrap().tracks.len(), 0);
+    }
+}
@@ -0,0 +1,120 @@
+//! Leading and trailing silence detection
+//!
+//! A track is split into short windows; a window counts as audible when any
+//! sample in it rises above the threshold. Everything before the first and
+//! after the last audible window is silence that playback may skip.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::audio::decoder;
+
+pub const DEFAULT_THRESHOLD_DB: f32 = -60.0;
+
+/// Length of the windows a track is split into
+const WINDOW_MS: u64 = 10;
+
+/// Windows are timed in milliseconds, so the rate only sets their precision
+const ANALYSIS_SAMPLE_RATE: u32 = 44100;
+const ANALYSIS_CHANNELS: u16 = 2;
+
+/// Audible region of a track, as stored in the library
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TrackSilence {
+    /// Where the leading silence ends
+    pub silence_start_ms: Option<u64>,
+    /// Where the trailing silence begins
+    pub silence_end_ms: Option<u64>,
+}
+
+pub struct SilenceDetector {
+    threshold: f32,
+    window_len: usize, // Samples per window, all channels
+    window_pos: usize,
+    window_loud: bool,
+    windows: u64,
+    first_loud: Option<u64>,
+    last_loud: u64,
+}
+
+impl SilenceDetector {
+    pub fn new(sample_rate: u32, channels: u16, threshold_db: f32) -> Self {
+        let frames = (sample_rate as u64 * WINDOW_MS / 1000).max(1) as usize;
+        Self {
+            threshold: 10f32.powf(threshold_db / 20.0),
+            window_len: frames * channels.max(1) as usize,
+            window_pos: 0,
+            window_loud: false,
+            windows: 0,
+            first_loud: None,
+            last_loud: 0,
+        }
+    }
+
+    pub fn process(&mut self, samples: &[f32]) {
+        for sample in samples {
+            self.window_loud |= sample.abs() > self.threshold;
+            self.window_pos += 1;
+            if self.window_pos == self.window_len {
+                self.finish_window();
+            }
+        }
+    }
+
+    fn finish_window(&mut self) {
+        if self.window_loud {
+            self.first_loud.get_or_insert(self.windows);
+            self.last_loud = self.windows;
+        }
+        self.windows += 1;
+        self.window_pos = 0;
+        self.window_loud = false;
+    }
+
+    /// Audible region in milliseconds, or `None` if nothing rose above the threshold
+    pub fn finish(mut self) -> Option<TrackSilence> {
+        if self.window_pos > 0 {
+            self.finish_window();
+        }
+        let first = self.first_loud?;
+        Some(TrackSilence {
+            silence_start_ms: Some(first * WINDOW_MS),
+            silence_end_ms: Some((self.last_loud + 1) * WINDOW_MS),
+        })
+    }
+}
+
+/// Decode a file and find its audible region
+pub fn analyze_file(path: &str, threshold_db: f32) -> io::Result<TrackSilence> {
+    let mut detector = SilenceDetector::new(ANALYSIS_SAMPLE_RATE, ANALYSIS_CHANNELS, threshold_db);
+    decoder::decode_file(path, ANALYSIS_SAMPLE_RATE, ANALYSIS_CHANNELS, |samples| {
+        detector.process(samples)
+    })?;
+
+    // A silent track is stored as having no trim so it is not analyzed again
+    Ok(detector.finish().unwrap_or(TrackSilence {
+        silence_start_ms: Some(0),
+        silence_end_ms: None,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_leading_and_trailing_silence() {
+        let mut detector = SilenceDetector::new(1000, 1, DEFAULT_THRESHOLD_DB);
+        let mut samples = vec![0.0005f32; 1500]; // -66 dBFS hiss
+        samples.extend(vec![0.5f32; 2000]);
+        samples.extend(vec![0.0f32; 1000]);
+        detector.process(&samples);
+
+        let silence = detector.finish().unwrap();
+        assert_eq!(silence.silence_start_ms, Some(1500));
+        assert_eq!(silence.silence_end_ms, Some(3500));
+
+        let silent = SilenceDetector::new(1000, 1, DEFAULT_THRESHOLD_DB);
+        assert!(silent.finish().is_none());
+    }
+}
@@ -0,0 +1,346 @@
+//! Rules of smart playlists and their translation to SQL
+//!
+//! A smart playlist has no stored members. Every time its tracks are asked
+//! for, `DbHelper` runs the query built here, so the result always reflects
+//! the library as it is after the latest scan.
+
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+
+/// Plays and skips per track, joined as `ps` for the history fields
+const PLAY_STATS_JOIN: &str = "LEFT JOIN (
+        SELECT
+            track_id,
+            SUM(CASE WHEN completed THEN 1 ELSE 0 END) as play_count,
+            SUM(CASE WHEN completed THEN 0 ELSE 1 END) as skip_count,
+            MAX(played_at) as last_played_at
+        FROM play_history
+        GROUP BY track_id
+    ) ps ON ps.track_id = t.id";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleField {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Genre,
+    FileFormat,
+    Year,
+    DurationMs,
+    PlayCount,
+    SkipCount,
+    DateAdded,
+    LastPlayed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FieldKind {
+    Text,
+    Number,
+    Date,
+}
+
+impl RuleField {
+    fn column(self) -> &'static str {
+        match self {
+            Self::Title => "t.title",
+            Self::Artist => "ar.name",
+            Self::Album => "al.title",
+            Self::AlbumArtist => "t.album_artist",
+            Self::Genre => "t.genre",
+            Self::FileFormat => "t.file_format",
+            Self::Year => "t.year",
+            Self::DurationMs => "t.duration_ms",
+            Self::PlayCount => "COALESCE(ps.play_count, 0)",
+            Self::SkipCount => "COALESCE(ps.skip_count, 0)",
+            Self::DateAdded => "t.created_at",
+            Self::LastPlayed => "ps.last_played_at",
+        }
+    }
+
+    fn kind(self) -> FieldKind {
+        match self {
+            Self::Title
+            | Self::Artist
+            | Self::Album
+            | Self::AlbumArtist
+            | Self::Genre
+            | Self::FileFormat => FieldKind::Text,
+            Self::Year | Self::DurationMs | Self::PlayCount | Self::SkipCount => FieldKind::Number,
+            Self::DateAdded | Self::LastPlayed => FieldKind::Date,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleOp {
+    Is,
+    IsNot,
+    Contains,
+    NotContains,
+    StartsWith,
+    GreaterThan,
+    LessThan,
+    /// Within the last `value` days
+    InLastDays,
+    /// Not within the last `value` days, including never
+    NotInLastDays,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum RuleValue {
+    Number(f64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    pub field: RuleField,
+    pub op: RuleOp,
+    pub value: RuleValue,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Every rule has to match
+    #[default]
+    All,
+    /// One matching rule is enough
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleSort {
+    pub field: RuleField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SmartRules {
+    #[serde(default, rename = "match")]
+    pub match_mode: MatchMode,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    pub sort: Option<RuleSort>,
+    pub limit: Option<u32>,
+}
+
+/// A statement selecting the tracks of a smart playlist, with its parameters
+pub struct SmartQuery {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl Rule {
+    fn to_sql(&self, params: &mut Vec<Value>) -> Result<String, String> {
+        let column = self.field.column();
+        let invalid = || format!("{:?} cannot be used with {:?}", self.op, self.field);
+
+        match (self.field.kind(), self.op, &self.value) {
+            (FieldKind::Text, op, RuleValue::Text(text)) => {
+                let (sql, param) = match op {
+                    RuleOp::Is => (format!("{} = ? COLLATE NOCASE", column), text.clone()),
+                    RuleOp::IsNot => (
+                        format!("COALESCE({}, '') <> ? COLLATE NOCASE", column),
+                        text.clone(),
+                    ),
+                    RuleOp::Contains => (
+                        format!("{} LIKE ? ESCAPE '\\'", column),
+                        format!("%{}%", escape_like(text)),
+                    ),
+                    RuleOp::NotContains => (
+                        format!("COALESCE({}, '') NOT LIKE ? ESCAPE '\\'", column),
+                        format!("%{}%", escape_like(text)),
+                    ),
+                    RuleOp::StartsWith => (
+                        format!("{} LIKE ? ESCAPE '\\'", column),
+                        format!("{}%", escape_like(text)),
+                    ),
+                    _ => return Err(invalid()),
+                };
+                params.push(Value::Text(param));
+                Ok(sql)
+            }
+            (FieldKind::Number, op, RuleValue::Number(number)) => {
+                let sql = match op {
+                    RuleOp::Is => format!("{} = ?", column),
+                    RuleOp::IsNot => format!("({} IS NULL OR {} <> ?)", column, column),
+                    RuleOp::GreaterThan => format!("{} > ?", column),
+                    RuleOp::LessThan => format!("{} < ?", column),
+                    _ => return Err(invalid()),
+                };
+                params.push(Value::Real(*number));
+                Ok(sql)
+            }
+            (FieldKind::Date, op, RuleValue::Number(days)) if days.is_finite() => {
+                let sql = match op {
+                    RuleOp::InLastDays => format!("{} >= datetime('now', ?)", column),
+                    RuleOp::NotInLastDays => {
+                        format!("({} IS NULL OR {} < datetime('now', ?))", column, column)
+                    }
+                    _ => return Err(invalid()),
+                };
+                params.push(Value::Text(format!("-{} days", days.max(0.0))));
+                Ok(sql)
+            }
+            _ => Err(format!(
+                "Invalid value for {:?}: {:?}",
+                self.field, self.value
+            )),
+        }
+    }
+}
+
+impl SmartRules {
+    /// Check that every rule can be evaluated, before the rules are saved
+    pub fn validate(&self) -> Result<(), String> {
+        self.to_sql("t.id").map(|_| ())
+    }
+
+    /// Build the query selecting `columns` of the matching tracks. Tracks are
+    /// joined as `t`, their artist as `ar` and their album as `al`.
+    pub fn to_sql(&self, columns: &str) -> Result<SmartQuery, String> {
+        let mut params = Vec::new();
+        let mut conditions = Vec::new();
+        for rule in &self.rules {
+            conditions.push(rule.to_sql(&mut params)?);
+        }
+
+        let mut sql = format!(
+            "SELECT {}
+            FROM tracks t
+            LEFT JOIN artists ar ON t.artist_id = ar.id
+            LEFT JOIN albums al ON t.album_id = al.id
+            {}",
+            columns, PLAY_STATS_JOIN
+        );
+
+        // Without rules the playlist holds the whole library
+        if !conditions.is_empty() {
+            let joiner = match self.match_mode {
+                MatchMode::All => " AND ",
+                MatchMode::Any => " OR ",
+            };
+            sql.push_str(&format!(" WHERE ({})", conditions.join(joiner)));
+        }
+
+        match self.sort {
+            Some(sort) => sql.push_str(&format!(
+                " ORDER BY {} {}, t.id ASC",
+                sort.field.column(),
+                if sort.descending { "DESC" } else { "ASC" }
+            )),
+            None => sql.push_str(
+                " ORDER BY ar.name ASC, al.title ASC, t.disc_number ASC, t.track_number ASC, t.title ASC",
+            ),
+        }
+
+        if let Some(limit) = self.limit {
+            sql.push_str(" LIMIT ?");
+            params.push(Value::Integer(limit as i64));
+        }
+
+        Ok(SmartQuery { sql, params })
+    }
+}
+
+pub(crate) fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::DbHelper;
+
+    fn rule(field: RuleField, op: RuleOp, value: RuleValue) -> Rule {
+        Rule { field, op, value }
+    }
+
+    #[test]
+    fn test_rules_select_matching_tracks() {
+        let mut db = DbHelper::new(":memory:").unwrap();
+        db.get_conn_mut()
+            .execute_batch(
+                "INSERT INTO tracks (id, title, file_path, genre, year, created_at) VALUES
+                    (1, 'So What', '/a.flac', 'Jazz', 1959, datetime('now', '-2 days')),
+                    (2, 'Giant Steps', '/b.flac', 'jazz', 1960, datetime('now', '-90 days')),
+                    (3, 'Windowlicker', '/c.flac', 'Electronic', 1999, datetime('now', '-1 days')),
+                    (4, 'Footprints', '/d.flac', 'Jazz', 1974, datetime('now', '-400 days'));
+                INSERT INTO play_history (track_id, completed) VALUES
+                    (2, 1), (2, 1), (2, 0), (4, 1);",
+            )
+            .unwrap();
+
+        let ids = |rules: &SmartRules| -> Vec<i64> {
+            db.get_smart_playlist_tracks(rules)
+                .unwrap()
+                .iter()
+                .map(|t| t.id)
+                .collect()
+        };
+
+        // genre = Jazz AND year < 1970
+        let jazz = SmartRules {
+            rules: vec![
+                rule(RuleField::Genre, RuleOp::Is, RuleValue::Text("Jazz".into())),
+                rule(RuleField::Year, RuleOp::LessThan, RuleValue::Number(1970.0)),
+            ],
+            sort: Some(RuleSort {
+                field: RuleField::Year,
+                descending: true,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(ids(&jazz), vec![2, 1]);
+
+        // Added in the last 30 days
+        let recent = SmartRules {
+            rules: vec![rule(
+                RuleField::DateAdded,
+                RuleOp::InLastDays,
+                RuleValue::Number(30.0),
+            )],
+            sort: Some(RuleSort {
+                field: RuleField::DateAdded,
+                descending: false,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(ids(&recent), vec![1, 3]);
+
+        // Played at least once, most played first, limited to one
+        let played = SmartRules {
+            match_mode: MatchMode::Any,
+            rules: vec![rule(
+                RuleField::PlayCount,
+                RuleOp::GreaterThan,
+                RuleValue::Number(0.0),
+            )],
+            sort: Some(RuleSort {
+                field: RuleField::PlayCount,
+                descending: true,
+            }),
+            limit: Some(1),
+        };
+        assert_eq!(ids(&played), vec![2]);
+
+        let invalid = SmartRules {
+            rules: vec![rule(
+                RuleField::Year,
+                RuleOp::Contains,
+                RuleValue::Number(19.0),
+            )],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! Waveform overviews for the seek bar
+//!
+//! A track is decoded once into short min/max blocks, which are then reduced
+//! to the requested number of peak pairs. Results are cached as JSON next to
+//! the cover cache, one file per track and resolution. Each entry records the
+//! size and modification time of the file it came from, so an edited or
+//! replaced file is analyzed again instead of serving a stale waveform.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::audio::decoder;
+
+pub const DEFAULT_RESOLUTION: usize = 1000;
+const MAX_RESOLUTION: usize = 10000;
+
+/// Peaks survive a mono downmix at a low rate well enough for drawing
+const ANALYSIS_SAMPLE_RATE: u32 = 22050;
+const ANALYSIS_CHANNELS: u16 = 1;
+
+/// Frames per block measured while decoding, 10 ms at the analysis rate
+const BLOCK_FRAMES: usize = 220;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Waveform {
+    pub duration_ms: u64,
+    /// Minimum and maximum sample of each slice of the track, -1..1
+    pub peaks: Vec<[f32; 2]>,
+}
+
+/// What a cache file holds
+#[derive(Serialize, Deserialize)]
+struct CachedWaveform {
+    file_size: u64,
+    modified_ms: u64,
+    waveform: Waveform,
+}
+
+/// Collects min/max blocks from decoded samples
+#[derive(Default)]
+pub struct WaveformBuilder {
+    blocks: Vec<[f32; 2]>,
+    current: [f32; 2],
+    block_pos: usize,
+    frames: u64,
+}
+
+impl WaveformBuilder {
+    /// Feed mono samples at the analysis rate
+    pub fn process(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            self.current[0] = self.current[0].min(sample);
+            self.current[1] = self.current[1].max(sample);
+            self.block_pos += 1;
+            if self.block_pos == BLOCK_FRAMES {
+                self.finish_block();
+            }
+        }
+        self.frames += samples.len() as u64;
+    }
+
+    fn finish_block(&mut self) {
+        self.blocks.push(self.current);
+        self.current = [0.0, 0.0];
+        self.block_pos = 0;
+    }
+
+    /// Reduce the blocks to `resolution` peak pairs spread over the track
+    pub fn finish(mut self, resolution: usize) -> Waveform {
+        if self.block_pos > 0 {
+            self.finish_block();
+        }
+        let resolution = resolution.clamp(1, MAX_RESOLUTION);
+        let blocks = self.blocks.len();
+
+        let peaks = (0..resolution)
+            .map(|i| {
+                let start = i * blocks / resolution;
+                // Very short tracks have fewer blocks than slices; repeat them
+                let end = ((i + 1) * blocks / resolution).max(start + 1).min(blocks);
+                self.blocks[start.min(end)..end]
+                    .iter()
+                    .fold([0.0f32, 0.0f32], |acc, block| {
+                        [acc[0].min(block[0]), acc[1].max(block[1])]
+                    })
+            })
+            .collect();
+
+        Waveform {
+            duration_ms: self.frames * 1000 / ANALYSIS_SAMPLE_RATE as u64,
+            peaks,
+        }
+    }
+}
+
+/// Decode a file and compute its waveform
+pub fn generate(path: &str, resolution: usize) -> io::Result<Waveform> {
+    let mut builder = WaveformBuilder::default();
+    decoder::decode_file(path, ANALYSIS_SAMPLE_RATE, ANALYSIS_CHANNELS, |samples| {
+        builder.process(samples)
+    })?;
+
+    Ok(builder.finish(resolution))
+}
+
+/// Cached waveform of `path`, generated first if missing or stale
+pub fn load_or_generate(cache_dir: &Path, path: &str, resolution: usize) -> io::Result<Waveform> {
+    let resolution = resolution.clamp(1, MAX_RESOLUTION);
+    let (file_size, modified_ms) = file_stamp(path)?;
+    let cache_path = cache_path(cache_dir, path, resolution);
+
+    if let Ok(data) = fs::read(&cache_path) {
+        match serde_json::from_slice::<CachedWaveform>(&data) {
+            Ok(cached) if cached.file_size == file_size && cached.modified_ms == modified_ms => {
+                return Ok(cached.waveform);
+            }
+            _ => {} // Stale or unreadable, overwritten below
+        }
+    }
+
+    let waveform = generate(path, resolution)?;
+    let cached = CachedWaveform {
+        file_size,
+        modified_ms,
+        waveform,
+    };
+
+    fs::create_dir_all(cache_dir)?;
+    // Write to a temp file and rename so readers never see a partial entry
+    let temp_path = cache_path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp_path, serde_json::to_vec(&cached)?)?;
+    if let Err(e) = fs::rename(&temp_path, &cache_path) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    Ok(cached.waveform)
+}
+
+/// Size and modification time identifying the current contents of a file
+fn file_stamp(path: &str) -> io::Result<(u64, u64)> {
+    let metadata = fs::metadata(path)?;
+    let modified_ms = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Ok((metadata.len(), modified_ms))
+}
+
+fn cache_path(cache_dir: &Path, path: &str, resolution: usize) -> PathBuf {
+    let mut hasher = Sha256::new();
+    hasher.update(path.as_bytes());
+    cache_dir.join(format!("{:x}_{}.json", hasher.finalize(), resolution))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blocks_reduce_to_min_max_pairs() {
+        let mut builder = WaveformBuilder::default();
+        // One second: a quiet first half and a loud, lopsided second half
+        let mut samples = vec![0.1f32; 11025];
+        samples.extend((0..11025).map(|i| if i % 2 == 0 { 0.9 } else { -0.4 }));
+        builder.process(&samples);
+
+        let waveform = builder.finish(4);
+        assert_eq!(waveform.duration_ms, 1000);
+        assert_eq!(waveform.peaks.len(), 4);
+        assert_eq!(waveform.peaks[0], [0.0, 0.1]);
+        assert_eq!(waveform.peaks[3], [-0.4, 0.9]);
+
+        // More slices than blocks still fills every slice
+        let mut short = WaveformBuilder::default();
+        short.process(&[0.5; 100]);
+        let waveform = short.finish(8);
+        assert!(waveform.peaks.iter().all(|peak| *peak == [0.0, 0.5]));
+    }
+}
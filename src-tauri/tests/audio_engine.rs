@@ -0,0 +1,485 @@
+//! End-to-end tests of the audio worker, rendered through the offline sinks
+
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use vibemusic_lib::audio::ab_loop::AbLoop;
+use vibemusic_lib::audio::channel_mix::ChannelMix;
+use vibemusic_lib::audio::crossfade::{CrossfadeSettings, FadeCurve};
+use vibemusic_lib::audio::events::EventSink;
+use vibemusic_lib::audio::queue::QueueItem;
+use vibemusic_lib::audio::sink::{NullSink, OutputSink, StreamFormat, WavSink};
+use vibemusic_lib::audio::sleep_timer::SleepTimerMode;
+use vibemusic_lib::audio::visualizer::VisualizerSettings;
+use vibemusic_lib::audio::{
+    AudioEngine, EVENT_PLAYBACK_FINISHED, EVENT_PLAYBACK_PROGRESS, EVENT_SLEEP_TIMER,
+    EVENT_TRACK_CHANGED, EVENT_VISUALIZATION,
+};
+
+const FORMAT: StreamFormat = StreamFormat {
+    sample_rate: 44100,
+    channels: 2,
+};
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Default)]
+struct RecordedEvents {
+    events: Mutex<Vec<(String, Value)>>,
+    changed: Condvar,
+}
+
+impl EventSink for RecordedEvents {
+    fn emit(&self, event: &str, payload: Value) {
+        self.events
+            .lock()
+            .unwrap()
+            .push((event.to_string(), payload));
+        self.changed.notify_all();
+    }
+}
+
+impl RecordedEvents {
+    fn wait_for(&self, event: &str) -> Value {
+        self.wait_for_matching(event, |_| true)
+    }
+
+    fn wait_for_matching(&self, event: &str, matches: impl Fn(&Value) -> bool) -> Value {
+        self.wait_for_after(None, event, matches).1
+    }
+
+    /// Index and payload of the first matching event recorded after index `after`
+    fn wait_for_after(
+        &self,
+        after: Option<usize>,
+        event: &str,
+        matches: impl Fn(&Value) -> bool,
+    ) -> (usize, Value) {
+        let start = after.map_or(0, |index| index + 1);
+        let found = |events: &Vec<(String, Value)>| {
+            events
+                .iter()
+                .enumerate()
+                .skip(start)
+                .find(|(_, (name, payload))| name == event && matches(payload))
+                .map(|(index, (_, payload))| (index, payload.clone()))
+        };
+        let events = self.events.lock().unwrap();
+        let (events, _) = self
+            .changed
+            .wait_timeout_while(events, TIMEOUT, |events| found(events).is_none())
+            .unwrap();
+        found(&events).unwrap_or_else(|| panic!("timed out waiting for {}", event))
+    }
+
+    /// Wait until playback has reached `ms` into the current track
+    fn wait_for_position(&self, ms: u64) {
+        self.wait_for_matching(EVENT_PLAYBACK_PROGRESS, |state| position_ms(state) >= ms);
+    }
+}
+
+/// Position reported by a progress event
+fn position_ms(state: &Value) -> u64 {
+    state["position_ms"].as_u64().unwrap_or(0)
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("vibemusic-{}-{}", std::process::id(), name))
+}
+
+/// Write a 16-bit stereo WAV whose left and right channels are `sample(frame)`
+fn write_input(name: &str, seconds: f32, sample: impl Fn(usize, usize) -> f32) -> PathBuf {
+    let path = temp_path(name);
+    let frames = (FORMAT.sample_rate as f32 * seconds) as usize;
+    let data_bytes = (frames * 4) as u32;
+
+    let mut bytes = Vec::with_capacity(44 + data_bytes as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_bytes).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&FORMAT.sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(FORMAT.sample_rate * 4).to_le_bytes());
+    bytes.extend_from_slice(&4u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_bytes.to_le_bytes());
+    for frame in 0..frames {
+        let value = (sample(frame, frames) * i16::MAX as f32) as i16;
+        bytes.extend_from_slice(&value.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+/// Samples recorded by a `WavSink`
+fn read_output(path: &Path) -> Vec<f32> {
+    let bytes = fs::read(path).unwrap();
+    assert_eq!(&bytes[..4], b"RIFF");
+    bytes[44..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
+fn track(path: &Path) -> QueueItem {
+    QueueItem {
+        id: None,
+        title: "Test".into(),
+        artist: None,
+        album: Some("Test Album".into()),
+        duration_ms: 0,
+        file_path: path.to_string_lossy().into_owned(),
+        artwork_path: None,
+        loudness: Default::default(),
+        silence: Default::default(),
+    }
+}
+
+fn wav_engine(output: &Path) -> (AudioEngine, Arc<RecordedEvents>) {
+    let events = Arc::new(RecordedEvents::default());
+    let output = output.to_path_buf();
+    let engine = AudioEngine::headless(
+        move || Box::new(WavSink::create(output, FORMAT).unwrap()) as Box<dyn OutputSink>,
+        events.clone(),
+    );
+    (engine, events)
+}
+
+fn cleanup(paths: &[&Path]) {
+    for path in paths {
+        fs::remove_file(path).ok();
+    }
+}
+
+#[test]
+fn test_end_of_track_plays_out_and_finishes() {
+    let input = write_input("eof.wav", 0.3, |_, _| 0.5);
+    let events = Arc::new(RecordedEvents::default());
+    let engine = AudioEngine::headless(|| Box::new(NullSink::new(FORMAT)), events.clone());
+
+    engine.play(track(&input));
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let state = engine.get_state();
+    assert!(!state.is_playing);
+    assert_eq!(state.current_file, None);
+    cleanup(&[&input]);
+}
+
+#[test]
+fn test_volume_scales_output() {
+    let input = write_input("volume-in.wav", 0.3, |_, _| 0.5);
+    let output = temp_path("volume-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.set_volume(0.5);
+    engine.play(track(&input));
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let audible: Vec<f32> = read_output(&output)
+        .into_iter()
+        .filter(|s| *s != 0.0)
+        .collect();
+    // The whole track reached the output, not just what was decoded before EOF
+    assert!(
+        audible.len() >= (0.3 * 44100.0 * 2.0) as usize,
+        "{}",
+        audible.len()
+    );
+    assert!(audible.iter().all(|s| (s - 0.25).abs() < 0.001));
+    cleanup(&[&input, &output]);
+}
+
+#[test]
+fn test_balance_attenuates_one_channel() {
+    let input = write_input("balance-in.wav", 0.3, |_, _| 0.5);
+    let output = temp_path("balance-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.set_channel_mix(ChannelMix::new(0.5, false, false));
+    engine.play(track(&input));
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    let frames: Vec<&[f32]> = samples
+        .chunks_exact(2)
+        .filter(|frame| frame[1] != 0.0)
+        .collect();
+    assert!(frames.len() >= (0.3 * 44100.0) as usize, "{}", frames.len());
+    assert!(frames
+        .iter()
+        .all(|frame| (frame[0] - 0.25).abs() < 0.001 && (frame[1] - 0.5).abs() < 0.001));
+    cleanup(&[&input, &output]);
+}
+
+#[test]
+fn test_seek_skips_to_position() {
+    // A ramp from 0 to 0.8 over two seconds, so each value maps to a time
+    let input = write_input("seek-in.wav", 2.0, |frame, frames| {
+        0.8 * frame as f32 / frames as f32
+    });
+    let output = temp_path("seek-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.play(track(&input));
+    events.wait_for_position(200);
+    engine.seek(1500);
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(*s));
+    assert!(peak > 0.79, "track did not play to the end: {}", peak);
+
+    // 0.5 s to 1.375 s was skipped; only the short post-seek fade passes through
+    let skipped = samples.iter().filter(|s| (0.2..0.55).contains(*s)).count();
+    assert!(skipped < 1000, "{} samples from the skipped range", skipped);
+    cleanup(&[&input, &output]);
+}
+
+#[test]
+fn test_crossfade_mixes_outgoing_and_incoming() {
+    let first = write_input("fade-a.wav", 2.0, |_, _| 0.6);
+    let second = write_input("fade-b.wav", 1.0, |_, _| 0.2);
+    let output = temp_path("fade-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.set_crossfade(CrossfadeSettings {
+        duration_ms: 300,
+        ..Default::default()
+    });
+    engine.play(track(&first));
+    events.wait_for_position(300);
+    engine.play(track(&second));
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    let mixed = samples.iter().filter(|s| (0.25..0.55).contains(*s)).count();
+    assert!(mixed > 5000, "only {} mixed samples", mixed);
+
+    let last = samples.iter().rev().find(|s| **s != 0.0).unwrap();
+    assert!((last - 0.2).abs() < 0.001, "ended on {}", last);
+    cleanup(&[&first, &second, &output]);
+}
+
+/// Play two queued tracks through to the end and return the mixed output
+fn play_queue_with_crossfade(name: &str, crossfade: CrossfadeSettings) -> (Vec<f32>, Value) {
+    let first = write_input(&format!("{}-a.wav", name), 1.0, |_, _| 0.6);
+    let second = write_input(&format!("{}-b.wav", name), 1.0, |_, _| 0.2);
+    let output = temp_path(&format!("{}-out.wav", name));
+    let (engine, events) = wav_engine(&output);
+
+    engine.set_crossfade(crossfade);
+    engine.set_queue(vec![track(&first), track(&second)], 0);
+    let change = events.wait_for(EVENT_TRACK_CHANGED);
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    cleanup(&[&first, &second, &output]);
+    (samples, change)
+}
+
+#[test]
+fn test_automatic_crossfade_before_track_end() {
+    let (samples, change) = play_queue_with_crossfade(
+        "auto-fade",
+        CrossfadeSettings {
+            duration_ms: 300,
+            curve: FadeCurve::EqualPower,
+            skip_same_album: false,
+        },
+    );
+    assert!(change["path"]
+        .as_str()
+        .unwrap()
+        .ends_with("auto-fade-b.wav"));
+
+    // Equal power overshoots the louder track while both are up
+    let mixed = samples
+        .iter()
+        .filter(|s| **s > 0.61 || (0.21..0.59).contains(*s))
+        .count();
+    assert!(mixed > 5000, "only {} mixed samples", mixed);
+
+    // The 300 ms overlap shortens the two seconds of audio
+    let audible = samples.iter().filter(|s| **s != 0.0).count();
+    let two_seconds = 2 * 44100 * 2;
+    assert!(audible < two_seconds - 20000, "{} audible samples", audible);
+}
+
+#[test]
+fn test_same_album_tracks_stay_gapless() {
+    let (samples, _) = play_queue_with_crossfade(
+        "album-fade",
+        CrossfadeSettings {
+            duration_ms: 300,
+            curve: FadeCurve::Linear,
+            skip_same_album: true,
+        },
+    );
+
+    let mixed = samples.iter().filter(|s| (0.21..0.59).contains(*s)).count();
+    assert_eq!(mixed, 0);
+    let audible = samples.iter().filter(|s| **s != 0.0).count();
+    assert_eq!(audible, 2 * 44100 * 2);
+}
+
+#[test]
+fn test_skip_silence_trims_track_ends() {
+    // Half a second of silence either side of one second of audio
+    let input = write_input("silence-in.wav", 2.0, |frame, _| {
+        if (22050..66150).contains(&frame) {
+            0.5
+        } else {
+            0.0
+        }
+    });
+    let output = temp_path("silence-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    let mut item = track(&input);
+    item.silence.silence_start_ms = Some(500);
+    item.silence.silence_end_ms = Some(1500);
+    engine.set_skip_silence(true);
+    engine.play(item);
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    let audible = samples.iter().filter(|s| **s != 0.0).count();
+    assert_eq!(audible, 44100 * 2);
+    // Playback ended with the audio instead of running through the trailing silence
+    let first = samples.iter().position(|s| *s != 0.0).unwrap();
+    assert!(
+        samples.len() - first < 44100 * 2 + 20000,
+        "{} samples",
+        samples.len() - first
+    );
+    cleanup(&[&input, &output]);
+}
+
+#[test]
+fn test_visualization_only_while_subscribed() {
+    let input = write_input("visual.wav", 1.0, |frame, _| {
+        0.5 * (frame as f32 * 440.0 * std::f32::consts::TAU / 44100.0).sin()
+    });
+    let events = Arc::new(RecordedEvents::default());
+    let engine = AudioEngine::headless(|| Box::new(NullSink::new(FORMAT)), events.clone());
+
+    engine.set_visualizer(Some(VisualizerSettings {
+        rate_hz: 20,
+        bands: 16,
+    }));
+    engine.play(track(&input));
+    // A few frames in, the analysis window is full of the tone
+    let (mut index, mut frame) = events.wait_for_after(None, EVENT_VISUALIZATION, |_| true);
+    for _ in 0..5 {
+        (index, frame) = events.wait_for_after(Some(index), EVENT_VISUALIZATION, |_| true);
+    }
+    assert_eq!(frame["bands"].as_array().unwrap().len(), 16);
+    let peak = frame["peak"][0].as_f64().unwrap();
+    assert!((peak - 0.5).abs() < 0.01, "peak {}", peak);
+
+    engine.set_visualizer(None);
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+    let frames = events.events.lock().unwrap();
+    let after_finish = frames
+        .iter()
+        .skip_while(|(name, _)| name != EVENT_PLAYBACK_FINISHED)
+        .filter(|(name, _)| name == EVENT_VISUALIZATION)
+        .count();
+    assert_eq!(after_finish, 0);
+    drop(frames);
+    cleanup(&[&input]);
+}
+
+#[test]
+fn test_sleep_timer_fades_out_at_end_of_track() {
+    let first = write_input("sleep-a.wav", 1.0, |_, _| 0.5);
+    let second = write_input("sleep-b.wav", 1.0, |_, _| 0.5);
+    let output = temp_path("sleep-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.start_sleep_timer(SleepTimerMode::EndOfTrack, 500);
+    engine.set_queue(vec![track(&first), track(&second)], 0);
+    events.wait_for_matching(EVENT_SLEEP_TIMER, |status| status["active"] == false);
+
+    let state = engine.get_state();
+    assert!(!state.is_playing);
+    assert_eq!(state.volume, 1.0);
+    assert!(!events
+        .events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|(name, _)| name == EVENT_TRACK_CHANGED));
+
+    let samples = read_output(&output);
+    let audible: Vec<f32> = samples.into_iter().filter(|s| *s != 0.0).collect();
+    assert!(
+        audible.len() <= 44100 * 2,
+        "{} audible samples",
+        audible.len()
+    );
+    assert!((audible[0] - 0.5).abs() < 0.001);
+    // The last few hundred milliseconds ramp down toward silence
+    let tail = &audible[audible.len() - 4410..];
+    assert!(
+        tail.iter().all(|s| *s < 0.1),
+        "tail peaks at {:?}",
+        tail.iter().cloned().fold(0.0, f32::max)
+    );
+    cleanup(&[&first, &second, &output]);
+}
+
+#[test]
+fn test_ab_loop_repeats_passage() {
+    // A ramp from 0 to 0.8 over two seconds, so each value maps to a time
+    let input = write_input("loop-in.wav", 2.0, |frame, frames| {
+        0.8 * frame as f32 / frames as f32
+    });
+    let output = temp_path("loop-out.wav");
+    let (engine, events) = wav_engine(&output);
+
+    engine.play(track(&input));
+    engine.set_loop(AbLoop::new(500, 1000));
+    // Let playback reach B and jump back toward A a few times
+    let mut index = None;
+    for _ in 0..3 {
+        let (at_b, _) = events.wait_for_after(index, EVENT_PLAYBACK_PROGRESS, |state| {
+            position_ms(state) >= 950
+        });
+        let (looped, _) = events.wait_for_after(Some(at_b), EVENT_PLAYBACK_PROGRESS, |state| {
+            position_ms(state) < 950
+        });
+        index = Some(looped);
+    }
+    let position = engine.get_state().position_ms;
+    assert!((500..=1000).contains(&position), "at {}ms", position);
+
+    engine.set_loop(AbLoop::default());
+    events.wait_for(EVENT_PLAYBACK_FINISHED);
+
+    let samples = read_output(&output);
+    // 0.5 s to 1 s played more than once, and the track still ran to the end
+    let passage = samples.iter().filter(|s| (0.21..0.39).contains(*s)).count();
+    assert!(passage > 44100 * 2, "{} samples from the loop", passage);
+    let peak = samples.iter().fold(0.0f32, |m, s| m.max(*s));
+    assert!(peak > 0.79, "track did not play to the end: {}", peak);
+    // Nothing past B was played while looping
+    let first_past_b = samples.iter().position(|s| *s > 0.41).unwrap();
+    let last_in_loop = samples
+        .iter()
+        .rposition(|s| (0.21..0.39).contains(s))
+        .unwrap();
+    assert!(
+        first_past_b > last_in_loop,
+        "played past B before the loop was cleared"
+    );
+    cleanup(&[&input, &output]);
+}
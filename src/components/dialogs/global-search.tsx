@@ -1,4 +1,4 @@
-import { useEffect, useState, useMemo, useCallback } from "react";
+import { useEffect, useState, useMemo, useCallback, Fragment } from "react";
 import {
   CommandDialog,
   CommandEmpty,
@@ -11,14 +11,15 @@ import {
 import { useNavigationStore } from "@/stores/navigation-store";
 import { useAudioStore } from "@/stores/audio-store";
 import {
-  getTracks,
-  getAlbums,
-  getPlaylists,
+  searchLibrary,
   getAlbumTracks,
   getPlaylistTracks,
-  Track,
-  Album,
-  Playlist,
+  HIGHLIGHT_START,
+  HIGHLIGHT_END,
+  SearchResults,
+  TrackHit,
+  AlbumHit,
+  PlaylistHit,
 } from "@/lib/api";
 import { Disc, ListMusic, Music, Play, Shuffle, Plus } from "lucide-react";
 import {
@@ -30,16 +31,43 @@ import {
 } from "@/components/ui/context-menu";
 import { toast } from "sonner";
 
-// Maximum items per category for performance
+// Maximum items per category
 const MAX_ITEMS = 20;
+// Wait for a pause in typing before querying the index
+const SEARCH_DELAY_MS = 150;
+
+const NO_RESULTS: SearchResults = {
+  tracks: [],
+  albums: [],
+  artists: [],
+  playlists: [],
+};
+
+/** Render text from the search index with its matched terms emphasized */
+function Highlighted({ text }: { text: string }) {
+  return (
+    <>
+      {text.split(HIGHLIGHT_START).map((part, i) => {
+        const end = part.indexOf(HIGHLIGHT_END);
+        if (i === 0 || end === -1) return <Fragment key={i}>{part}</Fragment>;
+        return (
+          <Fragment key={i}>
+            <mark className="bg-transparent text-primary">
+              {part.slice(0, end)}
+            </mark>
+            {part.slice(end + HIGHLIGHT_END.length)}
+          </Fragment>
+        );
+      })}
+    </>
+  );
+}
 
 export function GlobalSearch() {
   const isSearchOpen = useNavigationStore((s) => s.isSearchOpen);
   const setSearchOpen = useNavigationStore((s) => s.setSearchOpen);
 
-  const [tracks, setTracks] = useState<Track[]>([]);
-  const [albums, setAlbums] = useState<Album[]>([]);
-  const [playlists, setPlaylists] = useState<Playlist[]>([]);
+  const [results, setResults] = useState<SearchResults>(NO_RESULTS);
   const [searchQuery, setSearchQuery] = useState("");
 
   const openAlbumDetail = useNavigationStore((s) => s.openAlbumDetail);
@@ -67,24 +95,35 @@ export function GlobalSearch() {
     }
   }, [isSearchOpen]);
 
-  // Pre-fetch data on mount to avoid lag when opening
+  // Query the library index as the user types
   useEffect(() => {
-    Promise.all([getTracks(), getAlbums(), getPlaylists()])
-      .then(([t, a, p]) => {
-        setTracks(t);
-        setAlbums(a);
-        setPlaylists(p);
-      })
-      .catch((e) => console.error("Failed to fetch:", e));
-  }, []); // Run once on mount
+    const query = searchQuery.trim();
+    if (query.length === 0) {
+      setResults(NO_RESULTS);
+      return;
+    }
+    // Drop answers to queries that have since changed
+    let stale = false;
+    const timer = window.setTimeout(() => {
+      searchLibrary(query, MAX_ITEMS)
+        .then((r) => {
+          if (!stale) setResults(r);
+        })
+        .catch((e) => console.error("Failed to search:", e));
+    }, SEARCH_DELAY_MS);
+    return () => {
+      stale = true;
+      window.clearTimeout(timer);
+    };
+  }, [searchQuery]);
 
   // Actions
   const handlePlayTrack = useCallback(
-    (track: Track) => {
-      play(track, tracks);
+    (track: TrackHit) => {
+      play(track, results.tracks);
       setSearchOpen(false);
     },
-    [play, tracks, setSearchOpen]
+    [play, results.tracks, setSearchOpen]
   );
 
   const handlePlayAlbum = useCallback(
@@ -122,17 +161,17 @@ export function GlobalSearch() {
   );
 
   const handleAddToQueue = useCallback(
-    async (item: Track | Album | Playlist, type: string) => {
+    async (item: TrackHit | AlbumHit | PlaylistHit, type: string) => {
       try {
         if (type === "track") {
-          addToQueue(item as Track);
+          addToQueue(item as TrackHit);
           toast.success("Added to queue");
         } else if (type === "album") {
-          const t = await getAlbumTracks((item as Album).id);
+          const t = await getAlbumTracks((item as AlbumHit).id);
           t.forEach((x) => addToQueue(x));
           toast.success(`Added ${t.length} tracks`);
         } else {
-          const t = await getPlaylistTracks((item as Playlist).id);
+          const t = await getPlaylistTracks((item as PlaylistHit).id);
           t.forEach((x) => addToQueue(x));
           toast.success(`Added ${t.length} tracks`);
         }
@@ -144,17 +183,17 @@ export function GlobalSearch() {
   );
 
   const handlePlayNext = useCallback(
-    async (item: Track | Album | Playlist, type: string) => {
+    async (item: TrackHit | AlbumHit | PlaylistHit, type: string) => {
       try {
         if (type === "track") {
-          playNext(item as Track);
+          playNext(item as TrackHit);
           toast.success("Playing next");
         } else if (type === "album") {
-          const t = await getAlbumTracks((item as Album).id);
+          const t = await getAlbumTracks((item as AlbumHit).id);
           [...t].reverse().forEach((x) => playNext(x));
           toast.success("Album playing next");
         } else {
-          const t = await getPlaylistTracks((item as Playlist).id);
+          const t = await getPlaylistTracks((item as PlaylistHit).id);
           [...t].reverse().forEach((x) => playNext(x));
           toast.success("Playlist playing next");
         }
@@ -165,42 +204,32 @@ export function GlobalSearch() {
     [playNext]
   );
 
-  // Limited items for performance
-  const displayTracks = useMemo(() => tracks.slice(0, MAX_ITEMS), [tracks]);
-  const displayAlbums = useMemo(() => albums.slice(0, MAX_ITEMS), [albums]);
-  const displayPlaylists = useMemo(
-    () => playlists.slice(0, MAX_ITEMS),
-    [playlists]
-  );
-
   // Item renderer
   const renderItem = useCallback(
     (
       id: string,
       type: "track" | "album" | "playlist",
-      data: Track | Album | Playlist,
+      data: TrackHit | AlbumHit | PlaylistHit,
       icon: React.ReactNode,
       primary: string,
       secondary: string,
-      onSelect: () => void,
-      keywords: string[] = []
+      onSelect: () => void
     ) => {
       return (
         <ContextMenu key={id}>
           <ContextMenuTrigger asChild>
             <CommandItem
               value={id}
-              keywords={keywords}
               onSelect={onSelect}
               className="py-1.5"
             >
               {icon}
               <div className="flex flex-col min-w-0 flex-1">
                 <span className="truncate block font-medium group-data-[selected=true]:text-primary transition-colors">
-                  {primary}
+                  <Highlighted text={primary} />
                 </span>
                 <span className="text-xs text-neutral-500 truncate block">
-                  {secondary}
+                  <Highlighted text={secondary} />
                 </span>
               </div>
             </CommandItem>
@@ -208,19 +237,20 @@ export function GlobalSearch() {
           <ContextMenuContent className="w-48">
             <ContextMenuItem
               onSelect={() => {
-                if (type === "track") handlePlayTrack(data as Track);
-                else if (type === "album") handlePlayAlbum((data as Album).id);
-                else handlePlayPlaylist((data as Playlist).id);
+                if (type === "track") handlePlayTrack(data as TrackHit);
+                else if (type === "album")
+                  handlePlayAlbum((data as AlbumHit).id);
+                else handlePlayPlaylist((data as PlaylistHit).id);
               }}
             >
               <Play className="mr-2 h-4 w-4" /> Play
             </ContextMenuItem>
             <ContextMenuItem
               onSelect={() => {
-                if (type === "track") handlePlayTrack(data as Track);
+                if (type === "track") handlePlayTrack(data as TrackHit);
                 else if (type === "album")
-                  handlePlayAlbum((data as Album).id, true);
-                else handlePlayPlaylist((data as Playlist).id, true);
+                  handlePlayAlbum((data as AlbumHit).id, true);
+                else handlePlayPlaylist((data as PlaylistHit).id, true);
               }}
             >
               <Shuffle className="mr-2 h-4 w-4" /> Shuffle
@@ -254,81 +284,78 @@ export function GlobalSearch() {
     ]
   );
 
-  // Sections with unique IDs and search keywords
+  // Sections with unique IDs, in the order the index ranked them
   const songsSection = useMemo(
     () =>
-      displayTracks.length > 0 && (
+      results.tracks.length > 0 && (
         <CommandGroup heading="Songs">
-          {displayTracks.map((t) =>
+          {results.tracks.map((t) =>
             renderItem(
               `track-${t.id}`,
               "track",
               t,
               <Music className="mr-2 h-3 w-3 opacity-70 shrink-0" />,
-              t.title,
-              t.artist ?? "Unknown",
-              () => handlePlayTrack(t),
-              [t.title, t.artist ?? ""]
+              t.title_highlight,
+              t.snippet || (t.artist ?? "Unknown"),
+              () => handlePlayTrack(t)
             )
           )}
         </CommandGroup>
       ),
-    [displayTracks, renderItem, handlePlayTrack]
+    [results.tracks, renderItem, handlePlayTrack]
   );
 
   const albumsSection = useMemo(
     () =>
-      displayAlbums.length > 0 && (
+      results.albums.length > 0 && (
         <CommandGroup heading="Albums">
-          {displayAlbums.map((a) =>
+          {results.albums.map((a) =>
             renderItem(
               `album-${a.id}`,
               "album",
               a,
               <Disc className="mr-2 h-3 w-3 opacity-70 shrink-0" />,
-              a.title,
+              a.highlight,
               a.artist_name ?? "Unknown",
               () => {
                 openAlbumDetail(a.id);
                 setSearchOpen(false);
-              },
-              [a.title, a.artist_name ?? ""]
+              }
             )
           )}
         </CommandGroup>
       ),
-    [displayAlbums, renderItem, openAlbumDetail, setSearchOpen]
+    [results.albums, renderItem, openAlbumDetail, setSearchOpen]
   );
 
   const playlistsSection = useMemo(
     () =>
-      displayPlaylists.length > 0 && (
+      results.playlists.length > 0 && (
         <CommandGroup heading="Playlists">
-          {displayPlaylists.map((p) =>
+          {results.playlists.map((p) =>
             renderItem(
               `playlist-${p.id}`,
               "playlist",
               p,
               <ListMusic className="mr-2 h-3 w-3 opacity-70 shrink-0" />,
-              p.name,
-              `${p.track_count} tracks`,
+              p.highlight,
+              "Playlist",
               () => {
                 openPlaylistDetail(p.id);
                 setSearchOpen(false);
-              },
-              [p.name]
+              }
             )
           )}
         </CommandGroup>
       ),
-    [displayPlaylists, renderItem, openPlaylistDetail, setSearchOpen]
+    [results.playlists, renderItem, openPlaylistDetail, setSearchOpen]
   );
 
   return (
     <CommandDialog
       open={isSearchOpen}
       onOpenChange={setSearchOpen}
-      commandProps={{}}
+      commandProps={{ shouldFilter: false }}
     >
       <CommandInput
         placeholder="Search tracks, albums, or playlists..."
@@ -336,24 +363,16 @@ export function GlobalSearch() {
         onValueChange={setSearchQuery}
       />
       <CommandList className="max-h-[300px]">
-        <CommandEmpty>No results found.</CommandEmpty>
-        {searchQuery.length > 0 ? (
-          <>
-            {songsSection}
-            <CommandSeparator />
-            {albumsSection}
-            <CommandSeparator />
-            {playlistsSection}
-          </>
-        ) : (
-          <>
-            {albumsSection}
-            <CommandSeparator />
-            {playlistsSection}
-            <CommandSeparator />
-            {songsSection}
-          </>
-        )}
+        <CommandEmpty>
+          {searchQuery.trim().length > 0
+            ? "No results found."
+            : "Type to search your library."}
+        </CommandEmpty>
+        {songsSection}
+        <CommandSeparator />
+        {albumsSection}
+        <CommandSeparator />
+        {playlistsSection}
       </CommandList>
     </CommandDialog>
   );
@@ -19,9 +19,8 @@ import {
 import {
   SortableContext,
   verticalListSortingStrategy,
-  arrayMove,
 } from "@dnd-kit/sortable";
-import QueueItem from "./shared/item/queue-item";
+import QueueItem, { queueEntryId } from "./shared/item/queue-item";
 import { convertFileSrc } from "@tauri-apps/api/core";
 import placeholderArt from "@/assets/placeholder-art.png";
 
@@ -31,9 +30,10 @@ export default function QueueMenu() {
   const queue = useQueue();
   const isQueueOpen = useQueueOpen();
   const status = usePlayerStatus();
+  const currentIndex = useAudioStore((s) => s.currentIndex);
 
   // Get actions directly (stable references)
-  const reorderQueue = useAudioStore((s) => s.reorderQueue);
+  const moveInQueue = useAudioStore((s) => s.moveInQueue);
   const toggleQueue = useAudioStore((s) => s.toggleQueue);
 
   const sensors = useSensors(
@@ -54,11 +54,12 @@ export default function QueueMenu() {
     const { active, over } = event;
 
     if (over && active.id !== over.id) {
-      const oldIndex = queue.findIndex((t) => t.id === active.id);
-      const newIndex = queue.findIndex((t) => t.id === over.id);
+      const ids = queue.map((_, i) => queueEntryId(i));
+      const oldIndex = ids.indexOf(String(active.id));
+      const newIndex = ids.indexOf(String(over.id));
 
       if (oldIndex !== -1 && newIndex !== -1) {
-        reorderQueue(arrayMove(queue, oldIndex, newIndex));
+        moveInQueue(oldIndex, newIndex);
       }
     }
   };
@@ -135,7 +136,7 @@ export default function QueueMenu() {
             onDragEnd={handleDragEnd}
           >
             <SortableContext
-              items={queue.map((t) => t.id)}
+              items={queue.map((_, i) => queueEntryId(i))}
               strategy={verticalListSortingStrategy}
             >
               <div className="flex flex-col gap-1">
@@ -144,16 +145,14 @@ export default function QueueMenu() {
                     Queue is empty
                   </p>
                 ) : (
-                  queue.map((track) => {
-                    const isCurrent = currentTrack?.id === track.id;
-                    return (
-                      <QueueItem
-                        key={track.id}
-                        track={track}
-                        isActive={isCurrent}
-                      />
-                    );
-                  })
+                  queue.map((track, index) => (
+                    <QueueItem
+                      key={queueEntryId(index)}
+                      track={track}
+                      index={index}
+                      isActive={index === currentIndex}
+                    />
+                  ))
                 )}
               </div>
             </SortableContext>
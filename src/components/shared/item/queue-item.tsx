@@ -12,15 +12,19 @@ import { useAudioStore, usePlayerStatus } from "@/stores/audio-store";
 
 interface QueueItemProps {
   track: Track;
+  /** Position in the queue, which can hold the same track more than once */
+  index: number;
   isActive?: boolean;
 }
 
-export default function QueueItem({ track, isActive }: QueueItemProps) {
+/** Sortable id of the queue row at `index` */
+export const queueEntryId = (index: number) => `queue-entry-${index}`;
+
+export default function QueueItem({ track, index, isActive }: QueueItemProps) {
   const removeFromQueue = useAudioStore((s) => s.removeFromQueue);
-  const play = useAudioStore((s) => s.play);
+  const jumpTo = useAudioStore((s) => s.jumpTo);
   const pause = useAudioStore((s) => s.pause);
   const resume = useAudioStore((s) => s.resume);
-  const queue = useAudioStore((s) => s.queue);
   const status = usePlayerStatus();
 
   const {
@@ -30,7 +34,7 @@ export default function QueueItem({ track, isActive }: QueueItemProps) {
     transform,
     transition,
     isDragging,
-  } = useSortable({ id: track.id });
+  } = useSortable({ id: queueEntryId(index) });
 
   const style = {
     transform: CSS.Transform.toString(transform),
@@ -48,14 +52,8 @@ export default function QueueItem({ track, isActive }: QueueItemProps) {
         resume();
       }
     } else {
-      // Play this track from the current queue
-      // We need to find the queue index, or allow play to take an index?
-      // Actually play(track) will reset queue if we don't pass one.
-      // But we just want to jump to it.
-      // audioStore.play implementation:
-      // if newQueue passed, use it. else queue=[track].
-      // We should pass the *current* queue to preserve it!
-      play(track, queue);
+      // Jump to this track without rebuilding the queue
+      jumpTo(index);
     }
   };
 
@@ -121,7 +119,7 @@ export default function QueueItem({ track, isActive }: QueueItemProps) {
         </div>
       </ContextMenuTrigger>
       <ContextMenuContent>
-        <ContextMenuItem onSelect={() => removeFromQueue(track.id)}>
+        <ContextMenuItem onSelect={() => removeFromQueue(index)}>
           Remove from Queue
         </ContextMenuItem>
       </ContextMenuContent>
@@ -9,6 +9,25 @@ export interface Track {
   file_path: string;
   artwork_path: string | null;
   track_number: number | null;
+  disc_number: number | null;
+  /** Every credited artist, or just the track artist */
+  artists: string[];
+  year: number | null;
+  genre: string | null;
+  file_format: string | null;
+  sample_rate: number | null;
+  bit_rate: number | null;
+  channels: number | null;
+  file_size: number | null;
+  play_count: number;
+  skip_count: number;
+  last_played_at: string | null;
+  track_gain_db?: number | null;
+  track_peak?: number | null;
+  album_gain_db?: number | null;
+  album_peak?: number | null;
+  silence_start_ms?: number | null;
+  silence_end_ms?: number | null;
 }
 
 export interface Album {
@@ -20,8 +39,25 @@ export interface Album {
   artwork_path: string | null;
   track_count: number;
   total_duration_ms: number;
+  disc_count: number;
+}
+
+export interface Artist {
+  id: number;
+  name: string;
+  track_count: number;
+  album_count: number;
+  artwork_path: string | null;
+}
+
+export interface ArtistAlbums {
+  albums: Album[];
+  /** Other artists' albums with tracks by this artist */
+  appears_on: Album[];
 }
 
+export type ArtistSort = "name" | "track_count" | "album_count";
+
 export interface Playlist {
   id: number;
   name: string;
@@ -29,12 +65,109 @@ export interface Playlist {
   artwork_path: string | null;
   track_count: number;
   created_at: string;
+  smart_rules: SmartRules | null;
+  folder_id: number | null;
+}
+
+export interface PlaylistFolder {
+  id: number;
+  name: string;
+  parent_id: number | null;
+  created_at: string;
+}
+
+export interface PlaylistFolderNode extends PlaylistFolder {
+  folders: PlaylistFolderNode[];
+  playlists: Playlist[];
+}
+
+export interface PlaylistTree {
+  folders: PlaylistFolderNode[];
+  playlists: Playlist[];
+}
+
+export type RuleField =
+  | "title"
+  | "artist"
+  | "album"
+  | "album_artist"
+  | "genre"
+  | "file_format"
+  | "year"
+  | "duration_ms"
+  | "play_count"
+  | "skip_count"
+  | "date_added"
+  | "last_played";
+
+export type RuleOp =
+  | "is"
+  | "is_not"
+  | "contains"
+  | "not_contains"
+  | "starts_with"
+  | "greater_than"
+  | "less_than"
+  | "in_last_days"
+  | "not_in_last_days";
+
+export interface SmartRule {
+  field: RuleField;
+  op: RuleOp;
+  value: string | number;
+}
+
+export interface SmartRules {
+  match?: "all" | "any";
+  rules: SmartRule[];
+  sort?: { field: RuleField; descending?: boolean } | null;
+  limit?: number | null;
 }
 
 export async function getTracks(): Promise<Track[]> {
   return await invoke("get_all_tracks");
 }
 
+export interface Page<T> {
+  items: T[];
+  /** Matches across all pages */
+  total: number;
+  offset: number;
+}
+
+export type TrackSort = "title" | "artist" | "album" | "duration" | "date_added";
+
+export interface TrackQuery {
+  offset?: number;
+  limit?: number;
+  sort?: TrackSort;
+  descending?: boolean;
+  filter?: string;
+  artist_id?: number;
+  album_id?: number;
+  genre?: string;
+}
+
+export async function getTracksPage(query: TrackQuery): Promise<Page<Track>> {
+  return await invoke("get_tracks_page", { query });
+}
+
+export type AlbumSort = "title" | "artist" | "year" | "track_count" | "date_added";
+
+export interface AlbumQuery {
+  offset?: number;
+  limit?: number;
+  sort?: AlbumSort;
+  descending?: boolean;
+  filter?: string;
+  artist_id?: number;
+  year?: number;
+}
+
+export async function getAlbumsPage(query: AlbumQuery): Promise<Page<Album>> {
+  return await invoke("get_albums_page", { query });
+}
+
 export async function getAlbums(): Promise<Album[]> {
   return await invoke("get_all_albums");
 }
@@ -47,6 +180,71 @@ export async function getAlbumTracks(albumId: number): Promise<Track[]> {
   return await invoke("get_album_tracks", { albumId });
 }
 
+export async function getArtists(
+  sort?: ArtistSort,
+  descending?: boolean,
+  filter?: string
+): Promise<Artist[]> {
+  return await invoke("get_all_artists", { sort, descending, filter });
+}
+
+export async function getArtistById(id: number): Promise<Artist | null> {
+  return await invoke("get_artist_by_id", { id });
+}
+
+export async function getArtistAlbums(artistId: number): Promise<ArtistAlbums> {
+  return await invoke("get_artist_albums", { artistId });
+}
+
+export async function getArtistTracks(artistId: number): Promise<Track[]> {
+  return await invoke("get_artist_tracks", { artistId });
+}
+
+/** Matched terms in highlights are wrapped in these control characters */
+export const HIGHLIGHT_START = "\u0002";
+export const HIGHLIGHT_END = "\u0003";
+
+export interface TrackHit extends Track {
+  title_highlight: string;
+  snippet: string;
+}
+
+export interface AlbumHit {
+  id: number;
+  title: string;
+  artist_name: string | null;
+  artwork_path: string | null;
+  highlight: string;
+}
+
+export interface ArtistHit {
+  id: number;
+  name: string;
+  track_count: number;
+  highlight: string;
+}
+
+export interface PlaylistHit {
+  id: number;
+  name: string;
+  artwork_path: string | null;
+  highlight: string;
+}
+
+export interface SearchResults {
+  tracks: TrackHit[];
+  albums: AlbumHit[];
+  artists: ArtistHit[];
+  playlists: PlaylistHit[];
+}
+
+export async function searchLibrary(
+  query: string,
+  limit?: number
+): Promise<SearchResults> {
+  return await invoke("search_library", { query, limit });
+}
+
 export async function createPlaylist(
   name: string,
   description?: string
@@ -76,7 +274,85 @@ export async function getPlaylists(): Promise<Playlist[]> {
   return await invoke("get_playlists");
 }
 
-export async function getPlaylistTracks(id: number): Promise<Track[]> {
+export type PlaylistSort = "name" | "created_at" | "updated_at";
+
+export interface PlaylistQuery {
+  offset?: number;
+  limit?: number;
+  sort?: PlaylistSort;
+  descending?: boolean;
+  filter?: string;
+  /** true for smart playlists only, false for manual ones only */
+  smart?: boolean;
+}
+
+export async function getPlaylistsPage(
+  query: PlaylistQuery
+): Promise<Page<Playlist>> {
+  return await invoke("get_playlists_page", { query });
+}
+
+export async function createSmartPlaylist(
+  name: string,
+  rules: SmartRules,
+  description?: string
+): Promise<Playlist> {
+  return await invoke("create_smart_playlist", { name, description, rules });
+}
+
+export async function updateSmartPlaylistRules(
+  id: number,
+  rules: SmartRules
+): Promise<void> {
+  return await invoke("update_smart_playlist_rules", { id, rules });
+}
+
+export interface PlaylistTrack extends Track {
+  /** Tells apart copies of the same track; null in smart playlists */
+  entry_id: number | null;
+}
+
+export async function getPlaylistTree(): Promise<PlaylistTree> {
+  return await invoke("get_playlist_tree");
+}
+
+export async function movePlaylist(
+  id: number,
+  folderId: number | null
+): Promise<void> {
+  return await invoke("move_playlist", { id, folderId });
+}
+
+export async function createPlaylistFolder(
+  name: string,
+  parentId?: number
+): Promise<PlaylistFolder> {
+  return await invoke("create_playlist_folder", { name, parentId });
+}
+
+export async function renamePlaylistFolder(
+  id: number,
+  name: string
+): Promise<void> {
+  return await invoke("rename_playlist_folder", { id, name });
+}
+
+export async function movePlaylistFolder(
+  id: number,
+  parentId: number | null
+): Promise<void> {
+  return await invoke("move_playlist_folder", { id, parentId });
+}
+
+/** "cascade" deletes the contents too, "reparent" moves them up a level */
+export async function deletePlaylistFolder(
+  id: number,
+  mode: "cascade" | "reparent"
+): Promise<void> {
+  return await invoke("delete_playlist_folder", { id, mode });
+}
+
+export async function getPlaylistTracks(id: number): Promise<PlaylistTrack[]> {
   return await invoke("get_playlist_tracks", { id });
 }
 
@@ -87,6 +363,29 @@ export async function addTrackToPlaylist(
   return await invoke("add_track_to_playlist", { playlistId, trackId });
 }
 
+export async function addTracksToPlaylist(
+  playlistId: number,
+  trackIds: number[],
+  index?: number
+): Promise<void> {
+  return await invoke("add_tracks_to_playlist", { playlistId, trackIds, index });
+}
+
+export async function movePlaylistEntry(
+  playlistId: number,
+  entryId: number,
+  index: number
+): Promise<void> {
+  return await invoke("move_playlist_entry", { playlistId, entryId, index });
+}
+
+export async function removePlaylistEntries(
+  playlistId: number,
+  entryIds: number[]
+): Promise<void> {
+  return await invoke("remove_playlist_entries", { playlistId, entryIds });
+}
+
 export async function removeTrackFromPlaylist(
   playlistId: number,
   trackId: number
@@ -94,9 +393,51 @@ export async function removeTrackFromPlaylist(
   return await invoke("remove_track_from_playlist", { playlistId, trackId });
 }
 
-export async function reorderPlaylist(
+export type PlaylistFormat = "m3u" | "pls" | "xspf";
+
+export interface UnresolvedEntry {
+  position: number;
+  location: string;
+  title: string | null;
+  artist: string | null;
+}
+
+export interface ImportReport {
+  playlist: Playlist;
+  total_entries: number;
+  exact_matches: number;
+  fuzzy_matches: number;
+  unresolved: UnresolvedEntry[];
+}
+
+export async function exportPlaylist(
   id: number,
-  newOrder: number[]
+  path: string,
+  options?: { format?: PlaylistFormat; relativePaths?: boolean }
 ): Promise<void> {
-  return await invoke("reorder_playlist", { id, newOrder });
+  return await invoke("export_playlist", {
+    id,
+    path,
+    format: options?.format,
+    relativePaths: options?.relativePaths,
+  });
+}
+
+export async function importPlaylist(
+  path: string,
+  name?: string
+): Promise<ImportReport> {
+  return await invoke("import_playlist", { path, name });
+}
+
+export interface Waveform {
+  duration_ms: number;
+  peaks: [number, number][];
+}
+
+export async function getWaveform(
+  path: string,
+  resolution?: number
+): Promise<Waveform> {
+  return await invoke("get_waveform", { path, resolution });
 }
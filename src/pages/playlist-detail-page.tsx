@@ -2,8 +2,8 @@ import { useEffect, useState, useCallback } from "react";
 import {
   getPlaylistTracks,
   deletePlaylist,
-  removeTrackFromPlaylist,
-  Track,
+  removePlaylistEntries,
+  PlaylistTrack,
   getPlaylists,
   Playlist,
 } from "@/lib/api";
@@ -52,11 +52,14 @@ import { CSS } from "@dnd-kit/utilities";
 import { TrackSelectDialog } from "@/components/dialogs/track-select-dialog";
 
 interface SortableTrackItemProps {
-  track: Track;
+  track: PlaylistTrack;
   index: number;
   onRemove: (e: React.MouseEvent) => void;
 }
 
+/** Copies of the same track are told apart by their entry id */
+const rowId = (track: PlaylistTrack) => track.entry_id ?? track.id;
+
 function SortableTrackItem({ track, index, onRemove }: SortableTrackItemProps) {
   const {
     attributes,
@@ -65,7 +68,7 @@ function SortableTrackItem({ track, index, onRemove }: SortableTrackItemProps) {
     transform,
     transition,
     isDragging,
-  } = useSortable({ id: track.id });
+  } = useSortable({ id: rowId(track) });
 
   const style = {
     transform: CSS.Transform.toString(transform),
@@ -114,11 +117,11 @@ export default function PlaylistDetailPage() {
   const detailView = useDetailView();
   const goBack = useNavigationStore((s) => s.goBack);
   const play = useAudioStore((s) => s.play);
-  const reorderPlaylist = useLibraryStore((s) => s.reorderPlaylist);
+  const movePlaylistEntry = useLibraryStore((s) => s.movePlaylistEntry);
   const refreshPlaylists = useLibraryStore((s) => s.refreshPlaylists);
 
   const [playlist, setPlaylist] = useState<Playlist | null>(null);
-  const [tracks, setTracks] = useState<Track[]>([]);
+  const [tracks, setTracks] = useState<PlaylistTrack[]>([]);
   const [isLoading, setIsLoading] = useState(true);
   const [isDeleteDialogOpen, setIsDeleteDialogOpen] = useState(false);
   const [isEditOpen, setIsEditOpen] = useState(false);
@@ -188,34 +191,34 @@ export default function PlaylistDetailPage() {
   const handleDragEnd = async (event: DragEndEvent) => {
     const { active, over } = event;
 
-    if (over && active.id !== over.id) {
-      setTracks((items) => {
-        const oldIndex = items.findIndex((t) => t.id === active.id);
-        const newIndex = items.findIndex((t) => t.id === over.id);
-        const newOrder = arrayMove(items, oldIndex, newIndex);
-
-        // Optimistic update
-        const trackIds = newOrder.map((t) => t.id);
-        if (playlistId) {
-          reorderPlaylist(playlistId, trackIds).catch(() => {
-            loadData();
-          });
-        }
-
-        return newOrder;
-      });
-    }
+    if (!over || active.id === over.id || !playlistId) return;
+
+    const oldIndex = tracks.findIndex((t) => rowId(t) === active.id);
+    const newIndex = tracks.findIndex((t) => rowId(t) === over.id);
+    const entryId = tracks[oldIndex]?.entry_id;
+    // Smart playlists have no entries to move
+    if (entryId == null || newIndex < 0) return;
+
+    // Optimistic update
+    setTracks(arrayMove(tracks, oldIndex, newIndex));
+    movePlaylistEntry(playlistId, entryId, newIndex).catch(() => {
+      loadData();
+    });
   };
 
-  const handleRemoveTrack = async (trackId: number, e: React.MouseEvent) => {
+  const handleRemoveTrack = async (
+    track: PlaylistTrack,
+    e: React.MouseEvent
+  ) => {
     e.stopPropagation();
-    if (!playlistId) return;
+    // Smart playlists are filled by their rules
+    if (!playlistId || track.entry_id == null) return;
 
     try {
-      await removeTrackFromPlaylist(playlistId, trackId);
+      // Only this copy goes when the track is in the playlist more than once
+      await removePlaylistEntries(playlistId, [track.entry_id]);
       toast.success("Track removed");
-      const newTracks = tracks.filter((t) => t.id !== trackId);
-      setTracks(newTracks);
+      setTracks(tracks.filter((t) => rowId(t) !== rowId(track)));
     } catch (e) {
       console.error(e);
       toast.error("Failed to remove track");
@@ -459,15 +462,15 @@ export default function PlaylistDetailPage() {
               onDragEnd={handleDragEnd}
             >
               <SortableContext
-                items={tracks.map((t) => t.id)}
+                items={tracks.map(rowId)}
                 strategy={verticalListSortingStrategy}
               >
                 {tracks.map((track, index) => (
                   <SortableTrackItem
-                    key={track.id}
+                    key={rowId(track)}
                     track={track}
                     index={index}
-                    onRemove={(e) => handleRemoveTrack(track.id, e)}
+                    onRemove={(e) => handleRemoveTrack(track, e)}
                   />
                 ))}
               </SortableContext>
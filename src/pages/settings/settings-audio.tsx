@@ -1,4 +1,4 @@
-import { useSettingsStore } from "@/stores/settings-store";
+import { DecoderBackend, useSettingsStore } from "@/stores/settings-store";
 import { Input } from "@/components/ui/input";
 import { Button } from "@/components/ui/button";
 import {
@@ -11,6 +11,12 @@ import {
 import { ChevronDown, Speaker, RefreshCw } from "lucide-react";
 import { useState } from "react";
 
+const DECODER_BACKENDS: { value: DecoderBackend; label: string }[] = [
+  { value: "auto", label: "Automatic" },
+  { value: "symphonia", label: "Symphonia" },
+  { value: "ffmpeg", label: "FFmpeg" },
+];
+
 export function SettingsAudio() {
   const {
     selectedDevice,
@@ -19,6 +25,8 @@ export function SettingsAudio() {
     refreshAudioDevices,
     crossfadeDuration,
     setCrossfadeDuration,
+    decoderBackend,
+    setDecoderBackend,
   } = useSettingsStore();
 
   const [isRefreshingDevices, setIsRefreshingDevices] = useState(false);
@@ -117,6 +125,41 @@ export function SettingsAudio() {
             </div>
           </div>
         </div>
+
+        {/* Decoder */}
+        <div className="flex items-center justify-between p-4 rounded-xl bg-white/5 border border-white/10">
+          <div className="space-y-1">
+            <div className="font-medium">Decoder</div>
+            <div className="text-sm text-gray-400">
+              Automatic uses Symphonia where it works well and FFmpeg for the
+              rest
+            </div>
+          </div>
+          <DropdownMenu>
+            <DropdownMenuTrigger asChild>
+              <Button variant="outline" className="w-48 justify-between">
+                {DECODER_BACKENDS.find((b) => b.value === decoderBackend)
+                  ?.label ?? "Automatic"}
+                <ChevronDown className="w-4 h-4 opacity-50 ml-2" />
+              </Button>
+            </DropdownMenuTrigger>
+            <DropdownMenuContent align="end" className="w-48">
+              <DropdownMenuRadioGroup
+                value={decoderBackend}
+                onValueChange={(v) => setDecoderBackend(v as DecoderBackend)}
+              >
+                {DECODER_BACKENDS.map((backend) => (
+                  <DropdownMenuRadioItem
+                    key={backend.value}
+                    value={backend.value}
+                  >
+                    {backend.label}
+                  </DropdownMenuRadioItem>
+                ))}
+              </DropdownMenuRadioGroup>
+            </DropdownMenuContent>
+          </DropdownMenu>
+        </div>
       </div>
     </div>
   );
@@ -1,7 +1,6 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
-import { useSettingsStore } from "./settings-store";
 import { useLibraryStore } from "./library-store";
 import { toast } from "sonner";
 import { Track } from "@/lib/api";
@@ -22,6 +21,14 @@ interface AudioPlaybackProgressPayload {
   duration_ms: number;
 }
 
+// Mirrors the Rust-side queue (see src-tauri/src/audio/queue.rs)
+interface AudioQueuePayload {
+  items: Track[];
+  current_index: number | null;
+  shuffle: boolean;
+  repeat: RepeatMode;
+}
+
 // --- Store State Interface ---
 interface AudioState {
   // Player State
@@ -45,7 +52,6 @@ interface AudioState {
   _isDraggingSlider: boolean;
   _listenersInitialized: boolean;
   _lastProgressUpdate: number; // For throttling
-  _isTransitioning: boolean;
 }
 
 // --- Store Actions Interface ---
@@ -67,8 +73,9 @@ interface AudioActions {
   toggleRepeat: () => void;
   addToQueue: (track: Track) => void;
   playNext: (track: Track) => void;
-  removeFromQueue: (trackId: number) => void;
-  reorderQueue: (newQueue: Track[]) => void;
+  removeFromQueue: (index: number) => void;
+  moveInQueue: (from: number, to: number) => void;
+  jumpTo: (index: number) => void;
   clearQueue: () => void;
 
   // Progress Actions
@@ -83,79 +90,19 @@ type AudioStore = AudioState & AudioActions;
 
 // --- Store Implementation ---
 export const useAudioStore = create<AudioStore>((set, get) => {
-  // Internal helper for playing a track
-  const playInternal = async (track: Track) => {
+  // Queue, shuffle and repeat live in the Rust engine; these helpers only
+  // forward user intent and the store mirrors "audio-queue-changed"
+  const invokeQueue = async (
+    command: string,
+    args?: Record<string, unknown>
+  ) => {
     try {
-      await invoke("audio_play", {
-        path: track.file_path,
-        title: track.title,
-        artist: track.artist,
-        album: track.album,
-        cover: track.artwork_path,
-      });
+      await invoke(command, args);
     } catch (e) {
-      console.error("Failed to play:", e);
-      set({ status: "stopped" });
+      console.error(`Failed to run ${command}:`, e);
     }
   };
 
-  // Internal next handler
-  // Internal next handler
-  const handleNext = async () => {
-    const state = get();
-    console.log("[handleNext] Start", {
-      queue: state.queue.length,
-      currentIndex: state.currentIndex,
-      repeat: state.repeat,
-      currentTrack: state.currentTrack?.title,
-    });
-
-    if (state.queue.length === 0) {
-      console.log("[handleNext] Queue empty -> Stop");
-      // Don't clear currentTrack so UI can still show last played song
-      set({ status: "stopped", position: 0 });
-      await invoke("audio_stop");
-      return;
-    }
-
-    if (state.repeat === "one") {
-      console.log("[handleNext] Repeat One -> Replay");
-      if (state.currentTrack) {
-        set({ position: 0 });
-        await playInternal(state.currentTrack);
-      }
-      return;
-    }
-
-    // Determine next index
-    let nextIndex = state.currentIndex + 1;
-
-    // Handle end of queue
-    if (nextIndex >= state.queue.length) {
-      if (state.repeat === "all") {
-        console.log("[handleNext] Repeat All -> Loop to start");
-        nextIndex = 0;
-      } else {
-        console.log("[handleNext] End of queue -> Stop");
-        set({ status: "stopped", position: 0 });
-        await invoke("audio_stop");
-        return;
-      }
-    }
-
-    const nextTrack = state.queue[nextIndex];
-    console.log("[handleNext] Playing next:", nextIndex, nextTrack.title);
-
-    set({
-      currentTrack: nextTrack,
-      currentIndex: nextIndex,
-      status: "loading",
-      position: 0,
-    });
-
-    await playInternal(nextTrack);
-  };
-
   return {
     // Initial State
     status: "stopped",
@@ -172,7 +119,6 @@ export const useAudioStore = create<AudioStore>((set, get) => {
     _isDraggingSlider: false,
     _listenersInitialized: false,
     _lastProgressUpdate: 0,
-    _isTransitioning: false,
 
     // Player Actions
     play: async (track, newQueue?) => {
@@ -190,12 +136,18 @@ export const useAudioStore = create<AudioStore>((set, get) => {
       set({
         currentTrack: track,
         status: "loading",
-        queue,
-        currentIndex: index,
         position: 0,
       });
 
-      await playInternal(track);
+      try {
+        await invoke("audio_queue_set", {
+          items: queue,
+          startIndex: Math.max(index, 0),
+        });
+      } catch (e) {
+        console.error("Failed to play:", e);
+        set({ status: "stopped" });
+      }
     },
 
     pause: async () => {
@@ -210,31 +162,13 @@ export const useAudioStore = create<AudioStore>((set, get) => {
       await invoke("audio_stop");
     },
 
-    next: handleNext,
+    next: async () => {
+      await invokeQueue("audio_next");
+    },
 
+    // The engine restarts the current track instead when we are a few seconds in
     previous: async () => {
-      const state = get();
-      if (state.queue.length === 0) return;
-
-      if (state.position > 3000) {
-        await invoke("audio_seek", { positionMs: 0 });
-        set({ position: 0 });
-        return;
-      }
-
-      let prevIndex = state.currentIndex - 1;
-      if (prevIndex < 0) {
-        prevIndex = state.repeat === "all" ? state.queue.length - 1 : 0;
-      }
-
-      const prevTrack = state.queue[prevIndex];
-      set({
-        currentTrack: prevTrack,
-        currentIndex: prevIndex,
-        status: "loading",
-        position: 0,
-      });
-      await playInternal(prevTrack);
+      await invokeQueue("audio_previous");
     },
 
     seek: async (positionMs) => {
@@ -268,94 +202,66 @@ export const useAudioStore = create<AudioStore>((set, get) => {
 
     // Queue Actions
     toggleQueue: () => set((s) => ({ isQueueOpen: !s.isQueueOpen })),
-    toggleShuffle: () => set((s) => ({ shuffle: !s.shuffle })),
-    toggleRepeat: () =>
-      set((s) => ({
-        repeat: s.repeat === "off" ? "all" : s.repeat === "all" ? "one" : "off",
-      })),
-
-    addToQueue: (track) =>
-      set((s) => {
-        const existingIndex = s.queue.findIndex((t) => t.id === track.id);
-        if (existingIndex !== -1) {
-          // Track exists - move it to the end
-          const newQueue = [...s.queue];
-          const [removed] = newQueue.splice(existingIndex, 1);
-          newQueue.push(removed);
-          // Adjust currentIndex if needed
-          let newIndex = s.currentIndex;
-          if (existingIndex < s.currentIndex) {
-            newIndex--;
-          }
-          return { queue: newQueue, currentIndex: newIndex };
-        }
-        return { queue: [...s.queue, track] };
-      }),
-
-    playNext: (track) =>
-      set((s) => {
-        const existingIndex = s.queue.findIndex((t) => t.id === track.id);
-        const targetIndex = s.currentIndex + 1;
-
-        if (existingIndex !== -1) {
-          // Track exists - move it to play next position
-          if (existingIndex === targetIndex) {
-            return s; // Already in the right position
-          }
-          const newQueue = [...s.queue];
-          const [removed] = newQueue.splice(existingIndex, 1);
-          // Calculate insertion point after removal
-          const insertAt =
-            existingIndex < targetIndex ? targetIndex - 1 : targetIndex;
-          newQueue.splice(insertAt, 0, removed);
-          // Adjust currentIndex if needed
-          let newIndex = s.currentIndex;
-          if (existingIndex < s.currentIndex && insertAt >= s.currentIndex) {
-            newIndex--;
-          } else if (
-            existingIndex > s.currentIndex &&
-            insertAt <= s.currentIndex
-          ) {
-            newIndex++;
-          }
-          return { queue: newQueue, currentIndex: newIndex };
-        }
+    toggleShuffle: () => {
+      invokeQueue("audio_set_shuffle", { enabled: !get().shuffle });
+    },
+    toggleRepeat: () => {
+      const repeat = get().repeat;
+      const mode: RepeatMode =
+        repeat === "off" ? "all" : repeat === "all" ? "one" : "off";
+      invokeQueue("audio_set_repeat", { mode });
+    },
 
-        // Track doesn't exist - insert at play next position
-        const newQueue = [...s.queue];
-        newQueue.splice(targetIndex, 0, track);
-        return { queue: newQueue };
-      }),
-
-    removeFromQueue: (trackId) =>
-      set((s) => {
-        const newQueue = s.queue.filter((t) => t.id !== trackId);
-        let newIndex = s.currentIndex;
-        const removedIndex = s.queue.findIndex((t) => t.id === trackId);
-        if (removedIndex !== -1 && removedIndex < s.currentIndex) {
-          newIndex--;
-        }
-        return { queue: newQueue, currentIndex: newIndex };
-      }),
-
-    reorderQueue: (newQueue) =>
-      set((s) => {
-        const currentTrackId = s.currentTrack?.id;
-        const newIndex = newQueue.findIndex((t) => t.id === currentTrackId);
-        return {
-          queue: newQueue,
-          currentIndex: newIndex !== -1 ? newIndex : s.currentIndex,
-        };
-      }),
-
-    clearQueue: () =>
-      set((s) => {
-        if (!s.currentTrack) return { queue: [], currentIndex: -1 };
-        return {
-          queue: [s.currentTrack],
-          currentIndex: 0,
-        };
-      }),
+    addToQueue: (track) => {
+      const s = get();
+      const existingIndex = s.queue.findIndex((t) => t.id === track.id);
+      if (existingIndex !== -1) {
+        // Track exists - move it to the end
+        invokeQueue("audio_queue_move", {
+          from: existingIndex,
+          to: s.queue.length - 1,
+        });
+        return;
+      }
+      invokeQueue("audio_queue_insert", { items: [track], index: null });
+    },
+
+    playNext: (track) => {
+      const s = get();
+      const existingIndex = s.queue.findIndex((t) => t.id === track.id);
+      const targetIndex = s.currentIndex + 1;
+
+      if (existingIndex !== -1) {
+        // Track exists - move it to play next position
+        if (existingIndex === targetIndex) return;
+        const insertAt =
+          existingIndex < targetIndex ? targetIndex - 1 : targetIndex;
+        invokeQueue("audio_queue_move", { from: existingIndex, to: insertAt });
+        return;
+      }
+
+      invokeQueue("audio_queue_insert", {
+        items: [track],
+        index: targetIndex,
+      });
+    },
+
+    removeFromQueue: (index) => {
+      invokeQueue("audio_queue_remove", { index });
+    },
+
+    moveInQueue: (from, to) => {
+      invokeQueue("audio_queue_move", { from, to });
+    },
+
+    jumpTo: (index) => {
+      set({ status: "loading", position: 0 });
+      invokeQueue("audio_queue_jump", { index });
+    },
+
+    clearQueue: () => {
+      invokeQueue("audio_queue_clear");
+    },
 
     // Progress Actions
     setPosition: (position) => set({ position }),
@@ -407,70 +313,31 @@ export const useAudioStore = create<AudioStore>((set, get) => {
             duration: s.duration_ms,
             _lastProgressUpdate: now,
           });
-
-          // Automatic Crossfade Logic
-          const crossfadeMs =
-            useSettingsStore.getState().crossfadeDuration || 0;
-          if (crossfadeMs > 0 && s.duration_ms > 0) {
-            const threshold = s.duration_ms - crossfadeMs;
-
-            // Check if we reached the transition point
-            // Also ensure we aren't already transitioning
-            if (s.position_ms >= threshold && !state._isTransitioning) {
-              // Verify we have a next track
-              const hasNext =
-                state.queue.length > 0 &&
-                (state.repeat !== "off" ||
-                  state.currentIndex < state.queue.length - 1);
-              if (hasNext) {
-                console.log(
-                  "Triggering automatic crossfade",
-                  s.position_ms,
-                  threshold
-                );
-                set({ _isTransitioning: true });
-                get().next();
-              }
-            }
-
-            // Reset transition flag if we are at the beginning of a track
-            if (state._isTransitioning && s.position_ms < threshold * 0.5) {
-              set({ _isTransitioning: false });
-            }
-          }
         }
       );
 
+      // The engine advances the queue itself; this only fires at the very end
       const unlistenFinished = listen("audio-playback-finished", () => {
-        handleNext();
-      });
-
-      // Media Control Events
-      const unlistenMediaPlay = listen("media-play", () => {
-        get().resume();
-      });
-
-      const unlistenMediaPause = listen("media-pause", () => {
-        get().pause();
-      });
-
-      const unlistenMediaToggle = listen("media-toggle", () => {
-        const s = get();
-        if (s.status === "playing") s.pause();
-        else s.resume();
-      });
-
-      const unlistenMediaNext = listen("media-next", () => {
-        get().next();
-      });
-
-      const unlistenMediaPrev = listen("media-prev", () => {
-        get().previous();
+        set({ status: "stopped", position: 0 });
       });
 
-      const unlistenMediaStop = listen("media-stop", () => {
-        get().stop();
-      });
+      const unlistenQueue = listen<AudioQueuePayload>(
+        "audio-queue-changed",
+        (event) => {
+          const q = event.payload;
+          set((state) => ({
+            queue: q.items,
+            currentIndex: q.current_index ?? -1,
+            shuffle: q.shuffle,
+            repeat: q.repeat,
+            // Keep showing the last track when the queue has moved past it
+            currentTrack:
+              q.current_index !== null
+                ? q.items[q.current_index]
+                : state.currentTrack,
+          }));
+        }
+      );
 
       const unlistenError = listen<string>(
         "audio-playback-error",
@@ -505,15 +372,9 @@ export const useAudioStore = create<AudioStore>((set, get) => {
         unlistenState.then((f) => f());
         unlistenProgress.then((f) => f());
         unlistenFinished.then((f) => f());
+        unlistenQueue.then((f) => f());
         unlistenError.then((f) => f());
 
-        unlistenMediaPlay.then((f) => f());
-        unlistenMediaPause.then((f) => f());
-        unlistenMediaToggle.then((f) => f());
-        unlistenMediaNext.then((f) => f());
-        unlistenMediaPrev.then((f) => f());
-        unlistenMediaStop.then((f) => f());
-
         set({ _listenersInitialized: false });
       };
     },
@@ -553,7 +414,8 @@ export const getQueueActions = () => {
     addToQueue: s.addToQueue,
     playNext: s.playNext,
     removeFromQueue: s.removeFromQueue,
-    reorderQueue: s.reorderQueue,
+    moveInQueue: s.moveInQueue,
+    jumpTo: s.jumpTo,
     toggleQueue: s.toggleQueue,
     toggleShuffle: s.toggleShuffle,
     toggleRepeat: s.toggleRepeat,
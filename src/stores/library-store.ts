@@ -9,7 +9,7 @@ import {
   createPlaylist,
   updatePlaylist,
   addTrackToPlaylist,
-  reorderPlaylist,
+  movePlaylistEntry,
   deletePlaylist,
 } from "@/lib/api";
 import { toast } from "sonner";
@@ -39,7 +39,11 @@ interface LibraryState {
   ) => Promise<boolean>;
   deletePlaylist: (id: number) => Promise<boolean>;
   addToPlaylist: (playlistId: number, trackId: number) => Promise<void>;
-  reorderPlaylist: (id: number, newOrder: number[]) => Promise<void>;
+  movePlaylistEntry: (
+    playlistId: number,
+    entryId: number,
+    index: number
+  ) => Promise<void>;
 }
 
 export const useLibraryStore = create<LibraryState>((set, get) => ({
@@ -145,9 +149,9 @@ export const useLibraryStore = create<LibraryState>((set, get) => ({
     }
   },
 
-  reorderPlaylist: async (id, newOrder) => {
+  movePlaylistEntry: async (playlistId, entryId, index) => {
     try {
-      await reorderPlaylist(id, newOrder);
+      await movePlaylistEntry(playlistId, entryId, index);
       // No need to refresh global list for reordering tracks inside a playlist
     } catch (error) {
       console.error("Failed to reorder playlist:", error);
@@ -14,6 +14,9 @@ const getStore = async () => {
   return load(`settings_${state.currentProfileId}.json`);
 };
 
+/** Which decoder plays files; "auto" picks per format */
+export type DecoderBackend = "auto" | "symphonia" | "ffmpeg";
+
 export interface SidebarItem {
   id: string;
   hidden: boolean;
@@ -28,6 +31,7 @@ interface SettingsState {
   isLoading: boolean;
   currentProfileId: string | null;
   crossfadeDuration: number; // Audio
+  decoderBackend: DecoderBackend;
 
   // Behavior
   closeToTray: boolean;
@@ -58,6 +62,7 @@ interface SettingsActions {
   setAudioDevice: (deviceName: string) => void;
   refreshAudioDevices: () => Promise<void>;
   setCrossfadeDuration: (duration: number) => void;
+  setDecoderBackend: (backend: DecoderBackend) => void;
 
   // Behavior Actions
   setCloseToTray: (enabled: boolean) => void;
@@ -84,6 +89,7 @@ export const useSettingsStore = create<SettingsState & SettingsActions>(
     isLoading: true,
     currentProfileId: null,
     crossfadeDuration: 0,
+    decoderBackend: "auto",
     closeToTray: false,
     scanOnStartup: false,
     autoplay: false,
@@ -182,6 +188,14 @@ export const useSettingsStore = create<SettingsState & SettingsActions>(
       await store.save();
     },
 
+    setDecoderBackend: async (backend) => {
+      set({ decoderBackend: backend });
+      await invoke("audio_set_decoder_backend", { backend });
+      const store = await getStore();
+      await store.set("decoderBackend", backend);
+      await store.save();
+    },
+
     setCloseToTray: async (enabled) => {
       set({ closeToTray: enabled });
       const store = await getStore();
@@ -259,6 +273,7 @@ export const useSettingsStore = create<SettingsState & SettingsActions>(
         const libraryPaths = await getVal<string[]>("libraryPaths");
         const selectedDevice = await getVal<string>("selectedDevice");
         const crossfadeDuration = await getVal<number>("crossfadeDuration");
+        const decoderBackend = await getVal<DecoderBackend>("decoderBackend");
 
         const closeToTray = await getVal<boolean>("closeToTray");
         const scanOnStartup = await getVal<boolean>("scanOnStartup");
@@ -280,6 +295,7 @@ export const useSettingsStore = create<SettingsState & SettingsActions>(
           libraryPaths: libraryPaths ?? [],
           selectedDevice: selectedDevice ?? null,
           crossfadeDuration: crossfadeDuration ?? 0,
+          decoderBackend: decoderBackend ?? "auto",
           closeToTray: closeToTray ?? false,
           scanOnStartup: scanOnStartup ?? false,
           autoplay: autoplay ?? false,
@@ -311,12 +327,18 @@ export const useSettingsStore = create<SettingsState & SettingsActions>(
 
         if (selectedDevice) {
           await invoke("audio_set_device", { deviceName: selectedDevice });
+        } else {
+          await invoke("audio_restore_channel_mix");
         }
         if (typeof crossfadeDuration === "number") {
           await invoke("audio_set_crossfade", {
             durationMs: crossfadeDuration,
           });
         }
+        // Profiles without the setting still reset a previous profile's choice
+        await invoke("audio_set_decoder_backend", {
+          backend: decoderBackend ?? "auto",
+        });
 
         get().refreshAudioDevices();
 